@@ -24,6 +24,8 @@ fn compile_protobufs(out_dir: &Path) {
 
     tonic_build::configure()
         .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
+        .type_attribute(".engine", "#[serde(default)]")
+        .file_descriptor_set_path(proto_dir.join("descriptor.bin"))
         .out_dir(&proto_dir)
         .compile_with_config(
             cfg,
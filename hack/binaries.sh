@@ -49,6 +49,42 @@ mkdir -p /binaries/plugins \
 && cp -v ${OUT_DIR}/engine /binaries/engine \
 && cp -v ${OUT_DIR}/generator-acpi /binaries/generators/acpi-${OS}-${ARCH} \
 && cp -v ${OUT_DIR}/generator-disk /binaries/generators/disk-${OS}-${ARCH} \
+&& cp -v ${OUT_DIR}/plugin-accounts /binaries/plugins/accounts-${OS}-${ARCH} \
+&& cp -v ${OUT_DIR}/plugin-file /binaries/plugins/file-${OS}-${ARCH} \
+&& cp -v ${OUT_DIR}/plugin-hostname /binaries/plugins/hostname-${OS}-${ARCH} \
+&& cp -v ${OUT_DIR}/plugin-kmod /binaries/plugins/kmod-${OS}-${ARCH} \
 && cp -v ${OUT_DIR}/plugin-mount /binaries/plugins/mount-${OS}-${ARCH} \
+&& cp -v ${OUT_DIR}/plugin-network /binaries/plugins/network-${OS}-${ARCH} \
 && cp -v ${OUT_DIR}/plugin-resolver /binaries/plugins/resolver-${OS}-${ARCH} \
+&& cp -v ${OUT_DIR}/plugin-sysctl /binaries/plugins/sysctl-${OS}-${ARCH} \
+&& cp -v ${OUT_DIR}/plugin-time /binaries/plugins/time-${OS}-${ARCH} \
 && find /binaries -type f -exec strip -v {} \;
+
+# The eBPF generators need to lock memory for their maps.
+for generator in acpi disk; do
+  cat <<EOF >/binaries/generators/${generator}-${OS}-${ARCH}.yaml
+exec:
+  limits:
+    memlock: unlimited
+EOF
+done
+
+# The resolver runs as a user of its own, and only writes resolv.conf, which it replaces through a
+# temporary file next to it. It is rendered to a directory of the resolver's, which
+# /etc/resolv.conf links to, so that /etc stays read-only.
+cat <<EOF >/binaries/plugins/resolver-${OS}-${ARCH}.yaml
+exec:
+  environment:
+    COSI_RESOLV_CONF: /run/resolver/resolv.conf
+sandbox:
+  uid: 953
+  gid: 953
+  capabilities: []
+  no_new_privs: true
+  read_only_root: true
+  directories:
+    - /run/resolver
+EOF
+
+mkdir -p /binaries/etc \
+&& ln -sfv /run/resolver/resolv.conf /binaries/etc/resolv.conf
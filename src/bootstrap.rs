@@ -0,0 +1,192 @@
+//! The bootstrap contract between the engine and the processes it starts.
+//!
+//! A process learns where to reach the engine and the runtime, which service it runs as, and
+//! the token that identifies it, from the first of the following sources that is present:
+//!
+//! 1. `COSI_BOOTSTRAP_FD`: an inherited file descriptor to read a JSON `Bootstrap` from. This is
+//!    what the engine uses, the descriptor is the read end of a pipe.
+//! 2. `COSI_BOOTSTRAP_FILE`: the path of a JSON `Bootstrap` file (e.g. to run a plugin by hand).
+//! 3. `COSI_ENGINE_SOCKET`, `COSI_RUNTIME_SOCKET`, `COSI_RUNTIME_ADDRESS`, `COSI_SERVICE` and
+//!    `COSI_TOKEN`: one variable per field. The engine sets all of them but `COSI_TOKEN`, so that
+//!    the token does not show up in `/proc/<pid>/environ`.
+//!
+//! A JSON `Bootstrap` looks like:
+//!
+//! ```json
+//! {
+//!   "engine_socket": "/system/engine.sock",
+//!   "runtime_socket": "/system/runtime.sock",
+//!   "runtime_address": "127.0.0.1:50000",
+//!   "service": "mount",
+//!   "token": "5f0e..."
+//! }
+//! ```
+//!
+//! Standard input is not used, and is left to the process.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::{env, fmt, fs};
+use tonic::metadata::AsciiMetadataValue;
+
+pub static BOOTSTRAP_FD: &str = "COSI_BOOTSTRAP_FD";
+pub static BOOTSTRAP_FILE: &str = "COSI_BOOTSTRAP_FILE";
+pub static ENGINE_SOCKET: &str = "COSI_ENGINE_SOCKET";
+pub static RUNTIME_SOCKET: &str = "COSI_RUNTIME_SOCKET";
+pub static RUNTIME_ADDRESS: &str = "COSI_RUNTIME_ADDRESS";
+pub static SERVICE: &str = "COSI_SERVICE";
+pub static TOKEN: &str = "COSI_TOKEN";
+
+/// The metadata key that carries the token in requests.
+pub static AUTHORIZATION: &str = "authorization";
+
+/// Bootstrap is what a process started by the engine needs to know to take part in the system.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bootstrap {
+    /// The path of the engine's Unix socket.
+    pub engine_socket: String,
+    /// The path of the runtime's Unix socket.
+    pub runtime_socket: String,
+    /// The TCP address of the runtime, if it is reached over TCP instead of `runtime_socket`.
+    #[serde(default)]
+    pub runtime_address: Option<String>,
+    /// The name of the service the process runs as.
+    pub service: String,
+    /// Identifies the service to the engine, which rejects requests without it. It is generated
+    /// every time the service is started.
+    pub token: String,
+}
+
+#[derive(Debug)]
+pub enum BootstrapError {
+    IoError(std::io::Error),
+    JsonError(serde_json::Error),
+    /// A variable is missing or invalid.
+    VariableError(&'static str),
+}
+
+impl fmt::Display for BootstrapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BootstrapError::IoError(err) => write!(f, "failed to read bootstrap: {}", err),
+            BootstrapError::JsonError(err) => write!(f, "invalid bootstrap: {}", err),
+            BootstrapError::VariableError(name) => {
+                write!(f, "missing or invalid variable: {}", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BootstrapError {}
+
+impl From<std::io::Error> for BootstrapError {
+    fn from(error: std::io::Error) -> BootstrapError {
+        BootstrapError::IoError(error)
+    }
+}
+
+impl From<serde_json::Error> for BootstrapError {
+    fn from(error: serde_json::Error) -> BootstrapError {
+        BootstrapError::JsonError(error)
+    }
+}
+
+impl Bootstrap {
+    /// Reads the bootstrap of the calling process. See the module documentation for the
+    /// sources, and their order.
+    ///
+    /// The inherited descriptor is consumed and closed, so this must be called at most once.
+    pub fn load() -> Result<Bootstrap, BootstrapError> {
+        if let Ok(fd) = env::var(BOOTSTRAP_FD) {
+            env::remove_var(BOOTSTRAP_FD);
+
+            let fd: RawFd = match fd.parse() {
+                Ok(fd) if fd > 2 => fd,
+                _ => return Err(BootstrapError::VariableError(BOOTSTRAP_FD)),
+            };
+
+            // N.B.: `File` takes ownership of the descriptor, and closes it when dropped.
+            let mut file = unsafe { File::from_raw_fd(fd) };
+
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+
+            return Bootstrap::parse(&contents);
+        }
+
+        if let Ok(path) = env::var(BOOTSTRAP_FILE) {
+            let contents = fs::read_to_string(path)?;
+
+            return Bootstrap::parse(&contents);
+        }
+
+        Bootstrap::from_env()
+    }
+
+    pub fn parse(contents: &str) -> Result<Bootstrap, BootstrapError> {
+        Ok(serde_json::from_str(contents)?)
+    }
+
+    /// Reads the bootstrap from one variable per field.
+    pub fn from_env() -> Result<Bootstrap, BootstrapError> {
+        let var =
+            |name: &'static str| env::var(name).map_err(|_| BootstrapError::VariableError(name));
+
+        Ok(Bootstrap {
+            engine_socket: var(ENGINE_SOCKET)?,
+            runtime_socket: var(RUNTIME_SOCKET)?,
+            runtime_address: env::var(RUNTIME_ADDRESS).ok(),
+            service: var(SERVICE)?,
+            token: var(TOKEN)?,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, BootstrapError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Attaches the token to `request`.
+    pub fn authorize<T>(&self, request: &mut tonic::Request<T>) {
+        if let Ok(value) = AsciiMetadataValue::from_str(&format!("Bearer {}", self.token)) {
+            request.metadata_mut().insert(AUTHORIZATION, value);
+        }
+    }
+}
+
+/// Tokens holds the token issued to each service, as the engine checks them.
+#[derive(Debug, Clone, Default)]
+pub struct Tokens {
+    tokens: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl Tokens {
+    /// Records `token` as the token of `service`, replacing the one issued to a previous run.
+    pub fn issue(&self, service: &str, token: &str) {
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(service.to_owned(), token.to_owned());
+    }
+
+    /// Returns the service whose token `request` carries.
+    pub fn authenticate<T>(&self, request: &tonic::Request<T>) -> Result<String, tonic::Status> {
+        let token = request
+            .metadata()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| tonic::Status::unauthenticated("missing token"))?;
+
+        self.tokens
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, issued)| issued.as_str() == token)
+            .map(|(service, _)| service.clone())
+            .ok_or_else(|| tonic::Status::unauthenticated("invalid token"))
+    }
+}
@@ -1,6 +1,6 @@
 use clap::Clap;
 use cosi::{
-    spec::resource::{CreateOptions, CreateRequest, DestroyOptions, DestroyRequest},
+    spec::resource::{CreateRequest, DestroyOptions, DestroyRequest, Propagation},
     spec::resource::{Metadata, Resource, Spec},
     ResourceInstance,
 };
@@ -34,6 +34,9 @@ struct Create {
 struct Delete {
     #[clap(short)]
     filename: String,
+    /// Destroy dependents before their owners.
+    #[clap(long)]
+    foreground: bool,
 }
 
 #[tokio::main]
@@ -66,10 +69,13 @@ async fn main() {
                 let request = tonic::Request::new(CreateRequest {
                     resource: Some(Resource {
                         metadata: Some(Metadata {
+                            api: resource.api,
                             version: resource.version,
                             r#type: resource.r#type,
                             namespace: resource.namespace,
                             id: resource.id,
+                            labels: resource.labels,
+                            annotations: resource.annotations,
                             phase: "running".to_owned(),
                             ..Default::default()
                         }),
@@ -77,10 +83,9 @@ async fn main() {
                             proto_spec: vec![],
                             yaml_spec: spec.to_owned(),
                         }),
+                        status: None,
                     }),
-                    options: Some(CreateOptions {
-                        owner: String::from("system"),
-                    }),
+                    options: None,
                 });
 
                 let r = client.create(request).await;
@@ -103,6 +108,11 @@ async fn main() {
                 .await
                 .unwrap();
 
+            let propagation = match t.foreground {
+                true => Propagation::Foreground,
+                false => Propagation::Background,
+            };
+
             println!("Deleting {}", filename);
 
             for document in serde_yaml::Deserializer::from_str(&contents) {
@@ -114,11 +124,14 @@ async fn main() {
                 println!("Deleting {}: {}", resource.r#type, resource.id);
 
                 let request = tonic::Request::new(DestroyRequest {
+                    api: resource.api,
                     namespace: resource.namespace,
                     r#type: resource.r#type,
                     id: resource.id,
+                    // N.B.: Resources created by a user have no owner.
                     options: Some(DestroyOptions {
-                        owner: String::from("system"),
+                        owner: String::new(),
+                        propagation: propagation as i32,
                     }),
                 });
 
@@ -4,3 +4,14 @@ pub static PLUGINS: &str = "/system/plugins";
 pub static SOCKET_ENGINE: &str = "/system/engine.sock";
 pub static SOCKET_RUNTIME: &str = "/system/runtime.sock";
 pub static ADDRESS_RUNTIME: &str = "0.0.0.0:50000";
+pub static ADDRESS_RUNTIME_LOCAL: &str = "127.0.0.1:50000";
+// N.B.: These are constants rather than statics, so that the associated constants of the built-in
+// resource types can refer to them.
+pub const API: &str = "cosi.dev";
+pub const API_VERSION: &str = "v1alpha1";
+pub const NAMESPACE_SYSTEM: &str = "system";
+pub static CGROUP_ROOT: &str = "/sys/fs/cgroup/cosi";
+pub static RESOLV_CONF: &str = "/etc/resolv.conf";
+pub static ETC: &str = "/etc";
+pub static PROC_SYS: &str = "/proc/sys";
+pub static MODULES: &str = "/lib/modules";
@@ -0,0 +1,520 @@
+//! Controllers reconcile resources on behalf of plugins.
+//!
+//! A plugin implements `Controller` and hands it to `run`, which takes care of registering with
+//! the engine and the runtime, following reconcile events, retrying and shutting down:
+//!
+//! ```no_run
+//! use cosi::controller::{self, Context, Controller, Error};
+//! use cosi::spec::runtime::{ControllerInput, ControllerOutput};
+//!
+//! struct Example;
+//!
+//! #[tonic::async_trait]
+//! impl Controller for Example {
+//!     fn name(&self) -> String {
+//!         "example".to_owned()
+//!     }
+//!
+//!     fn inputs(&self) -> Vec<ControllerInput> {
+//!         vec![]
+//!     }
+//!
+//!     fn outputs(&self) -> Vec<ControllerOutput> {
+//!         vec![]
+//!     }
+//!
+//!     async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     controller::run(Example).await
+//! }
+//! ```
+
+use crate::bootstrap::Bootstrap;
+use crate::machinery::{plugin, runtime::client, state::PHASE_TEARING_DOWN};
+use crate::resource::{ResourceKind, TypedClient, TypedResource};
+use crate::spec::engine::Schema;
+use crate::spec::resource::{Resource, ResourceStatus};
+use crate::spec::runtime::{
+    controller_adapter_client::ControllerAdapterClient, ControllerInput, ControllerInputKind,
+    ControllerOutput, QueueReconcileRequest, ReconcileEventsRequest, RegisterControllerRequest,
+    RuntimeCreateRequest, RuntimeDestroyRequest, RuntimeGetRequest, RuntimeListRequest,
+    RuntimeUpdateRequest, RuntimeUpdateStatusRequest, StartRequest,
+};
+use std::future::Future;
+use std::time::Duration;
+use tonic::transport::Channel;
+use tonic::Code;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Controller reconciles the resources it is interested in.
+#[tonic::async_trait]
+pub trait Controller: Send {
+    /// The name the controller registers with. It is also the name of the plugin.
+    fn name(&self) -> String;
+
+    /// The resources that trigger a reconcile when they change.
+    fn inputs(&self) -> Vec<ControllerInput>;
+
+    /// The resources the controller writes. The status of the inputs is not an output, so a
+    /// controller that only reports the state of its inputs through their status has none.
+    fn outputs(&self) -> Vec<ControllerOutput>;
+
+    /// The schemas of the resource types the controller owns. They are registered with the
+    /// engine before the controller is started, and owned by the service the controller runs as,
+    /// so that the controller alone may report the status of their resources.
+    fn schemas(&self) -> Vec<Schema> {
+        vec![]
+    }
+
+    /// How long to wait for a reconcile event before reconciling anyway, e.g. to correct drift
+    /// in state the engine is not told about. By default, the controller only reconciles on
+    /// events.
+    fn resync_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Brings the system in line with the inputs. It is called on every reconcile event, and is
+    /// retried with a backoff if it fails. A controller that fails for some of its inputs should
+    /// still reconcile and report on the others before returning the error.
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error>;
+}
+
+impl ControllerInput {
+    /// Returns a strong input on every resource of type `T`.
+    pub fn strong<T: ResourceKind>() -> ControllerInput {
+        ControllerInput {
+            kind: ControllerInputKind::Strong as i32,
+            namespace: T::NAMESPACE.to_string(),
+            r#type: T::TYPE.to_string(),
+            id: None,
+            api: T::API.to_string(),
+            label_selector: String::new(),
+        }
+    }
+}
+
+/// Returns the resources that are not being torn down.
+pub fn active<T>(resources: Vec<TypedResource<T>>) -> Vec<TypedResource<T>> {
+    resources
+        .into_iter()
+        .filter(|resource| resource.metadata.phase != PHASE_TEARING_DOWN)
+        .collect()
+}
+
+/// Returns the error of the first failed action that `matches`, or an empty string, e.g. for the
+/// `error` field of a status.
+pub fn error<A, F>(results: &[(A, Result<(), String>)], matches: F) -> String
+where
+    F: Fn(&A) -> bool,
+{
+    results
+        .iter()
+        .find_map(|(action, result)| match (matches(action), result) {
+            (true, Err(err)) => Some(err.clone()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Context is what a controller reconciles with.
+pub struct Context {
+    pub bootstrap: Bootstrap,
+    /// The token the runtime issued to the controller.
+    pub token: String,
+    pub client: ControllerAdapterClient<Channel>,
+}
+
+impl Context {
+    /// Returns a client for typed resources.
+    pub fn typed(&self) -> TypedClient {
+        TypedClient::new(self.client.clone(), self.token.clone())
+    }
+
+    pub async fn get(
+        &mut self,
+        api: &str,
+        namespace: &str,
+        r#type: &str,
+        id: &str,
+    ) -> Result<Option<Resource>, tonic::Status> {
+        let request = RuntimeGetRequest {
+            controller_token: self.token.clone(),
+            namespace: namespace.to_owned(),
+            r#type: r#type.to_owned(),
+            id: id.to_owned(),
+            api: api.to_owned(),
+        };
+
+        match self.client.get(request).await {
+            Ok(response) => Ok(response.into_inner().resource),
+            Err(status) if status.code() == Code::NotFound => Ok(None),
+            Err(status) => Err(status),
+        }
+    }
+
+    pub async fn list(
+        &mut self,
+        api: &str,
+        namespace: &str,
+        r#type: &str,
+    ) -> Result<Vec<Resource>, tonic::Status> {
+        self.select(api, namespace, r#type, "").await
+    }
+
+    /// Returns the resources with labels matching `selector` (e.g. `tier=boot`).
+    pub async fn select(
+        &mut self,
+        api: &str,
+        namespace: &str,
+        r#type: &str,
+        selector: &str,
+    ) -> Result<Vec<Resource>, tonic::Status> {
+        let request = RuntimeListRequest {
+            controller_token: self.token.clone(),
+            namespace: namespace.to_owned(),
+            r#type: r#type.to_owned(),
+            api: api.to_owned(),
+            label_selector: selector.to_owned(),
+        };
+
+        let mut stream = self.client.list(request).await?.into_inner();
+
+        let mut resources = vec![];
+
+        while let Some(response) = stream.message().await? {
+            if let Some(resource) = response.resource {
+                resources.push(resource);
+            }
+        }
+
+        Ok(resources)
+    }
+
+    pub async fn create(&mut self, resource: Resource) -> Result<(), tonic::Status> {
+        let request = RuntimeCreateRequest {
+            controller_token: self.token.clone(),
+            resource: Some(resource),
+        };
+
+        self.client.create(request).await?;
+
+        Ok(())
+    }
+
+    pub async fn update(
+        &mut self,
+        current_version: &str,
+        resource: Resource,
+    ) -> Result<(), tonic::Status> {
+        let request = RuntimeUpdateRequest {
+            controller_token: self.token.clone(),
+            current_version: current_version.to_owned(),
+            new_resource: Some(resource),
+        };
+
+        self.client.update(request).await?;
+
+        Ok(())
+    }
+
+    /// Reports the status of a resource of a type the controller owns.
+    pub async fn update_status(
+        &mut self,
+        api: &str,
+        namespace: &str,
+        r#type: &str,
+        id: &str,
+        current_status_version: &str,
+        status: ResourceStatus,
+    ) -> Result<(), tonic::Status> {
+        let request = RuntimeUpdateStatusRequest {
+            controller_token: self.token.clone(),
+            namespace: namespace.to_owned(),
+            r#type: r#type.to_owned(),
+            id: id.to_owned(),
+            api: api.to_owned(),
+            current_status_version: current_status_version.to_owned(),
+            status: Some(status),
+        };
+
+        self.client.update_status(request).await?;
+
+        Ok(())
+    }
+
+    pub async fn destroy(
+        &mut self,
+        api: &str,
+        namespace: &str,
+        r#type: &str,
+        id: &str,
+    ) -> Result<(), tonic::Status> {
+        let request = RuntimeDestroyRequest {
+            controller_token: self.token.clone(),
+            namespace: namespace.to_owned(),
+            r#type: r#type.to_owned(),
+            id: id.to_owned(),
+            api: api.to_owned(),
+        };
+
+        self.client.destroy(request).await?;
+
+        Ok(())
+    }
+
+    /// Asks the runtime for another reconcile event.
+    pub async fn queue_reconcile(&mut self) -> Result<(), tonic::Status> {
+        let request = QueueReconcileRequest {
+            controller_token: self.token.clone(),
+        };
+
+        self.client.queue_reconcile(request).await?;
+
+        Ok(())
+    }
+}
+
+/// Runner runs a controller until it is asked to shut down.
+#[derive(Debug, Clone)]
+pub struct Runner {
+    pub bootstrap: Bootstrap,
+    /// How many times a failed step is attempted before it is given up on.
+    pub attempts: u32,
+    /// The delay before the first retry. It doubles with every retry, up to `max_backoff`.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Runner {
+    pub fn new(bootstrap: Bootstrap) -> Runner {
+        Runner {
+            bootstrap,
+            attempts: 5,
+            backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+
+    /// Runs `controller` until `SIGHUP`, `SIGINT` or `SIGTERM` is received.
+    pub async fn run<C>(&self, controller: C) -> Result<(), Error>
+    where
+        C: Controller,
+    {
+        self.run_until(
+            controller,
+            crate::unix::handle_signals(|| true, || true, || true, || false),
+        )
+        .await
+    }
+
+    /// Runs `controller` until `shutdown` completes.
+    pub async fn run_until<C, S>(&self, controller: C, shutdown: S) -> Result<(), Error>
+    where
+        C: Controller,
+        S: Future<Output = ()>,
+    {
+        tokio::select! {
+            result = self.serve(controller) => result,
+            _ = shutdown => {
+                println!("Shutting down");
+
+                Ok(())
+            }
+        }
+    }
+
+    async fn serve<C>(&self, mut controller: C) -> Result<(), Error>
+    where
+        C: Controller,
+    {
+        let name = controller.name();
+
+        self.retry("register plugin", || async {
+            match plugin::register(&self.bootstrap, name.clone()).await {
+                Ok(_) => Ok(()),
+                Err(status) if status.code() == Code::AlreadyExists => Ok(()),
+                Err(status) => Err(Error::from(status)),
+            }
+        })
+        .await?;
+
+        println!("Registered {}", name);
+
+        for schema in controller.schemas() {
+            let r#type = schema.r#type.clone();
+
+            self.retry("register schema", || async {
+                plugin::register_schema(&self.bootstrap, schema.clone())
+                    .await
+                    .map_err(Error::from)
+            })
+            .await?;
+
+            println!("Registered schema of {}", r#type);
+        }
+
+        let mut runtime = self
+            .retry("connect to the runtime", || async {
+                match &self.bootstrap.runtime_address {
+                    Some(address) => client::connect_runtime_tcp(address.clone()).await,
+                    None => client::connect_runtime(self.bootstrap.runtime_socket.clone()).await,
+                }
+                .map_err(|err| Error::from(err.to_string()))
+            })
+            .await?;
+
+        let request = RegisterControllerRequest {
+            controller_name: name.clone(),
+            inputs: controller.inputs(),
+            outputs: controller.outputs(),
+        };
+
+        let token = runtime
+            .register_controller(request)
+            .await?
+            .into_inner()
+            .controller_token;
+
+        let client = self.connect_adapter().await?;
+
+        let mut ctx = Context {
+            bootstrap: self.bootstrap.clone(),
+            token,
+            client,
+        };
+
+        // N.B.: Subscribe before starting the runtime so that no event is missed.
+        let request = ReconcileEventsRequest {
+            controller_token: ctx.token.clone(),
+        };
+
+        let mut events = ctx.client.reconcile_events(request).await?.into_inner();
+
+        runtime.start(StartRequest {}).await?;
+
+        let resync = controller.resync_interval();
+
+        loop {
+            let message = match resync {
+                Some(interval) => match tokio::time::timeout(interval, events.message()).await {
+                    Ok(message) => message,
+                    Err(_) => {
+                        self.reconcile(&mut controller, &mut ctx).await;
+
+                        continue;
+                    }
+                },
+                None => events.message().await,
+            };
+
+            match message {
+                Ok(Some(_)) => {
+                    self.reconcile(&mut controller, &mut ctx).await;
+
+                    continue;
+                }
+                Ok(None) => println!("Reconcile events ended, resubscribing"),
+                Err(status) => println!("Reconcile events failed, resubscribing: {}", status),
+            }
+
+            events = self
+                .retry("subscribe to reconcile events", || async {
+                    let request = ReconcileEventsRequest {
+                        controller_token: ctx.token.clone(),
+                    };
+
+                    let mut client = ctx.client.clone();
+
+                    Ok(client.reconcile_events(request).await?.into_inner())
+                })
+                .await?;
+        }
+    }
+
+    async fn reconcile<C>(&self, controller: &mut C, ctx: &mut Context)
+    where
+        C: Controller,
+    {
+        let mut backoff = self.backoff;
+
+        for attempt in 1..=self.attempts {
+            match controller.reconcile(ctx).await {
+                Ok(()) => return,
+                Err(err) => println!(
+                    "Reconcile failed (attempt {}/{}): {}",
+                    attempt, self.attempts, err
+                ),
+            }
+
+            if attempt < self.attempts {
+                tokio::time::sleep(backoff).await;
+                backoff = std::cmp::min(backoff * 2, self.max_backoff);
+            }
+        }
+
+        // Wait for the next event, instead of failing the controller.
+        println!("Giving up on reconcile until the next event");
+    }
+
+    async fn connect_adapter(&self) -> Result<ControllerAdapterClient<Channel>, Error> {
+        self.retry("connect to the runtime adapter", || async {
+            match &self.bootstrap.runtime_address {
+                Some(address) => client::connect_adapter_tcp(address.clone()).await,
+                None => client::connect_adapter(self.bootstrap.runtime_socket.clone()).await,
+            }
+            .map_err(|err| Error::from(err.to_string()))
+        })
+        .await
+    }
+
+    async fn retry<T, F, Fut>(&self, what: &str, mut f: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+
+        loop {
+            match f().await {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt >= self.attempts => return Err(err),
+                Err(err) => println!(
+                    "Failed to {} (attempt {}/{}): {}",
+                    what, attempt, self.attempts, err
+                ),
+            }
+
+            tokio::time::sleep(backoff).await;
+
+            backoff = std::cmp::min(backoff * 2, self.max_backoff);
+            attempt += 1;
+        }
+    }
+}
+
+/// Loads the bootstrap and runs `controller` until the process is asked to shut down. The
+/// process exits with a non-zero status if the controller fails.
+pub async fn run<C>(controller: C)
+where
+    C: Controller,
+{
+    let bootstrap = match Bootstrap::load() {
+        Ok(bootstrap) => bootstrap,
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(1)
+        }
+    };
+
+    if let Err(err) = Runner::new(bootstrap).run(controller).await {
+        println!("{}", err);
+        std::process::exit(1)
+    }
+}
@@ -4,26 +4,68 @@ use nix::unistd::Pid;
 #[tokio::main]
 #[cfg(unix)]
 async fn main() {
+    // The tokens issued to the services, which the engine checks.
+    let tokens = cosi::bootstrap::Tokens::default();
+
+    let engine_tokens = tokens.clone();
+
     tokio::spawn(async {
         println!("Listening on {:?}", cosi::consts::SOCKET_ENGINE.to_owned());
 
-        let service = cosi::machinery::engine::v1alpha1::EngineService::default();
+        let service = cosi::machinery::engine::v1alpha1::EngineService::new(engine_tokens);
 
         service.serve(cosi::consts::SOCKET_ENGINE.to_owned()).await
     });
 
-    let (tx, reaper) = unix::process::reaper::new();
+    // N.B.: When running as pid 1, orphans are reparented to the engine regardless.
+    if Pid::this() != Pid::from_raw(1) {
+        if let Err(err) = unix::process::tree::subreaper() {
+            println!("Failed to become a subreaper: {:?}", err)
+        }
+    }
+
+    let reaper = unix::process::reaper::new();
+
+    // Attribute processes to services periodically, so that descendants can still be attributed
+    // once their parents have exited.
+    let tracker = reaper.tracker.clone();
+
+    std::thread::spawn(move || loop {
+        if let Err(err) = tracker.refresh() {
+            println!("Failed to refresh process tree: {:?}", err)
+        }
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    });
+
+    // Log the resource usage of the services periodically.
+    let tracker = reaper.tracker.clone();
+
+    std::thread::spawn(move || loop {
+        std::thread::sleep(std::time::Duration::from_secs(60));
+
+        for service in tracker.accounted() {
+            match tracker.usage(&service) {
+                Ok(Some(usage)) => println!("<{}>: usage: {}", service, usage),
+                Ok(None) => (),
+                Err(err) => println!("<{}>: failed to read usage: {:?}", service, err),
+            }
+        }
+    });
+
     let r1 = reaper.clone();
     let r2 = reaper.clone();
     let r3 = reaper.clone();
 
     tokio::spawn(async move {
-        match cosi::machinery::runtime::load(cosi::consts::SOCKET_ENGINE.to_owned(), r1) {
+        let socket = cosi::consts::SOCKET_ENGINE.to_owned();
+
+        match cosi::machinery::runtime::load(socket.clone(), r1, tokens.clone()) {
             Ok(_) => println!("Loaded runtime"),
             Err(err) => println!("Failed to load runtime: {:?}", err),
         };
 
-        match cosi::machinery::generator::load(cosi::consts::SOCKET_ENGINE.to_owned(), r2) {
+        match cosi::machinery::generator::load(socket.clone(), r2, tokens.clone()) {
             Ok(generators) => {
                 for generator in generators.iter() {
                     println!("Loaded {:?}", generator);
@@ -32,7 +74,7 @@ async fn main() {
             Err(err) => println!("Failed to load generators: {:?}", err),
         };
 
-        match cosi::machinery::plugin::load(cosi::consts::SOCKET_ENGINE.to_owned(), r3) {
+        match cosi::machinery::plugin::load(socket, r3, tokens) {
             Ok(plugins) => {
                 for plugin in plugins.iter() {
                     println!("Loaded {:?}", plugin);
@@ -59,8 +101,24 @@ async fn main() {
             false
         },
         || {
-            if let Err(err) = reaper.reap(Pid::from_raw(-1), &tx) {
-                println!("Failed to reap: {:?}", err)
+            // N.B.: Refresh before reaping, `/proc` is needed to attribute the processes.
+            if let Err(err) = reaper.tracker.refresh() {
+                println!("Failed to refresh process tree: {:?}", err)
+            }
+
+            match reaper.reap(Pid::from_raw(-1)) {
+                Ok(exits) => {
+                    for exit in exits.iter().filter(|exit| exit.terminated()) {
+                        match reaper.tracker.forget(exit.pid) {
+                            Some(origin) if !origin.main => {
+                                println!("Reaped orphan of {}: {}", origin.service, exit)
+                            }
+                            Some(_) => (),
+                            None => println!("Reaped orphan of unknown origin: {}", exit),
+                        }
+                    }
+                }
+                Err(err) => println!("Failed to reap: {:?}", err),
             };
 
             false
@@ -1,9 +1,9 @@
+use cosi::bootstrap::Bootstrap;
 use cosi_probes::disk::Event as RawEvent;
 use futures::stream::StreamExt;
 use redbpf::load::{Loaded, Loader};
 use std::boxed::Box;
 use std::collections::HashMap;
-use std::io::{stdin, Read};
 use std::ptr;
 use std::sync::{Arc, Mutex};
 use tokio_02::runtime::Runtime;
@@ -12,10 +12,13 @@ use tokio_02::signal;
 type Acc = Arc<Mutex<HashMap<i32, RawEvent>>>;
 
 fn main() {
-    let mut buffer = String::new();
-    stdin().read_to_string(&mut buffer).unwrap();
-
-    let _socket = buffer.as_str().to_owned();
+    let _bootstrap = match Bootstrap::load() {
+        Ok(bootstrap) => bootstrap,
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(1)
+        }
+    };
 
     let acc: Acc = Arc::new(Mutex::new(HashMap::new()));
 
@@ -1,10 +1,20 @@
+pub mod bootstrap;
 pub mod consts;
+pub mod controller;
 pub mod machinery;
+pub mod resource;
+pub mod schema;
+pub mod selector;
 pub mod unix;
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 pub mod spec {
+    /// The encoded `FileDescriptorSet` of the specification.
+    pub static FILE_DESCRIPTOR_SET: &[u8] =
+        include_bytes!(concat!(env!("OUT_DIR"), "/proto/descriptor.bin"));
+
     pub mod engine {
         include!(concat!(env!("OUT_DIR"), "/proto/engine.rs"));
     }
@@ -25,5 +35,9 @@ pub struct ResourceInstance {
     pub r#type: String,
     pub namespace: String,
     pub id: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
     pub spec: Box<serde_yaml::Value>,
 }
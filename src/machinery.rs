@@ -1,3 +1,5 @@
+pub mod state;
+
 pub mod engine {
     pub mod client {
         use crate::spec::engine::engine_client::EngineClient;
@@ -14,8 +16,7 @@ pub mod engine {
                 .connect_with_connector(service_fn(move |_: Uri| {
                     UnixStream::connect(socket.clone())
                 }))
-                .await
-                .unwrap();
+                .await?;
 
             Ok(EngineClient::new(channel))
         }
@@ -23,14 +24,18 @@ pub mod engine {
 
     pub mod v1alpha1 {
         use crate::{
+            bootstrap::Tokens,
+            machinery::state::{Registry, StateService},
             spec::engine::{
                 engine_server::{Engine, EngineServer},
-                Plugin, RegisterResponse,
+                Plugin, RegisterResponse, RegisterSchemaResponse, Schema,
             },
+            spec::resource::{namespaces_server::NamespacesServer, state_server::StateServer},
             unix,
         };
         use std::{
             fs,
+            os::unix::fs::PermissionsExt,
             path::Path,
             sync::{Arc, Mutex},
         };
@@ -40,9 +45,25 @@ pub mod engine {
         #[derive(Default, Clone)]
         pub struct EngineService {
             plugins: Arc<Mutex<Vec<Plugin>>>,
+            registry: Registry,
+            tokens: Tokens,
         }
 
         impl EngineService {
+            /// Returns an engine that accepts requests carrying one of `tokens`.
+            pub fn new(tokens: Tokens) -> EngineService {
+                EngineService {
+                    tokens,
+                    ..Default::default()
+                }
+            }
+
+            /// Returns the State service that validates specs against the registered schemas, and
+            /// authenticates services with the same tokens as the engine.
+            pub fn state(&self) -> StateService {
+                StateService::new(self.registry.clone(), self.tokens.clone())
+            }
+
             pub async fn serve(self, socket: String) {
                 if Path::new(&socket).exists() {
                     fs::remove_file(&socket).expect("failed to remove socket");
@@ -50,11 +71,23 @@ pub mod engine {
 
                 let s = socket.clone();
 
+                let state = self.state();
+
                 let handle = tokio::spawn(async move {
-                    match unix::UnixIncoming::bind(s) {
+                    // N.B.: Services that run as other users connect too. What a request may do
+                    // is decided by the token it carries, not by who can connect.
+                    let incoming = unix::UnixIncoming::bind(&s).and_then(|socket| {
+                        fs::set_permissions(&s, fs::Permissions::from_mode(0o666))?;
+
+                        Ok(socket)
+                    });
+
+                    match incoming {
                         Ok(socket) => {
                             match Server::builder()
                                 .add_service(EngineServer::new(self))
+                                .add_service(StateServer::new(state.clone()))
+                                .add_service(NamespacesServer::new(state))
                                 .serve_with_incoming(socket)
                                 .await
                             {
@@ -76,6 +109,8 @@ pub mod engine {
                 &self,
                 request: Request<Plugin>,
             ) -> Result<Response<RegisterResponse>, Status> {
+                self.tokens.authenticate(&request)?;
+
                 let request_plugin = request.into_inner();
 
                 let mutex = self.plugins.clone();
@@ -94,17 +129,59 @@ pub mod engine {
 
                 Ok(Response::new(RegisterResponse {}))
             }
+
+            async fn register_schema(
+                &self,
+                request: Request<Schema>,
+            ) -> Result<Response<RegisterSchemaResponse>, Status> {
+                let service = self.tokens.authenticate(&request)?;
+
+                let mut schema = request.into_inner();
+
+                // N.B.: A service registers the types it owns, it cannot register them on behalf
+                // of another.
+                if schema.owner.is_empty() {
+                    schema.owner = service;
+                } else if schema.owner != service {
+                    return Err(Status::permission_denied(format!(
+                        "cannot register a schema owned by {:?}",
+                        schema.owner
+                    )));
+                }
+
+                match self.registry.register(&schema) {
+                    Ok(()) => Ok(Response::new(RegisterSchemaResponse {})),
+                    Err(err) => Err(Status::new(
+                        Code::InvalidArgument,
+                        format!("invalid schema for {:?}: {}", schema.r#type, err),
+                    )),
+                }
+            }
         }
     }
 }
 
 pub mod runtime {
+    use crate::bootstrap::Tokens;
     use crate::consts;
-    use crate::unix::process::reaper::Reaper;
+    use crate::unix::process::{reaper::Reaper, service::Service};
+
+    pub fn load(
+        socket: String,
+        reaper: Reaper,
+        tokens: Tokens,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut service = Service::load(consts::RUNTIME.to_owned())?;
+
+        // N.B.: The runtime is not built from this repository, and is told where to listen with
+        // an argument rather than through its bootstrap.
+        service.args.splice(
+            0..0,
+            vec!["--address".to_owned(), consts::ADDRESS_RUNTIME.to_owned()],
+        );
 
-    pub fn load(socket: String, reaper: Reaper) -> Result<(), Box<dyn std::error::Error>> {
         tokio::spawn(async {
-            crate::unix::process::monitor(consts::RUNTIME.to_owned(), socket, reaper)
+            crate::unix::process::monitor(service, socket, reaper, tokens)
                 .await
                 .unwrap();
         });
@@ -113,6 +190,7 @@ pub mod runtime {
     }
 
     pub mod client {
+        use crate::spec::resource::namespaces_client::NamespacesClient;
         use crate::spec::resource::state_client::StateClient;
         use crate::spec::runtime::controller_adapter_client::ControllerAdapterClient;
         use crate::spec::runtime::controller_runtime_client::ControllerRuntimeClient;
@@ -129,8 +207,7 @@ pub mod runtime {
                 .connect_with_connector(service_fn(move |_: Uri| {
                     UnixStream::connect(socket.clone())
                 }))
-                .await
-                .unwrap();
+                .await?;
 
             Ok(StateClient::new(channel))
         }
@@ -140,11 +217,25 @@ pub mod runtime {
         ) -> Result<StateClient<tonic::transport::Channel>, Box<dyn std::error::Error>> {
             let address = format!("http://{}", address);
 
-            let client = StateClient::connect(address).await.unwrap();
+            let client = StateClient::connect(address).await?;
 
             Ok(client)
         }
 
+        pub async fn connect_namespaces(
+            socket: String,
+        ) -> Result<NamespacesClient<tonic::transport::Channel>, Box<dyn std::error::Error>>
+        {
+            let channel = Endpoint::try_from("http://[::]")
+                .unwrap()
+                .connect_with_connector(service_fn(move |_: Uri| {
+                    UnixStream::connect(socket.clone())
+                }))
+                .await?;
+
+            Ok(NamespacesClient::new(channel))
+        }
+
         pub async fn connect_runtime(
             socket: String,
         ) -> Result<ControllerRuntimeClient<tonic::transport::Channel>, Box<dyn std::error::Error>>
@@ -154,8 +245,7 @@ pub mod runtime {
                 .connect_with_connector(service_fn(move |_: Uri| {
                     UnixStream::connect(socket.clone())
                 }))
-                .await
-                .unwrap();
+                .await?;
 
             Ok(ControllerRuntimeClient::new(channel))
         }
@@ -166,7 +256,7 @@ pub mod runtime {
         {
             let address = format!("http://{}", address);
 
-            let client = ControllerRuntimeClient::connect(address).await.unwrap();
+            let client = ControllerRuntimeClient::connect(address).await?;
 
             Ok(client)
         }
@@ -180,8 +270,7 @@ pub mod runtime {
                 .connect_with_connector(service_fn(move |_: Uri| {
                     UnixStream::connect(socket.clone())
                 }))
-                .await
-                .unwrap();
+                .await?;
 
             Ok(ControllerAdapterClient::new(channel))
         }
@@ -192,7 +281,7 @@ pub mod runtime {
         {
             let address = format!("http://{}", address);
 
-            let client = ControllerAdapterClient::connect(address).await.unwrap();
+            let client = ControllerAdapterClient::connect(address).await?;
 
             Ok(client)
         }
@@ -354,6 +443,16 @@ pub mod runtime {
                 todo!()
             }
 
+            // N.B.: This service is a stand-in that keeps no resources, statuses are stored by
+            // the runtime the engine starts.
+            async fn update_status(
+                &self,
+                _request: Request<crate::spec::runtime::RuntimeUpdateStatusRequest>,
+            ) -> Result<Response<crate::spec::runtime::RuntimeUpdateStatusResponse>, Status>
+            {
+                Err(Status::unimplemented("status updates are not supported"))
+            }
+
             async fn teardown(
                 &self,
                 _request: Request<crate::spec::runtime::RuntimeTeardownRequest>,
@@ -390,11 +489,16 @@ pub mod runtime {
 }
 
 pub mod generator {
+    use crate::bootstrap::Tokens;
     use crate::consts;
     use crate::unix::process::reaper::Reaper;
     use std::env;
 
-    pub fn load(socket: String, reaper: Reaper) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    pub fn load(
+        socket: String,
+        reaper: Reaper,
+        tokens: Tokens,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
         let pattern = format!(
             "{}/*-{}-{}",
             consts::GENERATORS,
@@ -402,19 +506,24 @@ pub mod generator {
             env::consts::ARCH
         );
 
-        super::load(socket, reaper, pattern)
+        super::load(socket, reaper, tokens, pattern)
     }
 }
 
 pub mod plugin {
     use crate::unix::process::reaper::Reaper;
     use crate::{
+        bootstrap::{Bootstrap, Tokens},
         consts,
-        spec::engine::{Plugin, RegisterResponse},
+        spec::engine::{Plugin, RegisterResponse, RegisterSchemaResponse, Schema},
     };
     use std::env;
 
-    pub fn load(socket: String, reaper: Reaper) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    pub fn load(
+        socket: String,
+        reaper: Reaper,
+        tokens: Tokens,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
         let pattern = format!(
             "{}/*-{}-{}",
             consts::PLUGINS,
@@ -422,28 +531,49 @@ pub mod plugin {
             env::consts::ARCH
         );
 
-        super::load(socket, reaper, pattern)
+        super::load(socket, reaper, tokens, pattern)
     }
 
     pub async fn register(
-        socket: String,
+        bootstrap: &Bootstrap,
         name: String,
     ) -> Result<tonic::Response<RegisterResponse>, tonic::Status> {
-        let mut client = super::engine::client::connect(socket).await.unwrap();
+        let mut client = super::engine::client::connect(bootstrap.engine_socket.clone())
+            .await
+            .map_err(|err| tonic::Status::unavailable(err.to_string()))?;
+
+        let mut request = tonic::Request::new(Plugin { name });
 
-        let request = tonic::Request::new(Plugin { name });
+        bootstrap.authorize(&mut request);
 
         client.register(request).await
     }
+
+    pub async fn register_schema(
+        bootstrap: &Bootstrap,
+        schema: Schema,
+    ) -> Result<tonic::Response<RegisterSchemaResponse>, tonic::Status> {
+        let mut client = super::engine::client::connect(bootstrap.engine_socket.clone())
+            .await
+            .map_err(|err| tonic::Status::unavailable(err.to_string()))?;
+
+        let mut request = tonic::Request::new(schema);
+
+        bootstrap.authorize(&mut request);
+
+        client.register_schema(request).await
+    }
 }
 
-use crate::unix::process::reaper::Reaper;
+use crate::bootstrap::Tokens;
+use crate::unix::process::{reaper::Reaper, service::Service};
 use glob::glob_with;
 use glob::MatchOptions;
 
 pub fn load(
     socket: String,
     reaper: Reaper,
+    tokens: Tokens,
     pattern: String,
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let options = MatchOptions {
@@ -461,10 +591,19 @@ pub fn load(
             let s = socket.clone();
 
             if let Ok(executable) = path.into_os_string().into_string() {
+                let service = match Service::load(executable) {
+                    Ok(service) => service,
+                    Err(err) => {
+                        println!("Failed to load service definition: {:?}", err);
+                        continue;
+                    }
+                };
+
                 let r = reaper.clone();
+                let t = tokens.clone();
 
                 tokio::spawn(async {
-                    crate::unix::process::monitor(executable, s, r)
+                    crate::unix::process::monitor(service, s, r, t)
                         .await
                         .unwrap();
                 });
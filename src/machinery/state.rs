@@ -0,0 +1,1016 @@
+//! An in-memory implementation of the State service.
+
+use crate::bootstrap::{Tokens, AUTHORIZATION};
+use crate::consts;
+use crate::schema::{SchemaError, Validator};
+use crate::selector::{self, Selector};
+use crate::spec::engine::Schema;
+use crate::spec::resource::{
+    namespaces_server::Namespaces, state_server::State, CreateNamespaceRequest,
+    CreateNamespaceResponse, CreateRequest, CreateResponse, DestroyNamespaceRequest,
+    DestroyNamespaceResponse, DestroyRequest, DestroyResponse, Event, EventType, GetRequest,
+    GetResponse, ListNamespacesRequest, ListNamespacesResponse, ListRequest, ListResponse,
+    Metadata, Namespace, OwnerReference, Propagation, Resource, UpdateRequest, UpdateResponse,
+    UpdateStatusRequest, UpdateStatusResponse, WatchRequest, WatchResponse,
+};
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::Stream;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tonic::{Request, Response, Status};
+
+/// The phase of a resource that is being torn down.
+pub const PHASE_TEARING_DOWN: &str = "tearingDown";
+
+/// The phase of a namespace that is in use.
+pub const PHASE_ACTIVE: &str = "active";
+
+/// The phase of a namespace whose resources are being destroyed.
+pub const PHASE_TERMINATING: &str = "terminating";
+
+/// The finalizer that holds a resource destroyed in the foreground until its dependents are
+/// destroyed.
+pub const FINALIZER_FOREGROUND: &str = "foregroundDeletion";
+
+/// Returns `api`, or the default API group if it is empty.
+pub fn api(api: &str) -> &str {
+    match api {
+        "" => consts::API,
+        api => api,
+    }
+}
+
+#[derive(Debug)]
+struct Registered {
+    validator: Validator,
+    owner: String,
+}
+
+/// Registry holds the schemas of resource types, and the controllers that own them, by API group
+/// and type.
+#[derive(Debug, Default, Clone)]
+pub struct Registry {
+    schemas: Arc<Mutex<HashMap<(String, String), Registered>>>,
+}
+
+impl Registry {
+    /// Compiles and registers `schema`, replacing the schema previously registered for its type.
+    ///
+    /// N.B.: The schema of a type that is owned by a controller can only be replaced by that
+    /// controller.
+    pub fn register(&self, schema: &Schema) -> Result<(), SchemaError> {
+        if schema.r#type.is_empty() {
+            return Err(SchemaError::Invalid("type must be set".to_owned()));
+        }
+
+        let validator = match (
+            schema.descriptor_set.is_empty(),
+            schema.json_schema.is_empty(),
+        ) {
+            (false, true) => {
+                Validator::from_descriptor_set(&schema.descriptor_set, &schema.message)?
+            }
+            (true, false) => Validator::from_json_schema(&schema.json_schema)?,
+            _ => {
+                return Err(SchemaError::Invalid(
+                    "exactly one of descriptor_set and json_schema must be set".to_owned(),
+                ))
+            }
+        };
+
+        let mut schemas = self.schemas.lock().unwrap();
+
+        let key = (api(&schema.api).to_owned(), schema.r#type.clone());
+
+        if let Some(registered) = schemas.get(&key) {
+            if !registered.owner.is_empty() && registered.owner != schema.owner {
+                return Err(SchemaError::Invalid(format!(
+                    "type is owned by {:?}",
+                    registered.owner
+                )));
+            }
+        }
+
+        let owner = schema.owner.clone();
+
+        schemas.insert(key, Registered { validator, owner });
+
+        Ok(())
+    }
+
+    /// Returns the controller that owns a type, if the type is registered and owned.
+    pub fn owner(&self, group: &str, r#type: &str) -> Option<String> {
+        let schemas = self.schemas.lock().unwrap();
+
+        schemas
+            .get(&(api(group).to_owned(), r#type.to_owned()))
+            .map(|registered| registered.owner.clone())
+            .filter(|owner| !owner.is_empty())
+    }
+
+    /// Validates the spec of `resource` against the schema of its type.
+    ///
+    /// N.B.: Only specs in `yaml_spec` are validated field by field, a spec that is only given as
+    /// `proto_spec` is accepted as is.
+    pub fn validate(&self, resource: &Resource) -> Result<(), Status> {
+        let metadata = metadata(resource)?;
+
+        let schemas = self.schemas.lock().unwrap();
+
+        let key = (api(&metadata.api).to_owned(), metadata.r#type.clone());
+
+        let validator = match schemas.get(&key) {
+            Some(registered) => &registered.validator,
+            None => {
+                return Err(Status::invalid_argument(format!(
+                    "unknown resource type: {}/{}",
+                    key.0, key.1
+                )))
+            }
+        };
+
+        let spec = resource.spec.clone().unwrap_or_default();
+
+        if spec.yaml_spec.trim().is_empty() && !spec.proto_spec.is_empty() {
+            return Ok(());
+        }
+
+        let value = match spec.yaml_spec.trim().is_empty() {
+            true => serde_json::Value::Object(serde_json::Map::new()),
+            false => serde_yaml::from_str(&spec.yaml_spec)
+                .map_err(|err| Status::invalid_argument(format!("spec: invalid YAML: {}", err)))?,
+        };
+
+        validator.validate("spec", &value).map_err(|errors| {
+            let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+
+            Status::invalid_argument(errors.join("; "))
+        })
+    }
+}
+
+// Resources are keyed by their namespace, API group, type and ID, so that types with the same
+// name in different API groups can coexist.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Key {
+    namespace: String,
+    api: String,
+    r#type: String,
+    id: String,
+}
+
+impl Key {
+    fn new(namespace: &str, group: &str, r#type: &str, id: &str) -> Key {
+        Key {
+            namespace: namespace.to_owned(),
+            api: api(group).to_owned(),
+            r#type: r#type.to_owned(),
+            id: id.to_owned(),
+        }
+    }
+
+    fn of(metadata: &Metadata) -> Key {
+        Key::new(
+            &metadata.namespace,
+            &metadata.api,
+            &metadata.r#type,
+            &metadata.id,
+        )
+    }
+
+    fn owner(reference: &OwnerReference) -> Key {
+        Key::new(
+            &reference.namespace,
+            &reference.api,
+            &reference.r#type,
+            &reference.id,
+        )
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}/{}",
+            self.namespace, self.api, self.r#type, self.id
+        )
+    }
+}
+
+struct Watcher {
+    namespace: String,
+    api: String,
+    r#type: String,
+    id: Option<String>,
+    selector: Selector,
+    tx: UnboundedSender<Result<WatchResponse, Status>>,
+}
+
+impl Watcher {
+    fn selects(&self, resource: Option<&Resource>) -> bool {
+        resource
+            .and_then(|resource| resource.metadata.as_ref())
+            .map(|metadata| self.matches(metadata) && self.selector.matches(&metadata.labels))
+            .unwrap_or(false)
+    }
+
+    fn matches(&self, metadata: &Metadata) -> bool {
+        self.namespace == metadata.namespace
+            && self.api == api(&metadata.api)
+            && self.r#type == metadata.r#type
+            && self
+                .id
+                .as_ref()
+                .map(|id| *id == metadata.id)
+                .unwrap_or(true)
+    }
+}
+
+// A step of garbage collection.
+enum Collection {
+    // Tears down a dependent whose owners are all gone, in the foreground if an owner is being
+    // destroyed in the foreground.
+    TearDown { foreground: bool },
+    // Removes the foreground finalizer of a resource whose dependents are all destroyed.
+    Release,
+    // Removes a torn down resource whose finalizers are all removed.
+    Remove,
+}
+
+// Checks that `name` is a DNS label (e.g. `system`).
+//
+// https://tools.ietf.org/html/rfc1123#section-2.1.
+fn validate_namespace(name: &str) -> Result<(), Status> {
+    let valid = !name.is_empty()
+        && name.len() <= 63
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        && !name.starts_with('-')
+        && !name.ends_with('-');
+
+    match valid {
+        true => Ok(()),
+        false => Err(Status::invalid_argument(format!(
+            "invalid namespace name: {:?}",
+            name
+        ))),
+    }
+}
+
+fn is_foreground(metadata: &Metadata) -> bool {
+    metadata
+        .finalizers
+        .iter()
+        .any(|f| f == FINALIZER_FOREGROUND)
+}
+
+fn increment(version: &str) -> String {
+    let version: u64 = version.parse().unwrap_or(0);
+
+    (version + 1).to_string()
+}
+
+fn bump(metadata: &mut Metadata) {
+    metadata.version = increment(&metadata.version);
+}
+
+#[derive(Default)]
+struct Store {
+    namespaces: BTreeMap<String, Namespace>,
+    resources: BTreeMap<Key, Resource>,
+    watchers: Vec<Watcher>,
+}
+
+impl Store {
+    // Notifies the watchers of a change from `previous` to `resource`.
+    //
+    // N.B.: A resource whose labels change enters or leaves the selection of a watcher, which
+    // sees it as created or destroyed respectively.
+    fn notify(&mut self, previous: Option<&Resource>, resource: &Resource, event_type: EventType) {
+        // N.B.: Watchers that went away are dropped on the first event they miss.
+        self.watchers.retain(|watcher| {
+            let selected = (watcher.selects(previous), watcher.selects(Some(resource)));
+
+            let (resource, event_type) = match (event_type, selected) {
+                (EventType::Updated, (false, true)) => (resource, EventType::Created),
+                (EventType::Updated, (true, false)) => (previous.unwrap(), EventType::Destroyed),
+                (_, (_, true)) => (resource, event_type),
+                _ => return !watcher.tx.is_closed(),
+            };
+
+            let response = WatchResponse {
+                event: Some(Event {
+                    resource: Some(resource.clone()),
+                    event_type: event_type as i32,
+                }),
+            };
+
+            watcher.tx.unbounded_send(Ok(response)).is_ok()
+        });
+    }
+
+    fn metadata(&self, key: &Key) -> Option<&Metadata> {
+        self.resources.get(key).and_then(|r| r.metadata.as_ref())
+    }
+
+    fn has_dependents(&self, owner: &Key) -> bool {
+        self.resources.values().any(|resource| {
+            resource
+                .metadata
+                .iter()
+                .flat_map(|metadata| metadata.owner_references.iter())
+                .any(|reference| Key::owner(reference) == *owner)
+        })
+    }
+
+    // Returns the next step of garbage collection, if any.
+    fn next_collection(&self) -> Option<(Key, Collection)> {
+        for (key, resource) in &self.resources {
+            let metadata = match &resource.metadata {
+                Some(metadata) => metadata,
+                None => continue,
+            };
+
+            if metadata.phase == PHASE_TEARING_DOWN {
+                if is_foreground(metadata) && !self.has_dependents(key) {
+                    return Some((key.clone(), Collection::Release));
+                }
+
+                if metadata.finalizers.is_empty() {
+                    return Some((key.clone(), Collection::Remove));
+                }
+
+                continue;
+            }
+
+            let terminating = self
+                .namespaces
+                .get(&metadata.namespace)
+                .map(|namespace| namespace.phase == PHASE_TERMINATING)
+                .unwrap_or(false);
+
+            if terminating {
+                return Some((key.clone(), Collection::TearDown { foreground: false }));
+            }
+
+            if metadata.owner_references.is_empty() {
+                continue;
+            }
+
+            // N.B.: An owner that is being destroyed in the foreground is gone as far as its
+            // dependents are concerned.
+            let owners: Vec<Option<&Metadata>> = metadata
+                .owner_references
+                .iter()
+                .map(|reference| self.metadata(&Key::owner(reference)))
+                .collect();
+
+            if owners
+                .iter()
+                .all(|owner| owner.map(is_foreground).unwrap_or(true))
+            {
+                let foreground = owners
+                    .iter()
+                    .any(|owner| owner.map(is_foreground) == Some(true));
+
+                return Some((key.clone(), Collection::TearDown { foreground }));
+            }
+        }
+
+        None
+    }
+
+    // Collects garbage until there is none left.
+    fn collect(&mut self) {
+        while let Some((key, collection)) = self.next_collection() {
+            let previous = match self.resources.remove(&key) {
+                Some(resource) => resource,
+                None => continue,
+            };
+
+            let mut resource = previous.clone();
+            let metadata = resource.metadata.as_mut().unwrap();
+
+            match collection {
+                Collection::TearDown { foreground } => {
+                    println!("Collecting {}", key);
+
+                    metadata.phase = PHASE_TEARING_DOWN.to_owned();
+
+                    if foreground {
+                        metadata.finalizers.push(FINALIZER_FOREGROUND.to_owned());
+                    }
+                }
+                Collection::Release => metadata.finalizers.retain(|f| f != FINALIZER_FOREGROUND),
+                Collection::Remove => {
+                    self.notify(Some(&previous), &previous, EventType::Destroyed);
+
+                    continue;
+                }
+            }
+
+            bump(metadata);
+
+            self.resources.insert(key, resource.clone());
+            self.notify(Some(&previous), &resource, EventType::Updated);
+        }
+
+        let resources = &self.resources;
+
+        self.namespaces.retain(|name, namespace| {
+            namespace.phase != PHASE_TERMINATING
+                || resources.keys().any(|key| key.namespace == *name)
+        });
+    }
+
+    // Checks that `owner`, the service that makes the request, may write resources in
+    // `namespace`. Resources can only be created in namespaces that are active.
+    fn check_write(&self, namespace: &str, owner: &str, creating: bool) -> Result<(), Status> {
+        let namespace = match self.namespaces.get(namespace) {
+            Some(namespace) => namespace,
+            None => {
+                return Err(Status::failed_precondition(format!(
+                    "namespace not found: {}",
+                    namespace
+                )))
+            }
+        };
+
+        if creating && namespace.phase == PHASE_TERMINATING {
+            return Err(Status::failed_precondition(format!(
+                "namespace is terminating: {}",
+                namespace.name
+            )));
+        }
+
+        let open = !namespace.read_only && namespace.writers.is_empty();
+
+        if !open && !namespace.writers.iter().any(|writer| writer == owner) {
+            return Err(Status::permission_denied(format!(
+                "namespace {} is not writable by {:?}",
+                namespace.name, owner
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Checks that the owners `metadata` refers to exist, other than those in `existing`.
+    fn check_owners(&self, metadata: &Metadata, existing: &[OwnerReference]) -> Result<(), Status> {
+        for reference in &metadata.owner_references {
+            if existing.contains(reference) {
+                continue;
+            }
+
+            let key = Key::owner(reference);
+
+            if key == Key::of(metadata) {
+                return Err(Status::invalid_argument("a resource cannot own itself"));
+            }
+
+            match self.metadata(&key) {
+                Some(owner) if owner.phase != PHASE_TEARING_DOWN => {}
+                Some(_) => {
+                    return Err(Status::failed_precondition(format!(
+                        "owner is being torn down: {}",
+                        key
+                    )))
+                }
+                None => {
+                    return Err(Status::failed_precondition(format!(
+                        "owner not found: {}",
+                        key
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// StateService stores resources in namespaces, and validates their specs against the schemas in
+/// its registry. The `system` namespace always exists.
+///
+/// Services started by the engine are authenticated with the tokens they were issued, and write
+/// as themselves. A request without a token is made by a user.
+#[derive(Clone)]
+pub struct StateService {
+    store: Arc<Mutex<Store>>,
+    registry: Registry,
+    tokens: Tokens,
+}
+
+impl StateService {
+    pub fn new(registry: Registry, tokens: Tokens) -> StateService {
+        let mut store = Store::default();
+
+        store.namespaces.insert(
+            consts::NAMESPACE_SYSTEM.to_owned(),
+            Namespace {
+                name: consts::NAMESPACE_SYSTEM.to_owned(),
+                phase: PHASE_ACTIVE.to_owned(),
+                ..Default::default()
+            },
+        );
+
+        StateService {
+            store: Arc::new(Mutex::new(store)),
+            registry,
+            tokens,
+        }
+    }
+
+    // Returns the service that makes `request`, or the empty string for a user, and checks that
+    // it is the `claimed` owner if one is given.
+    fn writer<T>(&self, request: &Request<T>, claimed: &str) -> Result<String, Status> {
+        let writer = match request.metadata().contains_key(AUTHORIZATION) {
+            true => self.tokens.authenticate(request)?,
+            false => String::new(),
+        };
+
+        if !claimed.is_empty() && claimed != writer {
+            return Err(Status::permission_denied(format!(
+                "cannot write as {:?}",
+                claimed
+            )));
+        }
+
+        Ok(writer)
+    }
+}
+
+fn metadata(resource: &Resource) -> Result<&Metadata, Status> {
+    resource
+        .metadata
+        .as_ref()
+        .ok_or_else(|| Status::invalid_argument("resource has no metadata"))
+}
+
+// Validates `resource`, and fills in the defaults of its metadata.
+fn normalize(resource: &mut Resource) -> Result<&mut Metadata, Status> {
+    let metadata = resource
+        .metadata
+        .as_mut()
+        .ok_or_else(|| Status::invalid_argument("resource has no metadata"))?;
+
+    metadata.api = api(&metadata.api).to_owned();
+
+    selector::validate(&metadata.labels).map_err(invalid_argument)?;
+
+    // N.B.: Annotation values are arbitrary, only their keys are restricted.
+    for key in metadata.annotations.keys() {
+        selector::validate_key(key).map_err(invalid_argument)?;
+    }
+
+    Ok(metadata)
+}
+
+fn invalid_argument<E: fmt::Display>(error: E) -> Status {
+    Status::invalid_argument(error.to_string())
+}
+
+fn not_found(key: &Key) -> Status {
+    Status::not_found(format!("resource not found: {}", key))
+}
+
+type Stream_<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + Sync + 'static>>;
+
+#[tonic::async_trait]
+impl State for StateService {
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let request = request.into_inner();
+
+        let store = self.store.lock().unwrap();
+
+        let key = Key::new(
+            &request.namespace,
+            &request.api,
+            &request.r#type,
+            &request.id,
+        );
+
+        match store.resources.get(&key) {
+            Some(resource) => Ok(Response::new(GetResponse {
+                resource: Some(resource.clone()),
+            })),
+            None => Err(not_found(&key)),
+        }
+    }
+
+    type ListStream = Stream_<ListResponse>;
+
+    async fn list(
+        &self,
+        request: Request<ListRequest>,
+    ) -> Result<Response<Self::ListStream>, Status> {
+        let request = request.into_inner();
+
+        let options = request.options.clone().unwrap_or_default();
+
+        let selector = Selector::parse(&options.label_selector).map_err(invalid_argument)?;
+
+        let store = self.store.lock().unwrap();
+
+        let responses: Vec<Result<ListResponse, Status>> = store
+            .resources
+            .iter()
+            .filter(|(key, _)| {
+                key.namespace == request.namespace
+                    && key.api == api(&request.api)
+                    && key.r#type == request.r#type
+            })
+            .filter(|(_, resource)| {
+                let metadata = resource.metadata.as_ref();
+
+                selector.matches(&metadata.map(|m| m.labels.clone()).unwrap_or_default())
+            })
+            .map(|(_, resource)| {
+                Ok(ListResponse {
+                    resource: Some(resource.clone()),
+                })
+            })
+            .collect();
+
+        Ok(Response::new(Box::pin(futures::stream::iter(responses))))
+    }
+
+    async fn create(
+        &self,
+        request: Request<CreateRequest>,
+    ) -> Result<Response<CreateResponse>, Status> {
+        let options = request.get_ref().options.as_ref();
+        let owner = self.writer(&request, options.map_or("", |options| &options.owner))?;
+
+        let request = request.into_inner();
+
+        let mut resource = request
+            .resource
+            .ok_or_else(|| Status::invalid_argument("resource must be set"))?;
+
+        normalize(&mut resource)?;
+
+        self.registry.validate(&resource)?;
+
+        let mut store = self.store.lock().unwrap();
+
+        let metadata = resource.metadata.as_mut().unwrap();
+
+        let key = Key::of(metadata);
+
+        if store.resources.contains_key(&key) {
+            return Err(Status::already_exists(format!(
+                "resource already exists: {}",
+                key
+            )));
+        }
+
+        store.check_write(&metadata.namespace, &owner, true)?;
+        store.check_owners(metadata, &[])?;
+
+        // N.B.: The status is written by the controller that owns the type, never along with the
+        // spec.
+        metadata.version = "1".to_owned();
+        metadata.status_version = "0".to_owned();
+        metadata.owner = owner;
+
+        if metadata.phase.is_empty() {
+            metadata.phase = "running".to_owned();
+        }
+
+        resource.status = None;
+
+        store.resources.insert(key, resource.clone());
+        store.notify(None, &resource, EventType::Created);
+
+        Ok(Response::new(CreateResponse {}))
+    }
+
+    async fn update(
+        &self,
+        request: Request<UpdateRequest>,
+    ) -> Result<Response<UpdateResponse>, Status> {
+        let options = request.get_ref().options.as_ref();
+        let owner = self.writer(&request, options.map_or("", |options| &options.owner))?;
+
+        let request = request.into_inner();
+
+        let mut resource = request
+            .new_resource
+            .ok_or_else(|| Status::invalid_argument("new_resource must be set"))?;
+
+        normalize(&mut resource)?;
+
+        self.registry.validate(&resource)?;
+
+        let mut store = self.store.lock().unwrap();
+
+        let metadata = resource.metadata.as_mut().unwrap();
+
+        let key = Key::of(metadata);
+
+        let current = match store.resources.get(&key).and_then(|r| r.metadata.as_ref()) {
+            Some(current) => current,
+            None => return Err(not_found(&key)),
+        };
+
+        if current.version != request.current_version {
+            return Err(Status::failed_precondition(format!(
+                "version conflict: current version is {}, not {}",
+                current.version, request.current_version
+            )));
+        }
+
+        if current.owner != owner {
+            return Err(Status::permission_denied(format!(
+                "resource is owned by {:?}",
+                current.owner
+            )));
+        }
+
+        store.check_write(&current.namespace, &owner, false)?;
+
+        // N.B.: A resource that is being torn down stays so, whatever phase its owner reports.
+        if current.phase == PHASE_TEARING_DOWN {
+            metadata.phase = current.phase.clone();
+        }
+
+        // N.B.: Owners that are gone may still be referred to, a dependent is updated as it
+        // is torn down.
+        store.check_owners(metadata, &current.owner_references)?;
+
+        metadata.version = current.version.clone();
+        metadata.status_version = current.status_version.clone();
+        metadata.owner = owner;
+
+        bump(metadata);
+
+        resource.status = store.resources[&key].status.clone();
+
+        let previous = store.resources.insert(key, resource.clone());
+        store.notify(previous.as_ref(), &resource, EventType::Updated);
+
+        // N.B.: Removing the last finalizer of a resource that is being torn down destroys it.
+        store.collect();
+
+        Ok(Response::new(UpdateResponse {}))
+    }
+
+    async fn update_status(
+        &self,
+        request: Request<UpdateStatusRequest>,
+    ) -> Result<Response<UpdateStatusResponse>, Status> {
+        let options = request.get_ref().options.as_ref();
+        let writer = self.writer(&request, options.map_or("", |options| &options.owner))?;
+
+        let request = request.into_inner();
+
+        match self.registry.owner(&request.api, &request.r#type) {
+            Some(controller) if controller == writer => {}
+            Some(controller) => {
+                return Err(Status::permission_denied(format!(
+                    "status is owned by {:?}",
+                    controller
+                )))
+            }
+            None => {
+                return Err(Status::failed_precondition(format!(
+                    "type is not owned by a controller: {}/{}",
+                    api(&request.api),
+                    request.r#type
+                )))
+            }
+        }
+
+        let mut store = self.store.lock().unwrap();
+
+        let key = Key::new(
+            &request.namespace,
+            &request.api,
+            &request.r#type,
+            &request.id,
+        );
+
+        let previous = match store.resources.get(&key) {
+            Some(resource) => resource.clone(),
+            None => return Err(not_found(&key)),
+        };
+
+        let mut resource = previous.clone();
+        let metadata = resource.metadata.as_mut().unwrap();
+
+        if metadata.status_version != request.current_status_version {
+            return Err(Status::failed_precondition(format!(
+                "status version conflict: current status version is {}, not {}",
+                metadata.status_version, request.current_status_version
+            )));
+        }
+
+        metadata.status_version = increment(&metadata.status_version);
+
+        resource.status = request.status;
+
+        store.resources.insert(key, resource.clone());
+        store.notify(Some(&previous), &resource, EventType::Updated);
+
+        Ok(Response::new(UpdateStatusResponse {}))
+    }
+
+    async fn destroy(
+        &self,
+        request: Request<DestroyRequest>,
+    ) -> Result<Response<DestroyResponse>, Status> {
+        let options = request.get_ref().options.as_ref();
+        let owner = self.writer(&request, options.map_or("", |options| &options.owner))?;
+
+        let request = request.into_inner();
+
+        let mut store = self.store.lock().unwrap();
+
+        let key = Key::new(
+            &request.namespace,
+            &request.api,
+            &request.r#type,
+            &request.id,
+        );
+
+        let current = match store.resources.get(&key).and_then(|r| r.metadata.as_ref()) {
+            Some(current) => current,
+            None => return Err(not_found(&key)),
+        };
+
+        let options = request.options.unwrap_or_default();
+
+        if current.owner != owner {
+            return Err(Status::permission_denied(format!(
+                "resource is owned by {:?}",
+                current.owner
+            )));
+        }
+
+        store.check_write(&current.namespace, &owner, false)?;
+
+        match options.propagation() {
+            Propagation::Background => {
+                if !current.finalizers.is_empty() {
+                    return Err(Status::failed_precondition(format!(
+                        "resource has pending finalizers: {:?}",
+                        current.finalizers
+                    )));
+                }
+
+                if let Some(resource) = store.resources.remove(&key) {
+                    store.notify(Some(&resource), &resource, EventType::Destroyed);
+                }
+            }
+            Propagation::Foreground => {
+                if current.phase != PHASE_TEARING_DOWN || !is_foreground(current) {
+                    let previous = store.resources.get(&key).cloned().unwrap();
+
+                    let mut resource = previous.clone();
+                    let metadata = resource.metadata.as_mut().unwrap();
+
+                    metadata.phase = PHASE_TEARING_DOWN.to_owned();
+
+                    if !is_foreground(metadata) {
+                        metadata.finalizers.push(FINALIZER_FOREGROUND.to_owned());
+                    }
+
+                    bump(metadata);
+
+                    store.resources.insert(key, resource.clone());
+                    store.notify(Some(&previous), &resource, EventType::Updated);
+                }
+            }
+        }
+
+        store.collect();
+
+        Ok(Response::new(DestroyResponse {}))
+    }
+
+    type WatchStream = Stream_<WatchResponse>;
+
+    async fn watch(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let request = request.into_inner();
+
+        let options = request.options.unwrap_or_default();
+
+        let selector = Selector::parse(&options.label_selector).map_err(invalid_argument)?;
+
+        let (tx, rx) = unbounded();
+
+        let watcher = Watcher {
+            namespace: request.namespace,
+            api: api(&request.api).to_owned(),
+            r#type: request.r#type,
+            id: request.id,
+            selector,
+            tx,
+        };
+
+        let mut store = self.store.lock().unwrap();
+
+        if options.bootstrap_contents {
+            for resource in store.resources.values() {
+                if !watcher.selects(Some(resource)) {
+                    continue;
+                }
+
+                let response = WatchResponse {
+                    event: Some(Event {
+                        resource: Some(resource.clone()),
+                        event_type: EventType::Created as i32,
+                    }),
+                };
+
+                let _ = watcher.tx.unbounded_send(Ok(response));
+            }
+        }
+
+        store.watchers.push(watcher);
+
+        Ok(Response::new(Box::pin(rx)))
+    }
+}
+
+#[tonic::async_trait]
+impl Namespaces for StateService {
+    async fn create_namespace(
+        &self,
+        request: Request<CreateNamespaceRequest>,
+    ) -> Result<Response<CreateNamespaceResponse>, Status> {
+        self.tokens.authenticate(&request)?;
+
+        let mut namespace = request
+            .into_inner()
+            .namespace
+            .ok_or_else(|| Status::invalid_argument("namespace must be set"))?;
+
+        validate_namespace(&namespace.name)?;
+
+        let mut store = self.store.lock().unwrap();
+
+        if store.namespaces.contains_key(&namespace.name) {
+            return Err(Status::already_exists(format!(
+                "namespace already exists: {}",
+                namespace.name
+            )));
+        }
+
+        namespace.phase = PHASE_ACTIVE.to_owned();
+
+        store.namespaces.insert(namespace.name.clone(), namespace);
+
+        Ok(Response::new(CreateNamespaceResponse {}))
+    }
+
+    async fn list_namespaces(
+        &self,
+        _: Request<ListNamespacesRequest>,
+    ) -> Result<Response<ListNamespacesResponse>, Status> {
+        let store = self.store.lock().unwrap();
+
+        Ok(Response::new(ListNamespacesResponse {
+            namespaces: store.namespaces.values().cloned().collect(),
+        }))
+    }
+
+    async fn destroy_namespace(
+        &self,
+        request: Request<DestroyNamespaceRequest>,
+    ) -> Result<Response<DestroyNamespaceResponse>, Status> {
+        self.tokens.authenticate(&request)?;
+
+        let name = request.into_inner().name;
+
+        if name == consts::NAMESPACE_SYSTEM {
+            return Err(Status::failed_precondition(format!(
+                "namespace cannot be destroyed: {}",
+                name
+            )));
+        }
+
+        let mut store = self.store.lock().unwrap();
+
+        match store.namespaces.get_mut(&name) {
+            Some(namespace) => namespace.phase = PHASE_TERMINATING.to_owned(),
+            None => return Err(Status::not_found(format!("namespace not found: {}", name))),
+        }
+
+        println!("Terminating namespace {}", name);
+
+        // N.B.: The resources in the namespace are destroyed as garbage, and the namespace along
+        // with the last of them.
+        store.collect();
+
+        Ok(Response::new(DestroyNamespaceResponse {}))
+    }
+}
@@ -0,0 +1,152 @@
+use cosi::{
+    controller::{self, active, error, Context, Controller, Error},
+    resource::ResourceKind,
+    spec::engine::{Group, GroupStatus, Schema, User, UserStatus},
+    spec::runtime::{ControllerInput, ControllerOutput},
+    unix::{
+        self,
+        accounts::{self, Accounts, Action, Managed},
+    },
+};
+use std::path::PathBuf;
+
+pub static NAME: &str = "accounts";
+
+struct AccountsController {
+    root: PathBuf,
+    // The users and groups created by the controller, which it removes once no resource
+    // describes them.
+    managed: Managed,
+    // Whether the managed users and groups were recovered from the status of the resources.
+    recovered: bool,
+}
+
+#[tonic::async_trait]
+impl Controller for AccountsController {
+    fn name(&self) -> String {
+        NAME.to_owned()
+    }
+
+    fn inputs(&self) -> Vec<ControllerInput> {
+        vec![
+            ControllerInput::strong::<User>(),
+            ControllerInput::strong::<Group>(),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<ControllerOutput> {
+        vec![]
+    }
+
+    fn schemas(&self) -> Vec<Schema> {
+        vec![User::schema(), Group::schema()]
+    }
+
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        let mut client = ctx.typed();
+
+        let users = client.list::<User>(User::NAMESPACE).await?;
+        let groups = client.list::<Group>(Group::NAMESPACE).await?;
+
+        // N.B.: After a restart, the users and groups the controller created are recovered from
+        // the status of the resources, including those being torn down, so that they are still
+        // removed.
+        if !self.recovered {
+            for resource in &users {
+                if let Some(status) = resource.decode_status::<UserStatus>()? {
+                    if status.created {
+                        self.managed.users.insert(resource.spec.name.clone());
+                    }
+
+                    if status.private_group {
+                        self.managed.groups.insert(resource.spec.name.clone());
+                    }
+                }
+            }
+
+            for resource in &groups {
+                if let Some(status) = resource.decode_status::<GroupStatus>()? {
+                    if status.created {
+                        self.managed.groups.insert(resource.spec.name.clone());
+                    }
+                }
+            }
+
+            self.recovered = true;
+        }
+
+        let users = active(users);
+        let groups = active(groups);
+
+        let results: Vec<(Action, Result<(), String>)> = accounts::converge(
+            &self.root,
+            &users.iter().map(|r| r.spec.clone()).collect::<Vec<_>>(),
+            &groups.iter().map(|r| r.spec.clone()).collect::<Vec<_>>(),
+            &mut self.managed,
+        )?
+        .into_iter()
+        .map(|(action, result)| {
+            match &result {
+                Ok(()) => println!("{}: done", action),
+                Err(err) => println!("{}: {}", action, err),
+            }
+
+            (action, result.map_err(|err| err.to_string()))
+        })
+        .collect();
+
+        let accounts = Accounts::read(&self.root)?;
+
+        for resource in &users {
+            let name = &resource.spec.name;
+            let (uid, gid) = accounts.user(name).unwrap_or_default();
+
+            let status = UserStatus {
+                uid,
+                gid,
+                created: self.managed.users.contains(name),
+                private_group: resource.spec.group.is_empty() && self.managed.groups.contains(name),
+                error: error(&results, |action| match action {
+                    Action::AddUser(user)
+                    | Action::UpdateGroups(user)
+                    | Action::CreateHome(user) => user == name,
+                    _ => false,
+                }),
+            };
+
+            if resource.decode_status::<UserStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
+            }
+        }
+
+        for resource in &groups {
+            let name = &resource.spec.name;
+
+            let status = GroupStatus {
+                gid: accounts.group(name).unwrap_or_default(),
+                created: self.managed.groups.contains(name),
+                error: error(&results, |action| *action == Action::AddGroup(name.clone())),
+            };
+
+            if resource.decode_status::<GroupStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
+            }
+        }
+
+        match results.iter().filter(|(_, result)| result.is_err()).count() {
+            0 => Ok(()),
+            n => Err(Error::from(format!("{} account actions failed", n))),
+        }
+    }
+}
+
+#[tokio::main]
+#[cfg(unix)]
+async fn main() {
+    controller::run(AccountsController {
+        root: unix::etc(),
+        managed: Managed::default(),
+        recovered: false,
+    })
+    .await
+}
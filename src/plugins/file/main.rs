@@ -0,0 +1,215 @@
+use cosi::{
+    controller::{self, active, Context, Controller, Error},
+    resource::ResourceKind,
+    spec::engine::{File, FileOriginal, FileStatus, Schema},
+    spec::runtime::{ControllerInput, ControllerOutput},
+    unix::file,
+};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+pub static NAME: &str = "file";
+
+// How often files are checked for drift, e.g. when something else rewrote them.
+const RESYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct FileController {
+    // The files before the controller first wrote them, by path, which are restored once no
+    // resource describes them.
+    originals: BTreeMap<String, FileOriginal>,
+    // Whether the originals were recovered from the status of the resources.
+    recovered: bool,
+}
+
+#[tonic::async_trait]
+impl Controller for FileController {
+    fn name(&self) -> String {
+        NAME.to_owned()
+    }
+
+    fn inputs(&self) -> Vec<ControllerInput> {
+        vec![ControllerInput::strong::<File>()]
+    }
+
+    fn outputs(&self) -> Vec<ControllerOutput> {
+        vec![]
+    }
+
+    fn schemas(&self) -> Vec<Schema> {
+        vec![File::schema()]
+    }
+
+    fn resync_interval(&self) -> Option<Duration> {
+        Some(RESYNC_INTERVAL)
+    }
+
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        let mut client = ctx.typed();
+
+        let listed = client.list::<File>(File::NAMESPACE).await?;
+
+        // N.B.: After a restart, the originals are recovered from the status of the resources,
+        // including those being torn down, so that their files are still restored.
+        if !self.recovered {
+            for resource in &listed {
+                if let Some(FileStatus {
+                    original: Some(original),
+                    ..
+                }) = resource.decode_status::<FileStatus>()?
+                {
+                    self.originals
+                        .entry(resource.spec.path.clone())
+                        .or_insert(original);
+                }
+            }
+
+            self.recovered = true;
+        }
+
+        let mut resources = active(listed);
+
+        resources.sort_by(|a, b| a.metadata.id.cmp(&b.metadata.id));
+
+        // Each file is written by the first resource that describes it, by ID.
+        let mut owners = BTreeMap::new();
+
+        for resource in &resources {
+            owners
+                .entry(resource.spec.path.clone())
+                .or_insert_with(|| resource.metadata.id.clone());
+        }
+
+        let mut failed = 0;
+
+        let released: Vec<String> = self
+            .originals
+            .keys()
+            .filter(|path| !owners.contains_key(*path))
+            .cloned()
+            .collect();
+
+        for path in released {
+            let original = &self.originals[&path];
+
+            match file::restore(Path::new(&path), original) {
+                Ok(()) => {
+                    match original.existed {
+                        true => println!("Restored {}", path),
+                        false => println!("Removed {}", path),
+                    }
+
+                    self.originals.remove(&path);
+                }
+                Err(err) => {
+                    println!("Failed to restore {}: {}", path, err);
+
+                    failed += 1;
+                }
+            }
+        }
+
+        // N.B.: The originals are persisted before the files are first written, so that they
+        // are still restored if the controller is restarted in between. Persisting them is a
+        // reconcile event of its own, which writes the files.
+        let mut persisted = false;
+        let mut unrecorded = BTreeMap::new();
+
+        for resource in &resources {
+            let path = &resource.spec.path;
+
+            if owners[path] != resource.metadata.id || self.originals.contains_key(path) {
+                continue;
+            }
+
+            let original = match record(&resource.spec) {
+                Ok(original) => original,
+                Err(err) => {
+                    unrecorded.insert(path.clone(), err.to_string());
+                    continue;
+                }
+            };
+
+            let mut status = resource.decode_status::<FileStatus>()?.unwrap_or_default();
+
+            status.original = Some(original.clone());
+
+            client.update_status(resource, &status).await?;
+
+            self.originals.insert(path.clone(), original);
+
+            persisted = true;
+        }
+
+        if persisted {
+            return match failed {
+                0 => Ok(()),
+                n => Err(Error::from(format!("{} files failed", n))),
+            };
+        }
+
+        for resource in &resources {
+            let path = &resource.spec.path;
+
+            let result = match (&owners[path], self.originals.get(path)) {
+                (owner, _) if *owner != resource.metadata.id => {
+                    Err(format!("{} is written by {}", path, owner))
+                }
+                (_, Some(original)) => match file::apply(&resource.spec, original) {
+                    Ok(written) => {
+                        if written {
+                            println!("Wrote {}", path);
+                        }
+
+                        Ok(())
+                    }
+                    Err(err) => Err(err.to_string()),
+                },
+                (_, None) => Err(unrecorded.remove(path).unwrap_or_default()),
+            };
+
+            if let Err(err) = &result {
+                println!("Failed to write {}: {}", path, err);
+
+                failed += 1;
+            }
+
+            let original = match &owners[path] {
+                owner if *owner == resource.metadata.id => self.originals.get(path).cloned(),
+                _ => None,
+            };
+
+            let status = FileStatus {
+                in_sync: original
+                    .as_ref()
+                    .map(|original| file::in_sync(&resource.spec, original).unwrap_or_default())
+                    .unwrap_or_default(),
+                original,
+                error: result.err().unwrap_or_default(),
+            };
+
+            if resource.decode_status::<FileStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
+            }
+        }
+
+        match failed {
+            0 => Ok(()),
+            n => Err(Error::from(format!("{} files failed", n))),
+        }
+    }
+}
+
+// Records the file of `spec` as it is before it is first written.
+fn record(spec: &File) -> std::io::Result<FileOriginal> {
+    file::validate(spec)?;
+
+    file::original(Path::new(&spec.path))
+}
+
+#[tokio::main]
+#[cfg(unix)]
+async fn main() {
+    controller::run(FileController::default()).await
+}
@@ -0,0 +1,109 @@
+use cosi::{
+    controller::{self, active, Context, Controller, Error},
+    resource::ResourceKind,
+    spec::engine::{Hostname, HostnameStatus, Schema},
+    spec::runtime::{ControllerInput, ControllerOutput},
+    unix::{self, hostname},
+};
+use std::path::PathBuf;
+
+pub static NAME: &str = "hostname";
+
+struct HostnameController {
+    root: PathBuf,
+}
+
+#[tonic::async_trait]
+impl Controller for HostnameController {
+    fn name(&self) -> String {
+        NAME.to_owned()
+    }
+
+    fn inputs(&self) -> Vec<ControllerInput> {
+        vec![ControllerInput::strong::<Hostname>()]
+    }
+
+    fn outputs(&self) -> Vec<ControllerOutput> {
+        vec![]
+    }
+
+    fn schemas(&self) -> Vec<Schema> {
+        vec![Hostname::schema()]
+    }
+
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        let mut client = ctx.typed();
+
+        let mut resources = active(client.list::<Hostname>(Hostname::NAMESPACE).await?);
+
+        // N.B.: Without any resources, the hostname (e.g. one set by a DHCP client) is left
+        // alone.
+        if resources.is_empty() {
+            return Ok(());
+        }
+
+        // The hostname is set by the first resource, by ID.
+        resources.sort_by(|a, b| a.metadata.id.cmp(&b.metadata.id));
+
+        let owner = resources[0].metadata.id.clone();
+
+        let result = self.apply(&resources[0].spec);
+
+        if let Err(err) = &result {
+            println!("Failed to set the hostname: {}", err);
+        }
+
+        let (hostname, domainname) = hostname::current()?;
+
+        for resource in &resources {
+            let error = match &result {
+                _ if resource.metadata.id != owner => format!("the hostname is set by {}", owner),
+                Ok(()) => String::new(),
+                Err(err) => err.to_string(),
+            };
+
+            let status = HostnameStatus {
+                hostname: hostname.clone(),
+                domainname: domainname.clone(),
+                error,
+            };
+
+            if resource.decode_status::<HostnameStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
+            }
+        }
+
+        result.map_err(Error::from)
+    }
+}
+
+impl HostnameController {
+    // Sets the hostname and domain name of `spec` unless they are already in effect, and writes
+    // the files that name them.
+    fn apply(&self, spec: &Hostname) -> std::io::Result<()> {
+        let (hostname, domainname) = hostname::resolve(spec)?;
+
+        let current = hostname::current()?;
+
+        if current.0 != hostname || (!domainname.is_empty() && current.1 != domainname) {
+            hostname::set(&hostname, &domainname)?;
+
+            println!("Set the hostname to {}", hostname);
+        }
+
+        // N.B.: An empty domain name leaves the current one in effect.
+        let (_, domainname) = hostname::current()?;
+
+        for path in hostname::sync(&self.root, &hostname, &domainname)? {
+            println!("Wrote {}", path.display());
+        }
+
+        Ok(())
+    }
+}
+
+#[tokio::main]
+#[cfg(unix)]
+async fn main() {
+    controller::run(HostnameController { root: unix::etc() }).await
+}
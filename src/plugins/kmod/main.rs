@@ -0,0 +1,101 @@
+use cosi::{
+    controller::{self, active, Context, Controller, Error},
+    resource::ResourceKind,
+    spec::engine::{KernelModule, KernelModuleStatus, Schema},
+    spec::runtime::{ControllerInput, ControllerOutput},
+    unix::kmod::{self, Action, KernelLoader, Loader, Modules},
+};
+use std::collections::HashMap;
+
+pub static NAME: &str = "kmod";
+
+struct KmodController {
+    modules: Modules<KernelLoader>,
+}
+
+#[tonic::async_trait]
+impl Controller for KmodController {
+    fn name(&self) -> String {
+        NAME.to_owned()
+    }
+
+    fn inputs(&self) -> Vec<ControllerInput> {
+        vec![ControllerInput::strong::<KernelModule>()]
+    }
+
+    fn outputs(&self) -> Vec<ControllerOutput> {
+        vec![]
+    }
+
+    fn schemas(&self) -> Vec<Schema> {
+        vec![KernelModule::schema()]
+    }
+
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        let mut client = ctx.typed();
+
+        let resources = active(client.list::<KernelModule>(KernelModule::NAMESPACE).await?);
+
+        let desired: Vec<KernelModule> = resources.iter().map(|r| r.spec.clone()).collect();
+
+        let mut errors = HashMap::new();
+
+        for (action, result) in self.modules.converge(&desired)? {
+            match (action, result) {
+                (Action::Load(module), Ok(())) => println!("Loaded {}", module),
+                (Action::Load(module), Err(err)) => {
+                    println!("Failed to load {}: {}", module, err);
+
+                    errors.insert(module, err.to_string());
+                }
+                (Action::Unload(module), Ok(())) => println!("Unloaded {}", module),
+                (Action::Unload(module), Err(err)) => {
+                    println!("Failed to unload {}: {}", module, err);
+
+                    errors.insert(module, err.to_string());
+                }
+            }
+        }
+
+        let index = self.modules.index()?;
+        let loaded = self.modules.loader().loaded()?;
+
+        for resource in &resources {
+            let module = kmod::name(&resource.spec.name);
+
+            let order = index.resolve(&module).unwrap_or_default();
+
+            // N.B.: The error of a module is that of the first module it needs that failed.
+            let error = order
+                .iter()
+                .chain(std::iter::once(&module))
+                .find_map(|module| errors.get(module))
+                .cloned()
+                .unwrap_or_default();
+
+            let status = KernelModuleStatus {
+                loaded: loaded.contains(&module) || index.is_builtin(&module),
+                dependencies: order.into_iter().filter(|m| *m != module).collect(),
+                error,
+            };
+
+            if resource.decode_status::<KernelModuleStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
+            }
+        }
+
+        match errors.len() {
+            0 => Ok(()),
+            n => Err(Error::from(format!("{} module actions failed", n))),
+        }
+    }
+}
+
+#[tokio::main]
+#[cfg(unix)]
+async fn main() {
+    controller::run(KmodController {
+        modules: Modules::new(kmod::root(), KernelLoader),
+    })
+    .await
+}
@@ -1,121 +1,96 @@
 use cosi::{
-    machinery::plugin,
-    spec::engine::Mount,
-    spec::runtime::{
-        ControllerInput, ControllerInputKind, ControllerOutput, ControllerOutputKind,
-        ReconcileEventsRequest, RegisterControllerRequest, RuntimeListRequest, StartRequest,
-    },
+    controller::{self, active, Context, Controller, Error},
+    resource::ResourceKind,
+    spec::engine::{Mount, MountStatus, Schema},
+    spec::runtime::{ControllerInput, ControllerOutput},
+    unix::mount::{self, Action},
 };
-use std::io::{stdin, Read};
+use std::collections::{BTreeMap, HashMap};
 
 pub static NAME: &str = "mount";
 
-// Resource.
-pub static API: &str = "cosi.dev";
-pub static VERSION: &str = "v1alpha1";
-pub static KIND: &str = "Mount";
-pub static NAMESPACE: &str = "system";
-
-#[tokio::main]
-#[cfg(unix)]
-async fn main() {
-    std::thread::sleep(std::time::Duration::from_secs(1));
-
-    let mut buffer = String::new();
-    stdin().read_to_string(&mut buffer).unwrap();
+#[derive(Default)]
+struct MountController {
+    // The mounts made by the controller, by target, which it unmounts once they are no longer
+    // desired.
+    managed: BTreeMap<String, Mount>,
+}
 
-    let socket = buffer.as_str().to_owned();
+#[tonic::async_trait]
+impl Controller for MountController {
+    fn name(&self) -> String {
+        NAME.to_owned()
+    }
 
-    println!("Registering at {}", socket);
+    fn inputs(&self) -> Vec<ControllerInput> {
+        vec![ControllerInput::strong::<Mount>()]
+    }
 
-    let r = plugin::register(socket.clone(), NAME.to_owned()).await;
+    fn outputs(&self) -> Vec<ControllerOutput> {
+        vec![]
+    }
 
-    match r {
-        Ok(_) => println!("Registered {}", NAME),
-        Err(err) => match err.code() {
-            tonic::Code::AlreadyExists => println!("Already registered"),
-            _ => std::process::exit(1),
-        },
+    fn schemas(&self) -> Vec<Schema> {
+        vec![Mount::schema()]
     }
 
-    let address = String::from("127.0.0.1:50000");
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        let mut client = ctx.typed();
 
-    let mut client = cosi::machinery::runtime::client::connect_runtime_tcp(address.clone())
-        .await
-        .unwrap();
+        let resources = active(client.list::<Mount>(Mount::NAMESPACE).await?);
 
-    let inputs = vec![ControllerInput {
-        kind: ControllerInputKind::Strong as i32,
-        namespace: NAMESPACE.to_string(),
-        r#type: KIND.to_string(),
-        id: None,
-    }];
+        let desired: Vec<Mount> = resources.iter().map(|r| r.spec.clone()).collect();
 
-    let outputs = vec![ControllerOutput {
-        r#type: format!("{}Status", KIND.to_string()),
-        kind: ControllerOutputKind::Shared as i32,
-    }];
+        let mut errors = HashMap::new();
 
-    let request = RegisterControllerRequest {
-        controller_name: NAME.to_string(),
-        outputs,
-        inputs,
-    };
+        for (action, result) in mount::converge(&desired, &mut self.managed)? {
+            match (action, result) {
+                (Action::Mount(spec), Ok(())) => println!("Mounted {}", spec.target),
+                (Action::Mount(spec), Err(err)) => {
+                    println!("Failed to mount {}: {}", spec.target, err);
 
-    client.register_controller(request).await.unwrap();
+                    errors.insert(spec.target, err.to_string());
+                }
+                (Action::Unmount(target), Ok(())) => println!("Unmounted {}", target),
+                (Action::Unmount(target), Err(err)) => {
+                    println!("Failed to unmount {}: {}", target, err);
 
-    tokio::spawn(async {
-        let mut client = cosi::machinery::runtime::client::connect_adapter_tcp(address)
-            .await
-            .unwrap();
+                    errors.insert(target, err.to_string());
+                }
+            }
+        }
 
-        let request = ReconcileEventsRequest {
-            controller_token: NAME.to_string(),
-        };
+        let mounted = mount::mountinfo()?;
 
-        let mut stream = client.reconcile_events(request).await.unwrap().into_inner();
+        for resource in &resources {
+            let info = mount::find(&mounted, &resource.spec.target);
 
-        while let Some(_event) = stream.message().await.unwrap() {
-            let request = RuntimeListRequest {
-                controller_token: NAME.to_string(),
-                namespace: NAMESPACE.to_string(),
-                r#type: KIND.to_string(),
+            let status = MountStatus {
+                mounted: info.is_some(),
+                source: info.map(|i| i.source.clone()).unwrap_or_default(),
+                r#type: info.map(|i| i.fs_type.clone()).unwrap_or_default(),
+                error: errors
+                    .get(&resource.spec.target)
+                    .cloned()
+                    .unwrap_or_default(),
             };
 
-            let mut stream = client.list(request).await.unwrap().into_inner();
-            while let Some(response) = stream.message().await.unwrap() {
-                if let Some(resource) = response.resource {
-                    // TODO: Read /proc/self/mountinfo.
-
-                    if let Some(metadata) = resource.metadata {
-                        println!("{:?}", metadata);
-                        if metadata.r#type != "Mount" {
-                            continue;
-                        }
-                    }
-
-                    if let Some(spec) = resource.spec {
-                        let mount: Mount = serde_yaml::from_str(&spec.yaml_spec).unwrap();
-                        println!("{:?}", mount);
-                    }
-                }
+            // N.B.: The status is only written when it changes, since every write is a reconcile
+            // event of its own.
+            if resource.decode_status::<MountStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
             }
         }
-    });
-
-    client.start(StartRequest {}).await.unwrap();
-
-    cosi::unix::handle_signals(
-        || {
-            std::process::exit(0);
-        },
-        || {
-            std::process::exit(0);
-        },
-        || {
-            std::process::exit(0);
-        },
-        || false,
-    )
-    .await;
+
+        match errors.len() {
+            0 => Ok(()),
+            n => Err(Error::from(format!("{} mount actions failed", n))),
+        }
+    }
+}
+
+#[tokio::main]
+#[cfg(unix)]
+async fn main() {
+    controller::run(MountController::default()).await
 }
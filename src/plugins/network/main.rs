@@ -0,0 +1,187 @@
+use cosi::{
+    controller::{self, active, error, Context, Controller, Error},
+    resource::ResourceKind,
+    spec::engine::{Address, AddressStatus, Link, LinkStatus, Route, RouteStatus, Schema},
+    spec::runtime::{ControllerInput, ControllerOutput},
+    unix::network::{self, Action, Network},
+};
+
+pub static NAME: &str = "network";
+
+struct NetworkController {
+    network: Network,
+    // Whether the links, addresses and routes the controller created were recovered from the
+    // status of the resources.
+    recovered: bool,
+}
+
+#[tonic::async_trait]
+impl Controller for NetworkController {
+    fn name(&self) -> String {
+        NAME.to_owned()
+    }
+
+    fn inputs(&self) -> Vec<ControllerInput> {
+        vec![
+            ControllerInput::strong::<Link>(),
+            ControllerInput::strong::<Address>(),
+            ControllerInput::strong::<Route>(),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<ControllerOutput> {
+        vec![]
+    }
+
+    fn schemas(&self) -> Vec<Schema> {
+        vec![Link::schema(), Address::schema(), Route::schema()]
+    }
+
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        let mut client = ctx.typed();
+
+        let links = client.list::<Link>(Link::NAMESPACE).await?;
+        let addresses = client.list::<Address>(Address::NAMESPACE).await?;
+        let routes = client.list::<Route>(Route::NAMESPACE).await?;
+
+        // N.B.: After a restart, the links, addresses and routes the controller created are
+        // recovered from the status of the resources, including those being torn down, so that
+        // they are still removed.
+        if !self.recovered {
+            let mut created_links = vec![];
+            let mut created_addresses = vec![];
+            let mut created_routes = vec![];
+
+            for resource in &links {
+                if let Some(status) = resource.decode_status::<LinkStatus>()? {
+                    if status.created {
+                        created_links.push(resource.spec.clone());
+                    }
+                }
+            }
+
+            for resource in &addresses {
+                if let Some(status) = resource.decode_status::<AddressStatus>()? {
+                    if status.created {
+                        created_addresses.push(resource.spec.clone());
+                    }
+                }
+            }
+
+            for resource in &routes {
+                if let Some(status) = resource.decode_status::<RouteStatus>()? {
+                    if status.created {
+                        created_routes.push(resource.spec.clone());
+                    }
+                }
+            }
+
+            self.network
+                .adopt(&created_links, &created_addresses, &created_routes);
+
+            self.recovered = true;
+        }
+
+        let links = active(links);
+        let addresses = active(addresses);
+        let routes = active(routes);
+
+        let results: Vec<(Action, Result<(), String>)> = self
+            .network
+            .converge(
+                &links.iter().map(|r| r.spec.clone()).collect::<Vec<_>>(),
+                &addresses.iter().map(|r| r.spec.clone()).collect::<Vec<_>>(),
+                &routes.iter().map(|r| r.spec.clone()).collect::<Vec<_>>(),
+            )?
+            .into_iter()
+            .map(|(action, result)| {
+                match &result {
+                    Ok(()) => println!("{}: done", action),
+                    Err(err) => println!("{}: {}", action, err),
+                }
+
+                (action, result.map_err(|err| err.to_string()))
+            })
+            .collect();
+
+        let socket = self.network.socket();
+
+        let current = socket.links()?;
+        let assigned = socket.addresses()?;
+        let installed = socket.routes()?;
+
+        for resource in &links {
+            let info = current.iter().find(|info| info.name == resource.spec.name);
+
+            let status = LinkStatus {
+                index: info.map(|i| i.index).unwrap_or_default(),
+                up: info.map(|i| i.up).unwrap_or_default(),
+                mtu: info.map(|i| i.mtu).unwrap_or_default(),
+                hardware_address: info.map(|i| i.address.clone()).unwrap_or_default(),
+                operational_state: info
+                    .map(|i| i.operational_state.clone())
+                    .unwrap_or_default(),
+                error: error(&results, |action| match action {
+                    Action::CreateLink(link) | Action::ConfigureLink(link) => {
+                        link.name == resource.spec.name
+                    }
+                    _ => false,
+                }),
+                created: self.network.created_link(&resource.spec.name),
+            };
+
+            if resource.decode_status::<LinkStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
+            }
+        }
+
+        for resource in &addresses {
+            let status = AddressStatus {
+                assigned: network::address_info(&resource.spec, &current)
+                    .map(|info| assigned.contains(&info))
+                    .unwrap_or_default(),
+                error: error(&results, |action| {
+                    *action == Action::AddAddress(resource.spec.clone())
+                }),
+                created: self.network.created_address(&resource.spec),
+            };
+
+            if resource.decode_status::<AddressStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
+            }
+        }
+
+        for resource in &routes {
+            let status = RouteStatus {
+                installed: network::route_info(&resource.spec, &current)
+                    .map(|info| network::installed(&installed, &info))
+                    .unwrap_or_default(),
+                error: error(&results, |action| {
+                    *action == Action::AddRoute(resource.spec.clone())
+                }),
+                created: self.network.created_route(&resource.spec),
+            };
+
+            if resource.decode_status::<RouteStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
+            }
+        }
+
+        match results.iter().filter(|(_, result)| result.is_err()).count() {
+            0 => Ok(()),
+            n => Err(Error::from(format!("{} network actions failed", n))),
+        }
+    }
+}
+
+#[tokio::main]
+#[cfg(unix)]
+async fn main() {
+    let network = Network::new().expect("failed to open an rtnetlink socket");
+
+    controller::run(NetworkController {
+        network,
+        recovered: false,
+    })
+    .await
+}
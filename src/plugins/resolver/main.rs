@@ -1,25 +1,68 @@
+use cosi::{
+    controller::{self, active, Context, Controller, Error},
+    resource::ResourceKind,
+    spec::engine::{Resolver, Schema},
+    spec::runtime::{ControllerInput, ControllerOutput},
+    unix::{self, resolver},
+};
+use std::path::PathBuf;
+
 pub static NAME: &str = "resolver";
 
-// Resource.
-pub static API: &str = "cosi.dev";
-pub static VERSION: &str = "v1alpha1";
-pub static KIND: &str = "Resolver";
-pub static NAMESPACE: &str = "system";
+struct ResolverController {
+    path: PathBuf,
+}
+
+#[tonic::async_trait]
+impl Controller for ResolverController {
+    fn name(&self) -> String {
+        NAME.to_owned()
+    }
+
+    fn inputs(&self) -> Vec<ControllerInput> {
+        vec![ControllerInput::strong::<Resolver>()]
+    }
+
+    fn outputs(&self) -> Vec<ControllerOutput> {
+        vec![]
+    }
+
+    fn schemas(&self) -> Vec<Schema> {
+        vec![Resolver::schema()]
+    }
+
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        let resolvers = active(ctx.typed().list::<Resolver>(Resolver::NAMESPACE).await?);
+
+        // N.B.: Without any resolvers, the existing file (e.g. one written by a DHCP client) is
+        // left alone.
+        if resolvers.is_empty() {
+            return Ok(());
+        }
+
+        let merged = resolver::merge(&resolvers);
+
+        resolver::validate(&merged)?;
+
+        let conf = resolver::render(&merged);
+
+        if std::fs::read_to_string(&self.path).ok().as_deref() == Some(conf.as_str()) {
+            return Ok(());
+        }
+
+        unix::write_atomically(&self.path, conf.as_bytes())?;
+
+        println!("Wrote {}", self.path.display());
+
+        Ok(())
+    }
+}
 
 #[tokio::main]
 #[cfg(unix)]
 async fn main() {
-    cosi::unix::handle_signals(
-        || {
-            std::process::exit(0);
-        },
-        || {
-            std::process::exit(0);
-        },
-        || {
-            std::process::exit(0);
-        },
-        || false,
-    )
-    .await;
+    controller::run(ResolverController {
+        path: resolver::path(),
+    })
+    .await
 }
@@ -0,0 +1,166 @@
+use cosi::{
+    controller::{self, active, Context, Controller, Error},
+    resource::ResourceKind,
+    spec::engine::{KernelParameter, KernelParameterStatus, Schema},
+    spec::runtime::{ControllerInput, ControllerOutput},
+    unix::sysctl,
+};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+pub static NAME: &str = "sysctl";
+
+struct SysctlController {
+    root: PathBuf,
+    // The values of the keys before the controller first wrote them, which are restored once no
+    // resource sets them.
+    originals: BTreeMap<String, String>,
+}
+
+#[tonic::async_trait]
+impl Controller for SysctlController {
+    fn name(&self) -> String {
+        NAME.to_owned()
+    }
+
+    fn inputs(&self) -> Vec<ControllerInput> {
+        vec![ControllerInput::strong::<KernelParameter>()]
+    }
+
+    fn outputs(&self) -> Vec<ControllerOutput> {
+        vec![]
+    }
+
+    fn schemas(&self) -> Vec<Schema> {
+        vec![KernelParameter::schema()]
+    }
+
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        let mut client = ctx.typed();
+
+        let mut resources = active(
+            client
+                .list::<KernelParameter>(KernelParameter::NAMESPACE)
+                .await?,
+        );
+
+        resources.sort_by(|a, b| a.metadata.id.cmp(&b.metadata.id));
+
+        // N.B.: After a restart, the original values are recovered from the status of the
+        // resources.
+        for resource in &resources {
+            if let Some(status) = resource.decode_status::<KernelParameterStatus>()? {
+                if !status.original.is_empty() {
+                    self.originals
+                        .entry(resource.spec.key.clone())
+                        .or_insert(status.original);
+                }
+            }
+        }
+
+        // Each key is set by the first resource that sets it, by ID.
+        let mut owners = BTreeMap::new();
+
+        for resource in &resources {
+            owners
+                .entry(resource.spec.key.clone())
+                .or_insert_with(|| resource.metadata.id.clone());
+        }
+
+        let mut failed = 0;
+
+        let released: Vec<String> = self
+            .originals
+            .keys()
+            .filter(|key| !owners.contains_key(*key))
+            .cloned()
+            .collect();
+
+        for key in released {
+            match sysctl::write(&self.root, &key, &self.originals[&key]) {
+                Ok(value) => {
+                    println!("Restored {} to {}", key, value);
+
+                    self.originals.remove(&key);
+                }
+                Err(err) => {
+                    println!("Failed to restore {}: {}", key, err);
+
+                    failed += 1;
+                }
+            }
+        }
+
+        for resource in &resources {
+            let key = &resource.spec.key;
+
+            let result = match &owners[key] {
+                owner if *owner != resource.metadata.id => {
+                    Err(format!("{} is set by {}", key, owner))
+                }
+                _ => self
+                    .apply(key, &resource.spec.value)
+                    .map_err(|err| err.to_string()),
+            };
+
+            let status = match result {
+                Ok(value) => KernelParameterStatus {
+                    value,
+                    original: self.originals.get(key).cloned().unwrap_or_default(),
+                    error: String::new(),
+                },
+                Err(error) => {
+                    println!("Failed to set {}: {}", key, error);
+
+                    failed += 1;
+
+                    KernelParameterStatus {
+                        value: sysctl::read(&self.root, key).unwrap_or_default(),
+                        original: self.originals.get(key).cloned().unwrap_or_default(),
+                        error,
+                    }
+                }
+            };
+
+            if resource.decode_status::<KernelParameterStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
+            }
+        }
+
+        match failed {
+            0 => Ok(()),
+            n => Err(Error::from(format!("{} kernel parameters failed", n))),
+        }
+    }
+}
+
+impl SysctlController {
+    // Writes `value` to `key`, recording the original value of the key first.
+    fn apply(&mut self, key: &str, value: &str) -> Result<String, sysctl::SysctlError> {
+        let current = sysctl::read(&self.root, key)?;
+
+        if !self.originals.contains_key(key) {
+            self.originals.insert(key.to_owned(), current.clone());
+        }
+
+        if current.split_whitespace().eq(value.split_whitespace()) {
+            return Ok(current);
+        }
+
+        let value = sysctl::write(&self.root, key, value)?;
+
+        println!("Set {} to {}", key, value);
+
+        Ok(value)
+    }
+}
+
+#[tokio::main]
+#[cfg(unix)]
+async fn main() {
+    controller::run(SysctlController {
+        root: sysctl::root(),
+        originals: BTreeMap::new(),
+    })
+    .await
+}
@@ -0,0 +1,183 @@
+use cosi::{
+    controller::{self, active, Context, Controller, Error},
+    resource::{ResourceKind, TypedResource},
+    spec::engine::{Schema, TimeServer, TimeServerStatus, TimeStatus},
+    spec::runtime::{ControllerInput, ControllerOutput, ControllerOutputKind},
+    unix::sntp::{self, Adjustment, Sample},
+};
+use std::time::{Duration, Instant};
+
+pub static NAME: &str = "time";
+
+// The ID of the `TimeStatus` resource the controller publishes.
+static STATUS_ID: &str = "time";
+
+// How often the servers are polled, as the minimum poll interval of RFC 4330.
+const POLL_INTERVAL: Duration = Duration::from_secs(64);
+
+// How soon servers may be polled again, e.g. when their resources change. RFC 4330 asks clients
+// not to poll more often than every 15 seconds.
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(16);
+
+// How long to wait for a server to answer.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+struct TimeController {
+    // When the clock was last synchronized, and with which servers.
+    last_poll: Option<(Instant, Vec<String>)>,
+}
+
+#[tonic::async_trait]
+impl Controller for TimeController {
+    fn name(&self) -> String {
+        NAME.to_owned()
+    }
+
+    fn inputs(&self) -> Vec<ControllerInput> {
+        vec![ControllerInput::strong::<TimeServer>()]
+    }
+
+    fn outputs(&self) -> Vec<ControllerOutput> {
+        vec![ControllerOutput {
+            r#type: TimeStatus::TYPE.to_string(),
+            kind: ControllerOutputKind::Exclusive as i32,
+            api: TimeStatus::API.to_string(),
+        }]
+    }
+
+    fn schemas(&self) -> Vec<Schema> {
+        vec![TimeServer::schema(), TimeStatus::schema()]
+    }
+
+    fn resync_interval(&self) -> Option<Duration> {
+        Some(POLL_INTERVAL)
+    }
+
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        let mut client = ctx.typed();
+
+        let mut resources = active(client.list::<TimeServer>(TimeServer::NAMESPACE).await?);
+
+        resources.sort_by(|a, b| a.metadata.id.cmp(&b.metadata.id));
+
+        let addresses: Vec<_> = resources.iter().map(|r| r.spec.address.clone()).collect();
+
+        // N.B.: Writing the status of the servers is a reconcile event of its own, which must
+        // not poll them again.
+        if let Some((instant, polled)) = &self.last_poll {
+            if *polled == addresses && instant.elapsed() < MIN_POLL_INTERVAL {
+                return Ok(());
+            }
+        }
+
+        // N.B.: Polls block the controller, which has nothing else to do in the meantime.
+        let samples: Vec<_> = addresses
+            .iter()
+            .map(|address| {
+                let result = sntp::query(address, TIMEOUT);
+
+                if let Err(err) = &result {
+                    println!("Failed to poll {}: {}", address, err);
+                }
+
+                result
+            })
+            .collect();
+
+        for (resource, result) in resources.iter().zip(&samples) {
+            let status = match result {
+                Ok(sample) => TimeServerStatus {
+                    reachable: true,
+                    stratum: u32::from(sample.stratum),
+                    error: String::new(),
+                },
+                Err(err) => TimeServerStatus {
+                    error: err.to_string(),
+                    ..Default::default()
+                },
+            };
+
+            if resource.decode_status::<TimeServerStatus>()? != Some(status.clone()) {
+                client.update_status(resource, &status).await?;
+            }
+        }
+
+        // The clock is synchronized with the server with the lowest round-trip delay.
+        let best = addresses
+            .iter()
+            .zip(&samples)
+            .filter_map(|(address, result)| result.as_ref().ok().map(|sample| (address, sample)))
+            .min_by_key(|(_, sample)| sample.delay);
+
+        // N.B.: Without any servers, the clock is left alone.
+        let result = match best {
+            Some((address, sample)) => synchronize(address, sample),
+            None if addresses.is_empty() => Ok(()),
+            None => Err(Error::from("no time server answered")),
+        };
+
+        let status = match (&result, best) {
+            (Ok(()), Some((address, sample))) => TimeStatus {
+                synced: true,
+                offset: sample.offset,
+                stratum: u32::from(sample.stratum),
+                server: address.clone(),
+            },
+            _ => TimeStatus::default(),
+        };
+
+        match client
+            .get::<TimeStatus>(TimeStatus::NAMESPACE, STATUS_ID)
+            .await?
+        {
+            None => {
+                client
+                    .create(&TypedResource::new(STATUS_ID, status))
+                    .await?
+            }
+            Some(mut resource) if resource.spec != status => {
+                resource.spec = status;
+
+                client.update(&resource).await?;
+            }
+            Some(_) => {}
+        }
+
+        if result.is_ok() {
+            self.last_poll = Some((Instant::now(), addresses));
+        }
+
+        result
+    }
+}
+
+// Adjusts the clock by the offset of `sample`, polled from `address`.
+fn synchronize(address: &str, sample: &Sample) -> Result<(), Error> {
+    let adjustment = sntp::adjust(sample).map_err(|err| {
+        println!("Failed to adjust the clock: {}", err);
+
+        err
+    })?;
+
+    let verb = match adjustment {
+        Adjustment::Slewed => "Slewed",
+        Adjustment::Stepped => "Stepped",
+    };
+
+    println!(
+        "{} the clock by {:.3}ms towards {} (stratum {})",
+        verb,
+        sample.offset as f64 / 1e6,
+        address,
+        sample.stratum
+    );
+
+    Ok(())
+}
+
+#[tokio::main]
+#[cfg(unix)]
+async fn main() {
+    controller::run(TimeController::default()).await
+}
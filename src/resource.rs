@@ -0,0 +1,417 @@
+//! Typed access to resources.
+//!
+//! On the wire, a resource carries its spec as either a JSON (or YAML) string in
+//! `Spec.yaml_spec`, or protobuf bytes in `Spec.proto_spec`. `TypedResource` converts between
+//! that and a Rust type that implements `ResourceKind`.
+
+use crate::consts;
+use crate::machinery::state::api;
+use crate::spec::engine::{
+    Address, File, Group, Hostname, KernelModule, KernelParameter, Link, Mount, Resolver, Route,
+    Schema, TimeServer, TimeStatus, User,
+};
+use crate::spec::resource::{Metadata, OwnerReference, Resource, ResourceStatus, Spec};
+use crate::spec::runtime::{
+    controller_adapter_client::ControllerAdapterClient, RuntimeCreateRequest, RuntimeGetRequest,
+    RuntimeListRequest, RuntimeUpdateRequest, RuntimeUpdateStatusRequest,
+};
+use crate::spec::FILE_DESCRIPTOR_SET;
+use serde::{de::DeserializeOwned, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use tonic::transport::Channel;
+use tonic::Code;
+
+/// ResourceKind identifies the type of a resource spec.
+pub trait ResourceKind: prost::Message + Serialize + DeserializeOwned + Default {
+    /// The API group of the type (e.g. `cosi.dev`).
+    const API: &'static str;
+    /// The version of the API group the spec is written in (e.g. `v1alpha1`).
+    const VERSION: &'static str;
+    const TYPE: &'static str;
+    /// The namespace resources of this type are created in by default.
+    const NAMESPACE: &'static str;
+    /// The fully qualified name of the spec's message (e.g. `engine.Mount`).
+    const MESSAGE: &'static str;
+
+    /// Returns the schema of the spec, to register with the engine.
+    fn schema() -> Schema {
+        Schema {
+            r#type: Self::TYPE.to_owned(),
+            descriptor_set: FILE_DESCRIPTOR_SET.to_vec(),
+            message: Self::MESSAGE.to_owned(),
+            json_schema: String::new(),
+            api: Self::API.to_owned(),
+            owner: String::new(),
+        }
+    }
+}
+
+// Implements `ResourceKind` for the built-in types, whose type is the name of their message.
+macro_rules! resource_kind {
+    ($($kind:ident),* $(,)?) => {
+        $(
+            impl ResourceKind for $kind {
+                const API: &'static str = consts::API;
+                const VERSION: &'static str = consts::API_VERSION;
+                const TYPE: &'static str = stringify!($kind);
+                const NAMESPACE: &'static str = consts::NAMESPACE_SYSTEM;
+                const MESSAGE: &'static str = concat!("engine.", stringify!($kind));
+            }
+        )*
+    };
+}
+
+resource_kind!(
+    Mount,
+    KernelParameter,
+    File,
+    Group,
+    Hostname,
+    KernelModule,
+    Link,
+    Address,
+    Route,
+    Resolver,
+    TimeServer,
+    TimeStatus,
+    User,
+);
+
+#[derive(Debug)]
+pub enum ResourceError {
+    /// The resource has no metadata.
+    MissingMetadata,
+    /// The resource is of another type.
+    TypeMismatch {
+        expected: String,
+        found: String,
+    },
+    /// The spec is written in another version of the API group.
+    VersionMismatch {
+        expected: String,
+        found: String,
+    },
+    YamlError(serde_yaml::Error),
+    JsonError(serde_json::Error),
+    DecodeError(prost::DecodeError),
+    Status(Box<tonic::Status>),
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResourceError::MissingMetadata => write!(f, "resource has no metadata"),
+            ResourceError::TypeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "expected a resource of type {}, found {}",
+                    expected, found
+                )
+            }
+            ResourceError::VersionMismatch { expected, found } => {
+                write!(
+                    f,
+                    "expected a resource of version {}, found {}",
+                    expected, found
+                )
+            }
+            ResourceError::YamlError(err) => write!(f, "invalid spec: {}", err),
+            ResourceError::JsonError(err) => write!(f, "invalid spec: {}", err),
+            ResourceError::DecodeError(err) => write!(f, "invalid spec: {}", err),
+            ResourceError::Status(status) => write!(f, "{}", status),
+        }
+    }
+}
+
+impl std::error::Error for ResourceError {}
+
+impl From<serde_yaml::Error> for ResourceError {
+    fn from(error: serde_yaml::Error) -> ResourceError {
+        ResourceError::YamlError(error)
+    }
+}
+
+impl From<serde_json::Error> for ResourceError {
+    fn from(error: serde_json::Error) -> ResourceError {
+        ResourceError::JsonError(error)
+    }
+}
+
+impl From<prost::DecodeError> for ResourceError {
+    fn from(error: prost::DecodeError) -> ResourceError {
+        ResourceError::DecodeError(error)
+    }
+}
+
+impl From<tonic::Status> for ResourceError {
+    fn from(status: tonic::Status) -> ResourceError {
+        ResourceError::Status(Box::new(status))
+    }
+}
+
+/// TypedResource is a resource whose spec is decoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedResource<T> {
+    pub metadata: Metadata,
+    pub spec: T,
+    /// The status last reported by the controller that owns the type, if any.
+    pub status: Option<ResourceStatus>,
+}
+
+impl<T> TypedResource<T>
+where
+    T: ResourceKind,
+{
+    /// Returns a resource in the default namespace of `T`.
+    pub fn new(id: &str, spec: T) -> TypedResource<T> {
+        TypedResource {
+            metadata: Metadata {
+                api: T::API.to_owned(),
+                api_version: T::VERSION.to_owned(),
+                namespace: T::NAMESPACE.to_owned(),
+                r#type: T::TYPE.to_owned(),
+                id: id.to_owned(),
+                ..Default::default()
+            },
+            spec,
+            status: None,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.metadata.id
+    }
+
+    /// Returns a reference to the resource, for its dependents to be owned by it.
+    pub fn reference(&self) -> OwnerReference {
+        OwnerReference {
+            namespace: self.metadata.namespace.clone(),
+            r#type: self.metadata.r#type.clone(),
+            id: self.metadata.id.clone(),
+            api: self.metadata.api.clone(),
+        }
+    }
+
+    /// Makes the resource a dependent of `owner`, so that it is garbage collected along with it.
+    pub fn owned_by(mut self, owner: OwnerReference) -> TypedResource<T> {
+        self.metadata.owner_references.push(owner);
+        self
+    }
+
+    /// Encodes the resource for the wire. The spec is encoded as JSON in `yaml_spec`, in the
+    /// version of `T`.
+    pub fn encode(&self) -> Result<Resource, ResourceError> {
+        Ok(Resource {
+            metadata: Some(Metadata {
+                api_version: T::VERSION.to_owned(),
+                ..self.metadata.clone()
+            }),
+            spec: Some(Spec {
+                proto_spec: vec![],
+                yaml_spec: serde_json::to_string(&self.spec)?,
+            }),
+            status: self.status.clone(),
+        })
+    }
+
+    /// Decodes the status of the resource from its JSON (or YAML) form. A resource without a
+    /// status decodes to `None`.
+    pub fn decode_status<S>(&self) -> Result<Option<S>, ResourceError>
+    where
+        S: DeserializeOwned,
+    {
+        match &self.status {
+            Some(status) if !status.yaml_status.trim().is_empty() => {
+                Ok(Some(serde_yaml::from_str(&status.yaml_status)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Decodes a resource from the wire. The spec is read from `yaml_spec` if it is set, and from
+    /// `proto_spec` otherwise. A resource without a spec decodes to the default spec, and one
+    /// without a version is taken to be in the version of `T`.
+    pub fn decode(resource: Resource) -> Result<TypedResource<T>, ResourceError> {
+        let mut metadata = resource.metadata.ok_or(ResourceError::MissingMetadata)?;
+
+        if api(&metadata.api) != T::API || metadata.r#type != T::TYPE {
+            return Err(ResourceError::TypeMismatch {
+                expected: format!("{}/{}", T::API, T::TYPE),
+                found: format!("{}/{}", api(&metadata.api), metadata.r#type),
+            });
+        }
+
+        if !metadata.api_version.is_empty() && metadata.api_version != T::VERSION {
+            return Err(ResourceError::VersionMismatch {
+                expected: T::VERSION.to_owned(),
+                found: metadata.api_version,
+            });
+        }
+
+        metadata.api_version = T::VERSION.to_owned();
+
+        let spec = match resource.spec {
+            Some(spec) if !spec.yaml_spec.trim().is_empty() => {
+                serde_yaml::from_str(&spec.yaml_spec)?
+            }
+            Some(spec) => T::decode(spec.proto_spec.as_slice())?,
+            None => T::default(),
+        };
+
+        Ok(TypedResource {
+            metadata,
+            spec,
+            status: resource.status,
+        })
+    }
+}
+
+impl<T> TryFrom<Resource> for TypedResource<T>
+where
+    T: ResourceKind,
+{
+    type Error = ResourceError;
+
+    fn try_from(resource: Resource) -> Result<TypedResource<T>, ResourceError> {
+        TypedResource::decode(resource)
+    }
+}
+
+/// TypedClient reads and writes typed resources through the runtime's adapter API on behalf of a
+/// controller.
+#[derive(Debug, Clone)]
+pub struct TypedClient {
+    client: ControllerAdapterClient<Channel>,
+    token: String,
+}
+
+impl TypedClient {
+    pub fn new(client: ControllerAdapterClient<Channel>, token: String) -> TypedClient {
+        TypedClient { client, token }
+    }
+
+    /// Returns the resource of type `T` with `id` in `namespace`, if it exists.
+    pub async fn get<T>(
+        &mut self,
+        namespace: &str,
+        id: &str,
+    ) -> Result<Option<TypedResource<T>>, ResourceError>
+    where
+        T: ResourceKind,
+    {
+        let request = RuntimeGetRequest {
+            controller_token: self.token.clone(),
+            namespace: namespace.to_owned(),
+            r#type: T::TYPE.to_owned(),
+            id: id.to_owned(),
+            api: T::API.to_owned(),
+        };
+
+        match self.client.get(request).await {
+            Ok(response) => match response.into_inner().resource {
+                Some(resource) => Ok(Some(TypedResource::decode(resource)?)),
+                None => Ok(None),
+            },
+            Err(status) if status.code() == Code::NotFound => Ok(None),
+            Err(status) => Err(status.into()),
+        }
+    }
+
+    /// Returns the resources of type `T` in `namespace`.
+    pub async fn list<T>(&mut self, namespace: &str) -> Result<Vec<TypedResource<T>>, ResourceError>
+    where
+        T: ResourceKind,
+    {
+        self.select(namespace, "").await
+    }
+
+    /// Returns the resources of type `T` in `namespace` with labels matching `selector`.
+    pub async fn select<T>(
+        &mut self,
+        namespace: &str,
+        selector: &str,
+    ) -> Result<Vec<TypedResource<T>>, ResourceError>
+    where
+        T: ResourceKind,
+    {
+        let request = RuntimeListRequest {
+            controller_token: self.token.clone(),
+            namespace: namespace.to_owned(),
+            r#type: T::TYPE.to_owned(),
+            api: T::API.to_owned(),
+            label_selector: selector.to_owned(),
+        };
+
+        let mut stream = self.client.list(request).await?.into_inner();
+
+        let mut resources = vec![];
+
+        while let Some(response) = stream.message().await? {
+            if let Some(resource) = response.resource {
+                resources.push(TypedResource::decode(resource)?);
+            }
+        }
+
+        Ok(resources)
+    }
+
+    pub async fn create<T>(&mut self, resource: &TypedResource<T>) -> Result<(), ResourceError>
+    where
+        T: ResourceKind,
+    {
+        let request = RuntimeCreateRequest {
+            controller_token: self.token.clone(),
+            resource: Some(resource.encode()?),
+        };
+
+        self.client.create(request).await?;
+
+        Ok(())
+    }
+
+    /// Updates `resource`, provided it is still at the version in its metadata.
+    pub async fn update<T>(&mut self, resource: &TypedResource<T>) -> Result<(), ResourceError>
+    where
+        T: ResourceKind,
+    {
+        let request = RuntimeUpdateRequest {
+            controller_token: self.token.clone(),
+            current_version: resource.metadata.version.clone(),
+            new_resource: Some(resource.encode()?),
+        };
+
+        self.client.update(request).await?;
+
+        Ok(())
+    }
+
+    /// Reports the status of `resource`, provided its status is still at the version in its
+    /// metadata. The status is encoded as JSON in `yaml_status`.
+    pub async fn update_status<T, S>(
+        &mut self,
+        resource: &TypedResource<T>,
+        status: &S,
+    ) -> Result<(), ResourceError>
+    where
+        T: ResourceKind,
+        S: Serialize,
+    {
+        let request = RuntimeUpdateStatusRequest {
+            controller_token: self.token.clone(),
+            namespace: resource.metadata.namespace.clone(),
+            r#type: T::TYPE.to_owned(),
+            id: resource.metadata.id.clone(),
+            api: T::API.to_owned(),
+            current_status_version: resource.metadata.status_version.clone(),
+            status: Some(ResourceStatus {
+                proto_status: vec![],
+                yaml_status: serde_json::to_string(status)?,
+            }),
+        };
+
+        self.client.update_status(request).await?;
+
+        Ok(())
+    }
+}
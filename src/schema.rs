@@ -0,0 +1,695 @@
+//! Schemas of resource specs.
+//!
+//! A schema is registered for a resource type either as an encoded protobuf
+//! `FileDescriptorSet` and the name of the spec's message, or as a JSON Schema document. Both are
+//! compiled to a `Validator`, which checks specs in their JSON form.
+//!
+//! Only a subset of JSON Schema is supported: `type` (a name or a list of names), `properties`,
+//! `required`, `additionalProperties` (a boolean), `items`, `enum`, `minimum`, `maximum`,
+//! `definitions`/`$defs` and local `$ref`s. Annotations (e.g. `description`) are ignored, any
+//! other keyword is rejected so that a schema never validates less than its author expects.
+//!
+//! https://json-schema.org/draft/2020-12/json-schema-validation.html.
+
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FileDescriptorSet};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum SchemaError {
+    JsonError(serde_json::Error),
+    DecodeError(prost::DecodeError),
+    /// The schema is valid, but uses something that is not supported.
+    Unsupported(String),
+    Invalid(String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaError::JsonError(err) => write!(f, "invalid JSON schema: {}", err),
+            SchemaError::DecodeError(err) => write!(f, "invalid descriptor set: {}", err),
+            SchemaError::Unsupported(what) => write!(f, "unsupported schema: {}", what),
+            SchemaError::Invalid(what) => write!(f, "invalid schema: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<serde_json::Error> for SchemaError {
+    fn from(error: serde_json::Error) -> SchemaError {
+        SchemaError::JsonError(error)
+    }
+}
+
+impl From<prost::DecodeError> for SchemaError {
+    fn from(error: prost::DecodeError) -> SchemaError {
+        SchemaError::DecodeError(error)
+    }
+}
+
+/// FieldError is a violation of a schema, at the path of the offending field (e.g.
+/// `spec.options[0]`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Schema {
+    Any,
+    Null,
+    Boolean,
+    Integer {
+        minimum: Option<f64>,
+        maximum: Option<f64>,
+    },
+    Number {
+        minimum: Option<f64>,
+        maximum: Option<f64>,
+    },
+    String,
+    Array(Box<Schema>),
+    Object {
+        properties: BTreeMap<String, Schema>,
+        required: BTreeSet<String>,
+        additional: bool,
+    },
+    /// An object with arbitrary keys (i.e. a protobuf map).
+    Map(Box<Schema>),
+    /// A value that must also be one of the listed values.
+    Enum(Box<Schema>, Vec<Value>),
+    /// A value that must match at least one of the schemas.
+    AnyOf(Vec<Schema>),
+    /// A value that may also be `null`.
+    Optional(Box<Schema>),
+    /// A reference to a definition.
+    Ref(String),
+}
+
+/// Validator validates specs against a compiled schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validator {
+    root: Schema,
+    definitions: BTreeMap<String, Schema>,
+}
+
+impl Validator {
+    /// Compiles a JSON Schema document.
+    pub fn from_json_schema(document: &str) -> Result<Validator, SchemaError> {
+        let document: Value = serde_json::from_str(document)?;
+
+        let mut definitions = BTreeMap::new();
+
+        for keyword in &["definitions", "$defs"] {
+            if let Some(defs) = document.get(keyword) {
+                let defs = object(defs, keyword)?;
+
+                for (name, schema) in defs {
+                    let reference = format!("#/{}/{}", keyword, name);
+
+                    definitions.insert(reference, compile(schema)?);
+                }
+            }
+        }
+
+        let validator = Validator {
+            root: compile(&document)?,
+            definitions,
+        };
+
+        validator.check_references()?;
+
+        Ok(validator)
+    }
+
+    /// Compiles the schema of `message` (e.g. `engine.Mount`) from an encoded
+    /// `FileDescriptorSet`. Fields are named as in the `.proto` file.
+    pub fn from_descriptor_set(bytes: &[u8], message: &str) -> Result<Validator, SchemaError> {
+        let set = FileDescriptorSet::decode(bytes)?;
+
+        let mut messages = BTreeMap::new();
+
+        for file in &set.file {
+            let prefix = file.package.clone().unwrap_or_default();
+
+            for descriptor in &file.message_type {
+                collect(&prefix, descriptor, &mut messages);
+            }
+        }
+
+        let message = message.trim_start_matches('.');
+
+        if !messages.contains_key(message) {
+            return Err(SchemaError::Invalid(format!(
+                "message not found in descriptor set: {}",
+                message
+            )));
+        }
+
+        let mut definitions = BTreeMap::new();
+
+        for (name, descriptor) in &messages {
+            // Map entries are inlined into the fields that use them.
+            if is_map_entry(descriptor) {
+                continue;
+            }
+
+            definitions.insert(name.clone(), message_schema(descriptor, &messages)?);
+        }
+
+        let validator = Validator {
+            root: Schema::Ref(message.to_owned()),
+            definitions,
+        };
+
+        validator.check_references()?;
+
+        Ok(validator)
+    }
+
+    /// Validates `value`, and returns every violation found. Paths start at `root`.
+    pub fn validate(&self, root: &str, value: &Value) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+
+        self.check(&self.root, root, value, &mut errors, 0);
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    fn check(
+        &self,
+        schema: &Schema,
+        path: &str,
+        value: &Value,
+        errors: &mut Vec<FieldError>,
+        depth: usize,
+    ) {
+        // N.B.: Recursive definitions can only be followed as deep as the value is nested, but
+        // guard against a definition that refers to itself.
+        if depth > 64 {
+            errors.push(error(path, "schema is nested too deeply".to_owned()));
+            return;
+        }
+
+        match schema {
+            Schema::Any => (),
+            Schema::Null => {
+                if !value.is_null() {
+                    errors.push(mismatch(path, "null", value));
+                }
+            }
+            Schema::Boolean => {
+                if !value.is_boolean() {
+                    errors.push(mismatch(path, "boolean", value));
+                }
+            }
+            Schema::Integer { minimum, maximum } => {
+                if !(value.is_i64() || value.is_u64()) {
+                    errors.push(mismatch(path, "integer", value));
+                } else {
+                    range(path, value, *minimum, *maximum, errors);
+                }
+            }
+            Schema::Number { minimum, maximum } => {
+                if !value.is_number() {
+                    errors.push(mismatch(path, "number", value));
+                } else {
+                    range(path, value, *minimum, *maximum, errors);
+                }
+            }
+            Schema::String => {
+                if !value.is_string() {
+                    errors.push(mismatch(path, "string", value));
+                }
+            }
+            Schema::Array(items) => match value.as_array() {
+                Some(values) => {
+                    for (index, value) in values.iter().enumerate() {
+                        let path = format!("{}[{}]", path, index);
+
+                        self.check(items, &path, value, errors, depth + 1);
+                    }
+                }
+                None => errors.push(mismatch(path, "array", value)),
+            },
+            Schema::Object {
+                properties,
+                required,
+                additional,
+            } => match value.as_object() {
+                Some(fields) => {
+                    for name in required {
+                        if !fields.contains_key(name) {
+                            errors.push(error(
+                                &field(path, name),
+                                "required field is missing".to_owned(),
+                            ));
+                        }
+                    }
+
+                    for (name, value) in fields {
+                        match properties.get(name) {
+                            Some(schema) => {
+                                self.check(schema, &field(path, name), value, errors, depth + 1)
+                            }
+                            None if *additional => (),
+                            None => {
+                                errors.push(error(&field(path, name), "unknown field".to_owned()))
+                            }
+                        }
+                    }
+                }
+                None => errors.push(mismatch(path, "object", value)),
+            },
+            Schema::Map(values) => match value.as_object() {
+                Some(fields) => {
+                    for (name, value) in fields {
+                        self.check(values, &field(path, name), value, errors, depth + 1);
+                    }
+                }
+                None => errors.push(mismatch(path, "object", value)),
+            },
+            Schema::Enum(schema, values) => {
+                let before = errors.len();
+
+                self.check(schema, path, value, errors, depth + 1);
+
+                if errors.len() == before && !values.contains(value) {
+                    errors.push(error(
+                        path,
+                        format!("{} is not one of the allowed values", value),
+                    ));
+                }
+            }
+            Schema::AnyOf(schemas) => {
+                let matched = schemas.iter().any(|schema| {
+                    let mut errors = vec![];
+
+                    self.check(schema, path, value, &mut errors, depth + 1);
+
+                    errors.is_empty()
+                });
+
+                if !matched {
+                    errors.push(error(
+                        path,
+                        format!("{} does not match any of the allowed types", kind(value)),
+                    ));
+                }
+            }
+            Schema::Optional(schema) => {
+                if !value.is_null() {
+                    self.check(schema, path, value, errors, depth);
+                }
+            }
+            Schema::Ref(reference) => match self.definitions.get(reference) {
+                Some(schema) => self.check(schema, path, value, errors, depth + 1),
+                None => errors.push(error(path, format!("unknown reference: {}", reference))),
+            },
+        }
+    }
+
+    fn check_references(&self) -> Result<(), SchemaError> {
+        let mut references = vec![];
+
+        references_of(&self.root, &mut references);
+
+        for schema in self.definitions.values() {
+            references_of(schema, &mut references);
+        }
+
+        for reference in references {
+            if !self.definitions.contains_key(reference) {
+                return Err(SchemaError::Invalid(format!(
+                    "unknown reference: {}",
+                    reference
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn references_of<'a>(schema: &'a Schema, references: &mut Vec<&'a String>) {
+    match schema {
+        Schema::Ref(reference) => references.push(reference),
+        Schema::Array(schema)
+        | Schema::Map(schema)
+        | Schema::Enum(schema, _)
+        | Schema::Optional(schema) => references_of(schema, references),
+        Schema::Object { properties, .. } => {
+            for schema in properties.values() {
+                references_of(schema, references);
+            }
+        }
+        Schema::AnyOf(schemas) => {
+            for schema in schemas {
+                references_of(schema, references);
+            }
+        }
+        _ => (),
+    }
+}
+
+// Keywords that do not affect validation.
+static ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+    "definitions",
+    "$defs",
+];
+
+fn compile(document: &Value) -> Result<Schema, SchemaError> {
+    let document = match document {
+        Value::Bool(true) => return Ok(Schema::Any),
+        Value::Object(document) => document,
+        _ => {
+            return Err(SchemaError::Unsupported(format!(
+                "schema must be an object, found {}",
+                document
+            )))
+        }
+    };
+
+    let supported = [
+        "type",
+        "properties",
+        "required",
+        "additionalProperties",
+        "items",
+        "enum",
+        "minimum",
+        "maximum",
+        "$ref",
+    ];
+
+    for keyword in document.keys() {
+        if !supported.contains(&keyword.as_str()) && !ANNOTATIONS.contains(&keyword.as_str()) {
+            return Err(SchemaError::Unsupported(format!("keyword: {}", keyword)));
+        }
+    }
+
+    if let Some(reference) = document.get("$ref") {
+        return match reference.as_str() {
+            Some(reference) if reference.starts_with("#/") => Ok(Schema::Ref(reference.to_owned())),
+            _ => Err(SchemaError::Unsupported(format!("$ref: {}", reference))),
+        };
+    }
+
+    let schema = match document.get("type") {
+        Some(Value::String(name)) => typed(name, document)?,
+        Some(Value::Array(names)) => {
+            let mut schemas = vec![];
+
+            for name in names {
+                match name.as_str() {
+                    Some(name) => schemas.push(typed(name, document)?),
+                    None => return Err(SchemaError::Invalid(format!("type: {}", name))),
+                }
+            }
+
+            Schema::AnyOf(schemas)
+        }
+        Some(name) => return Err(SchemaError::Invalid(format!("type: {}", name))),
+        None if document.contains_key("properties") => typed("object", document)?,
+        None => Schema::Any,
+    };
+
+    match document.get("enum") {
+        Some(Value::Array(values)) => Ok(Schema::Enum(Box::new(schema), values.clone())),
+        Some(values) => Err(SchemaError::Invalid(format!("enum: {}", values))),
+        None => Ok(schema),
+    }
+}
+
+fn typed(name: &str, document: &Map<String, Value>) -> Result<Schema, SchemaError> {
+    let bound = |keyword: &str| match document.get(keyword) {
+        Some(value) => match value.as_f64() {
+            Some(bound) => Ok(Some(bound)),
+            None => Err(SchemaError::Invalid(format!("{}: {}", keyword, value))),
+        },
+        None => Ok(None),
+    };
+
+    let schema = match name {
+        "null" => Schema::Null,
+        "boolean" => Schema::Boolean,
+        "integer" => Schema::Integer {
+            minimum: bound("minimum")?,
+            maximum: bound("maximum")?,
+        },
+        "number" => Schema::Number {
+            minimum: bound("minimum")?,
+            maximum: bound("maximum")?,
+        },
+        "string" => Schema::String,
+        "array" => match document.get("items") {
+            Some(items) => Schema::Array(Box::new(compile(items)?)),
+            None => Schema::Array(Box::new(Schema::Any)),
+        },
+        "object" => {
+            let mut properties = BTreeMap::new();
+
+            if let Some(document) = document.get("properties") {
+                for (name, schema) in object(document, "properties")? {
+                    properties.insert(name.clone(), compile(schema)?);
+                }
+            }
+
+            let mut required = BTreeSet::new();
+
+            if let Some(names) = document.get("required") {
+                match names.as_array() {
+                    Some(names) => {
+                        for name in names {
+                            match name.as_str() {
+                                Some(name) => required.insert(name.to_owned()),
+                                None => {
+                                    return Err(SchemaError::Invalid(format!("required: {}", name)))
+                                }
+                            };
+                        }
+                    }
+                    None => return Err(SchemaError::Invalid(format!("required: {}", names))),
+                }
+            }
+
+            let additional = match document.get("additionalProperties") {
+                Some(Value::Bool(additional)) => *additional,
+                Some(value) => {
+                    return Err(SchemaError::Unsupported(format!(
+                        "additionalProperties: {}",
+                        value
+                    )))
+                }
+                None => true,
+            };
+
+            Schema::Object {
+                properties,
+                required,
+                additional,
+            }
+        }
+        _ => return Err(SchemaError::Invalid(format!("type: {}", name))),
+    };
+
+    Ok(schema)
+}
+
+fn object<'a>(value: &'a Value, keyword: &str) -> Result<&'a Map<String, Value>, SchemaError> {
+    value
+        .as_object()
+        .ok_or_else(|| SchemaError::Invalid(format!("{}: {}", keyword, value)))
+}
+
+fn collect<'a>(
+    prefix: &str,
+    descriptor: &'a DescriptorProto,
+    messages: &mut BTreeMap<String, &'a DescriptorProto>,
+) {
+    let name = match prefix {
+        "" => descriptor.name().to_owned(),
+        _ => format!("{}.{}", prefix, descriptor.name()),
+    };
+
+    for nested in &descriptor.nested_type {
+        collect(&name, nested, messages);
+    }
+
+    messages.insert(name, descriptor);
+}
+
+fn is_map_entry(descriptor: &DescriptorProto) -> bool {
+    descriptor
+        .options
+        .as_ref()
+        .map(|options| options.map_entry())
+        .unwrap_or(false)
+}
+
+fn message_schema(
+    descriptor: &DescriptorProto,
+    messages: &BTreeMap<String, &DescriptorProto>,
+) -> Result<Schema, SchemaError> {
+    let mut properties = BTreeMap::new();
+    let mut required = BTreeSet::new();
+
+    for field in &descriptor.field {
+        // N.B.: A `oneof` is a single enum field in the generated code, named after the `oneof`.
+        if field.oneof_index.is_some() && !field.proto3_optional() {
+            let index = field.oneof_index() as usize;
+
+            if let Some(oneof) = descriptor.oneof_decl.get(index) {
+                properties.insert(
+                    oneof.name().to_owned(),
+                    Schema::Optional(Box::new(Schema::Any)),
+                );
+            }
+
+            continue;
+        }
+
+        let schema = match field.label() {
+            Label::Repeated => match map_value(field.type_name(), messages) {
+                Some(entry) => Schema::Map(Box::new(field_schema(entry)?)),
+                None => Schema::Array(Box::new(field_schema(field)?)),
+            },
+            Label::Required => {
+                required.insert(field.name().to_owned());
+
+                field_schema(field)?
+            }
+            Label::Optional => match field.r#type() {
+                Type::Message => Schema::Optional(Box::new(field_schema(field)?)),
+                _ if field.proto3_optional() => Schema::Optional(Box::new(field_schema(field)?)),
+                _ => field_schema(field)?,
+            },
+        };
+
+        properties.insert(field.name().to_owned(), schema);
+    }
+
+    Ok(Schema::Object {
+        properties,
+        required,
+        additional: false,
+    })
+}
+
+// Returns the value field of the map entry `type_name`, if it is one.
+fn map_value<'a>(
+    type_name: &str,
+    messages: &BTreeMap<String, &'a DescriptorProto>,
+) -> Option<&'a prost_types::FieldDescriptorProto> {
+    let entry = messages.get(type_name.trim_start_matches('.'))?;
+
+    match is_map_entry(entry) {
+        true => entry.field.iter().find(|field| field.name() == "value"),
+        false => None,
+    }
+}
+
+fn field_schema(field: &prost_types::FieldDescriptorProto) -> Result<Schema, SchemaError> {
+    let integer = |minimum: f64, maximum: f64| Schema::Integer {
+        minimum: Some(minimum),
+        maximum: Some(maximum),
+    };
+
+    let schema = match field.r#type() {
+        Type::Double | Type::Float => Schema::Number {
+            minimum: None,
+            maximum: None,
+        },
+        Type::Int32 | Type::Sint32 | Type::Sfixed32 | Type::Enum => {
+            integer(i32::MIN as f64, i32::MAX as f64)
+        }
+        Type::Uint32 | Type::Fixed32 => integer(0.0, u32::MAX as f64),
+        Type::Int64 | Type::Sint64 | Type::Sfixed64 => integer(i64::MIN as f64, i64::MAX as f64),
+        Type::Uint64 | Type::Fixed64 => integer(0.0, u64::MAX as f64),
+        Type::Bool => Schema::Boolean,
+        Type::String => Schema::String,
+        // N.B.: `bytes` fields are `Vec<u8>`, which serializes as an array of numbers.
+        Type::Bytes => Schema::Array(Box::new(integer(0.0, 255.0))),
+        Type::Message => Schema::Ref(field.type_name().trim_start_matches('.').to_owned()),
+        Type::Group => {
+            return Err(SchemaError::Unsupported(format!(
+                "group field: {}",
+                field.name()
+            )))
+        }
+    };
+
+    Ok(schema)
+}
+
+fn range(
+    path: &str,
+    value: &Value,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    errors: &mut Vec<FieldError>,
+) {
+    let number = value.as_f64().unwrap_or_default();
+
+    let below = minimum.map(|minimum| number < minimum).unwrap_or(false);
+    let above = maximum.map(|maximum| number > maximum).unwrap_or(false);
+
+    if below || above {
+        errors.push(error(path, format!("{} is out of range", value)));
+    }
+}
+
+fn field(path: &str, name: &str) -> String {
+    format!("{}.{}", path, name)
+}
+
+fn error(path: &str, message: String) -> FieldError {
+    FieldError {
+        path: path.to_owned(),
+        message,
+    }
+}
+
+fn mismatch(path: &str, expected: &str, value: &Value) -> FieldError {
+    error(
+        path,
+        format!("expected {}, found {}", expected, kind(value)),
+    )
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
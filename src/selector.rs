@@ -0,0 +1,352 @@
+//! Labels and label selectors.
+//!
+//! A selector is a comma separated list of requirements, all of which a set of labels must meet:
+//!
+//! - `key=value`, `key==value`: the label is set to `value`.
+//! - `key!=value`: the label is not set to `value` (or is not set at all).
+//! - `key in (a, b)`: the label is set to one of the values.
+//! - `key notin (a, b)`: the label is not set to any of the values (or is not set at all).
+//! - `key`: the label is set.
+//! - `!key`: the label is not set.
+//!
+//! The empty selector matches everything. Label keys and values follow the syntax of Kubernetes
+//! labels.
+//!
+//! https://kubernetes.io/docs/concepts/overview/working-with-objects/labels/#label-selectors.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectorError {
+    InvalidKey(String),
+    InvalidValue(String),
+    /// The selector is not well formed, at the given byte offset.
+    Syntax(usize, String),
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectorError::InvalidKey(key) => write!(f, "invalid label key: {:?}", key),
+            SelectorError::InvalidValue(value) => write!(f, "invalid label value: {:?}", value),
+            SelectorError::Syntax(offset, what) => {
+                write!(f, "invalid selector at offset {}: {}", offset, what)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+/// Validates the keys and values of `labels`.
+pub fn validate(labels: &HashMap<String, String>) -> Result<(), SelectorError> {
+    for (key, value) in labels {
+        validate_key(key)?;
+        validate_value(value)?;
+    }
+
+    Ok(())
+}
+
+/// Validates a label (or annotation) key: a name, optionally prefixed by a DNS subdomain and a
+/// slash (e.g. `cosi.dev/tier`).
+pub fn validate_key(key: &str) -> Result<(), SelectorError> {
+    let (prefix, name) = match key.rfind('/') {
+        Some(index) => (Some(&key[..index]), &key[index + 1..]),
+        None => (None, key),
+    };
+
+    let valid_prefix = prefix
+        .map(|prefix| {
+            !prefix.is_empty()
+                && prefix.len() <= 253
+                && prefix.split('.').all(|part| {
+                    !part.is_empty()
+                        && part
+                            .bytes()
+                            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+                        && !part.starts_with('-')
+                        && !part.ends_with('-')
+                })
+        })
+        .unwrap_or(true);
+
+    match valid_prefix && !name.is_empty() && is_name(name) {
+        true => Ok(()),
+        false => Err(SelectorError::InvalidKey(key.to_owned())),
+    }
+}
+
+// A value is empty, or a name.
+fn validate_value(value: &str) -> Result<(), SelectorError> {
+    match value.is_empty() || is_name(value) {
+        true => Ok(()),
+        false => Err(SelectorError::InvalidValue(value.to_owned())),
+    }
+}
+
+// A name is at most 63 alphanumeric characters, `-`, `_` or `.`, and begins and ends with an
+// alphanumeric character.
+fn is_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+
+    name.len() <= 63
+        && bytes
+            .first()
+            .map(u8::is_ascii_alphanumeric)
+            .unwrap_or(false)
+        && bytes.last().map(u8::is_ascii_alphanumeric).unwrap_or(false)
+        && bytes
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || *b == b'-' || *b == b'_' || *b == b'.')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    In(String, BTreeSet<String>),
+    NotIn(String, BTreeSet<String>),
+    Exists(String),
+    DoesNotExist(String),
+}
+
+impl Requirement {
+    fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        match self {
+            Requirement::Equals(key, value) => labels.get(key) == Some(value),
+            Requirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            Requirement::In(key, values) => labels
+                .get(key)
+                .map(|value| values.contains(value))
+                .unwrap_or(false),
+            Requirement::NotIn(key, values) => labels
+                .get(key)
+                .map(|value| !values.contains(value))
+                .unwrap_or(true),
+            Requirement::Exists(key) => labels.contains_key(key),
+            Requirement::DoesNotExist(key) => !labels.contains_key(key),
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |values: &BTreeSet<String>| values.iter().cloned().collect::<Vec<_>>().join(",");
+
+        match self {
+            Requirement::Equals(key, value) => write!(f, "{}={}", key, value),
+            Requirement::NotEquals(key, value) => write!(f, "{}!={}", key, value),
+            Requirement::In(key, values) => write!(f, "{} in ({})", key, join(values)),
+            Requirement::NotIn(key, values) => write!(f, "{} notin ({})", key, join(values)),
+            Requirement::Exists(key) => write!(f, "{}", key),
+            Requirement::DoesNotExist(key) => write!(f, "!{}", key),
+        }
+    }
+}
+
+/// Selector selects resources by their labels.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selector {
+    requirements: Vec<Requirement>,
+}
+
+impl Selector {
+    /// Parses a selector, e.g. `tier=boot,!deprecated,zone in (a, b)`.
+    pub fn parse(selector: &str) -> Result<Selector, SelectorError> {
+        let mut parser = Parser {
+            input: selector,
+            offset: 0,
+        };
+
+        let mut requirements = vec![];
+
+        parser.skip_whitespace();
+
+        if parser.is_done() {
+            return Ok(Selector { requirements });
+        }
+
+        loop {
+            requirements.push(parser.requirement()?);
+
+            parser.skip_whitespace();
+
+            if parser.is_done() {
+                break;
+            }
+
+            parser.expect(",")?;
+        }
+
+        Ok(Selector { requirements })
+    }
+
+    /// Returns true if the selector matches everything.
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    /// Returns true if `labels` meet all the requirements of the selector.
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.requirements
+            .iter()
+            .all(|requirement| requirement.matches(labels))
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let requirements: Vec<String> = self.requirements.iter().map(|r| r.to_string()).collect();
+
+        write!(f, "{}", requirements.join(","))
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.offset..]
+    }
+
+    fn is_done(&self) -> bool {
+        self.rest().is_empty()
+    }
+
+    fn error(&self, what: &str) -> SelectorError {
+        SelectorError::Syntax(self.offset, what.to_owned())
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    fn consume(&mut self, token: &str) -> bool {
+        match self.rest().starts_with(token) {
+            true => {
+                self.offset += token.len();
+                true
+            }
+            false => false,
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), SelectorError> {
+        match self.consume(token) {
+            true => Ok(()),
+            false => Err(self.error(&format!("expected {:?}", token))),
+        }
+    }
+
+    // Consumes a key or a value, which both end at whitespace or an operator.
+    fn word(&mut self) -> &'a str {
+        let rest = self.rest();
+
+        let end = rest
+            .find(|c: char| c.is_whitespace() || "=!,()".contains(c))
+            .unwrap_or(rest.len());
+
+        self.offset += end;
+
+        &rest[..end]
+    }
+
+    fn key(&mut self) -> Result<String, SelectorError> {
+        self.skip_whitespace();
+
+        let offset = self.offset;
+        let key = self.word();
+
+        if key.is_empty() {
+            return Err(SelectorError::Syntax(offset, "expected a key".to_owned()));
+        }
+
+        validate_key(key)?;
+
+        Ok(key.to_owned())
+    }
+
+    fn value(&mut self) -> Result<String, SelectorError> {
+        self.skip_whitespace();
+
+        let value = self.word();
+
+        validate_value(value)?;
+
+        Ok(value.to_owned())
+    }
+
+    fn values(&mut self) -> Result<BTreeSet<String>, SelectorError> {
+        self.skip_whitespace();
+        self.expect("(")?;
+
+        let mut values = BTreeSet::new();
+
+        loop {
+            values.insert(self.value()?);
+
+            self.skip_whitespace();
+
+            if self.consume(")") {
+                return Ok(values);
+            }
+
+            self.expect(",")?;
+        }
+    }
+
+    fn requirement(&mut self) -> Result<Requirement, SelectorError> {
+        self.skip_whitespace();
+
+        if self.consume("!") {
+            return Ok(Requirement::DoesNotExist(self.key()?));
+        }
+
+        let key = self.key()?;
+
+        self.skip_whitespace();
+
+        if self.consume("!=") {
+            return Ok(Requirement::NotEquals(key, self.value()?));
+        }
+
+        if self.consume("==") || self.consume("=") {
+            return Ok(Requirement::Equals(key, self.value()?));
+        }
+
+        // N.B.: `in` and `notin` are only operators when followed by whitespace or a parenthesis,
+        // so that a requirement on the existence of a key is never mistaken for one.
+        let rest = self.rest();
+
+        for (operator, negated) in &[("in", false), ("notin", true)] {
+            let follows = rest
+                .strip_prefix(operator)
+                .map(|after| after.trim_start().starts_with('('))
+                .unwrap_or(false);
+
+            if follows {
+                self.offset += operator.len();
+
+                let values = self.values()?;
+
+                return Ok(match negated {
+                    true => Requirement::NotIn(key, values),
+                    false => Requirement::In(key, values),
+                });
+            }
+        }
+
+        if self.is_done() || rest.starts_with(',') {
+            return Ok(Requirement::Exists(key));
+        }
+
+        Err(self.error("expected an operator"))
+    }
+}
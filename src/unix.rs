@@ -108,75 +108,277 @@ impl AsyncWrite for UnixStream {
     }
 }
 
+/// The environment variable that overrides `consts::ETC`, e.g. to use a temporary directory in
+/// tests.
+pub static ETC_ENV: &str = "COSI_ETC";
+
+/// Returns the directory system configuration files (e.g. `hosts` or `passwd`) are written to.
+pub fn etc() -> std::path::PathBuf {
+    match std::env::var(ETC_ENV) {
+        Ok(root) if !root.is_empty() => std::path::PathBuf::from(root),
+        _ => std::path::PathBuf::from(crate::consts::ETC),
+    }
+}
+
+/// Replaces the contents of `path` atomically, by writing them to a temporary file in the same
+/// directory and renaming it over `path`. Readers see either the old or the new contents.
+///
+/// https://man7.org/linux/man-pages/man2/rename.2.html.
+pub fn write_atomically<P>(path: P, contents: &[u8]) -> std::io::Result<()>
+where
+    P: AsRef<std::path::Path>,
+{
+    write_atomically_with(path, contents, |_| Ok(()))
+}
+
+/// Like `write_atomically`, but calls `prepare` on the temporary file before renaming it, e.g. to
+/// set its mode and owner so that the file never has others.
+pub fn write_atomically_with<P, F>(path: P, contents: &[u8], prepare: F) -> std::io::Result<()>
+where
+    P: AsRef<std::path::Path>,
+    F: FnOnce(&std::fs::File) -> std::io::Result<()>,
+{
+    use std::io::Write;
+
+    let path = path.as_ref();
+    let temporary = temporary(path)?;
+
+    let result = std::fs::File::create(&temporary).and_then(|mut file| {
+        file.write_all(contents)?;
+
+        // N.B.: The contents are synced before the rename, so that a crash does not leave an
+        // empty file behind.
+        file.sync_all()?;
+
+        prepare(&file)?;
+
+        std::fs::rename(&temporary, path)
+    });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+
+    result
+}
+
+/// Replaces `path` with a symbolic link to `target` atomically, like `write_atomically`.
+///
+/// https://man7.org/linux/man-pages/man2/symlink.2.html.
+pub fn symlink_atomically<P, Q>(target: P, path: Q) -> std::io::Result<()>
+where
+    P: AsRef<std::path::Path>,
+    Q: AsRef<std::path::Path>,
+{
+    let path = path.as_ref();
+    let temporary = temporary(path)?;
+
+    let result = std::os::unix::fs::symlink(target, &temporary)
+        .and_then(|_| std::fs::rename(&temporary, path));
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+
+    result
+}
+
+// Returns the temporary path that `path` is replaced from.
+fn temporary(path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+    let name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} is not a file", path.display()),
+        )
+    })?;
+
+    let mut temporary = std::ffi::OsString::from(".");
+    temporary.push(name);
+    temporary.push(format!(".{}", std::process::id()));
+
+    Ok(path.with_file_name(temporary))
+}
+
+pub mod accounts;
+
+pub mod file;
+
+pub mod hostname;
+
+pub mod kmod;
+
+pub mod mount;
+
+pub mod netlink;
+
+pub mod network;
+
+pub mod resolver;
+
+pub mod sntp;
+
+pub mod sysctl;
+
 pub mod process {
+    use crate::bootstrap::{Bootstrap, Tokens};
     use crate::consts;
-    use crate::unix::process::reaper::Reaper;
+    use crate::unix::process::{
+        activation::{Listeners, LISTEN_FDS_START},
+        cgroup::Cgroup,
+        reaper::Reaper,
+    };
     use manager::Manager;
+    use nix::sys::signal::Signal;
     use nix::unistd::Pid;
+    use service::Service;
+    use std::os::unix::process::CommandExt;
+    use std::sync::Arc;
     use std::{convert::TryInto, process::Command, process::Stdio};
 
+    pub mod activation;
+    pub mod bootstrap;
+    pub mod cgroup;
+    pub mod environment;
+    pub mod exec;
+    pub mod sandbox;
+    pub mod service;
+    pub mod tree;
+
     pub async fn monitor(
-        executable: String,
+        service: Service,
         socket: String,
         reaper: Reaper,
+        tokens: Tokens,
     ) -> Result<(), std::io::Error> {
+        let executable = service.executable.clone();
+
+        let cgroup = Cgroup::new(cgroup::root(), &service.name);
+
+        let cgroup = match cgroup.create(&service.resources) {
+            Ok(()) => {
+                reaper.tracker.track_cgroup(&service.name, cgroup.clone());
+
+                Some(cgroup)
+            }
+            Err(err) => {
+                println!("<{}>: failed to create cgroup: {}", executable, err);
+                None
+            }
+        };
+
+        let listeners = Arc::new(Listeners::bind(&service.sockets, &service.name)?);
+
         loop {
+            if service.on_demand && !listeners.is_empty() {
+                // N.B.: Waiting blocks, so it is kept off the threads that drive the other
+                // services.
+                let waiting = listeners.clone();
+
+                tokio::task::spawn_blocking(move || waiting.wait())
+                    .await
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))??;
+
+                println!("<{}>: starting on demand", executable);
+            }
+
             // N.B.: Create the manager _in_ the loop so that `drop` is called before the next iteration.
-            let manager = Manager::new(reaper.clone());
+            let manager = Manager::new();
 
-            let mut child = match Command::new(executable.clone())
-                .stdout(Stdio::piped())
-                .stdin(Stdio::piped())
-                .args(&["--address", consts::ADDRESS_RUNTIME])
-                .spawn()
-            {
-                Ok(child) => child,
-                Err(err) => return Err(err),
+            let placement = match &cgroup {
+                Some(cgroup) => Some(cgroup.prepare()?),
+                None => None,
             };
 
+            let bootstrap = Bootstrap {
+                engine_socket: socket.clone(),
+                runtime_socket: consts::SOCKET_RUNTIME.to_owned(),
+                runtime_address: Some(consts::ADDRESS_RUNTIME_LOCAL.to_owned()),
+                service: service.name.clone(),
+                token: bootstrap::token()?,
+            };
+
+            tokens.issue(&bootstrap.service, &bootstrap.token);
+
+            // The bootstrap is passed right after the sockets.
+            let fd = LISTEN_FDS_START + listeners.len() as i32;
+
+            let mut environment = service.exec.environment();
+            listeners.export(&mut environment);
+            bootstrap::export(&bootstrap, fd, &mut environment);
+
+            let mut environment = environment.prepare()?;
+            let activation = listeners.prepare()?;
+            let handoff = bootstrap::prepare(&bootstrap, fd)?;
+            let exec = service.exec.prepare()?;
+            let sandbox = service.sandbox.prepare()?;
+
+            let mut command = Command::new(executable.clone());
+
+            command
+                .stdout(Stdio::piped())
+                .stdin(Stdio::null())
+                .args(&service.args);
+
+            service.exec.configure(&mut command);
+
+            unsafe {
+                command.pre_exec(move || {
+                    // N.B.: Join the cgroup first, the sandbox may drop the privileges needed to do so.
+                    if let Some(placement) = &placement {
+                        placement.apply()?;
+                    }
+
+                    environment.apply()?;
+                    activation.apply()?;
+                    handoff.apply()?;
+                    exec.apply()?;
+                    sandbox.apply()
+                });
+            }
+
+            let (mut child, exits) = reaper.spawn(|| command.spawn())?;
+
             let pid: Pid = match child.id().try_into() {
                 Ok(id) => Pid::from_raw(id),
                 Err(err) => panic!(err),
             };
 
-            if let Some(stdin) = child.stdin.take() {
-                manager.write_stdin(stdin, socket.clone());
-            }
+            reaper.tracker.track(&service.name, pid);
 
             if let Some(stdout) = child.stdout.take() {
                 manager.read_stdout(stdout, executable.clone());
             }
 
-            manager.watch(pid);
+            if let Some(exit) = manager.watch(exits) {
+                println!("<{}>: {}", executable, exit);
+            }
+
+            // Stop whatever the service left behind before it is restarted.
+            match reaper.tracker.kill(&service.name, Signal::SIGKILL) {
+                Ok(killed) if !killed.is_empty() => {
+                    println!("<{}>: killed remaining processes {:?}", executable, killed)
+                }
+                Ok(_) => (),
+                Err(err) => println!(
+                    "<{}>: failed to kill remaining processes: {}",
+                    executable, err
+                ),
+            }
 
-            // N.B.: When `stdin` and `stdout` go out of scope, the underlying file handles are closed.
+            // N.B.: When `stdout` goes out of scope, the underlying file handles are closed.
         }
     }
 
     mod manager {
-        use crate::unix::process::reaper::Reaper;
-        use nix::unistd::Pid;
-        use std::io::{BufRead, BufReader, Write};
+        use crate::unix::process::reaper::ChildExit;
+        use crossbeam_channel::Receiver;
+        use std::io::{BufRead, BufReader};
 
-        pub struct Manager {
-            reaper: Reaper,
-        }
+        pub struct Manager {}
 
         impl Manager {
-            pub fn new(reaper: Reaper) -> Manager {
-                Manager { reaper }
-            }
-
-            pub fn write_stdin(&self, mut stdin: std::process::ChildStdin, socket: String) {
-                std::thread::spawn(move || loop {
-                    match stdin.write_all(socket.as_bytes()) {
-                        Ok(()) => break,
-                        Err(err) => {
-                            println!("{}", err);
-                            std::thread::sleep(std::time::Duration::from_millis(250))
-                        }
-                    };
-                });
+            pub fn new() -> Manager {
+                Manager {}
             }
 
             pub fn read_stdout(&self, stdout: std::process::ChildStdout, executable: String) {
@@ -197,14 +399,19 @@ pub mod process {
                 });
             }
 
-            pub fn watch(&self, child: Pid) {
+            /// Blocks until the child whose state changes are received on `exits` terminates,
+            /// and returns how it did so. Stop and continue events are logged along the way.
+            pub fn watch(&self, exits: Receiver<ChildExit>) -> Option<ChildExit> {
                 // N.B.: In testing, only `recv` worked consistently (e.g. `iter` "missed" some messages).
-                #[allow(clippy::for_loops_over_fallibles)]
-                for reaped in self.reaper.rx.recv() {
-                    if child == reaped {
-                        return;
+                while let Ok(exit) = exits.recv() {
+                    if exit.terminated() {
+                        return Some(exit);
                     }
+
+                    println!("{}", exit);
                 }
+
+                None
             }
         }
 
@@ -216,21 +423,23 @@ pub mod process {
     }
 
     pub mod reaper {
-        use crossbeam_channel::{self, unbounded, Receiver, SendError, Sender};
-        use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+        use crate::unix::process::tree::Tracker;
+        use crossbeam_channel::{self, unbounded, Receiver, Sender};
+        use nix::errno::Errno;
+        use nix::sys::signal::Signal;
+        use nix::sys::wait::{WaitPidFlag, WaitStatus};
         use nix::unistd::Pid;
+        use std::collections::HashMap;
         use std::convert::From;
+        use std::fmt;
+        use std::io;
+        use std::process::Child;
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
 
         #[derive(Debug)]
         pub enum ReapError {
             WaitError(nix::Error),
-            SendError(SendError<Pid>),
-        }
-
-        impl From<SendError<Pid>> for ReapError {
-            fn from(error: SendError<Pid>) -> ReapError {
-                ReapError::SendError(error)
-            }
         }
 
         impl From<nix::Error> for ReapError {
@@ -239,65 +448,266 @@ pub mod process {
             }
         }
 
-        #[derive(Clone, Debug)]
+        /// The state change observed for a child process.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ChildState {
+            /// The child terminated normally.
+            Exited,
+            /// The child was terminated by a signal.
+            Signaled,
+            /// The child was stopped by a signal.
+            Stopped,
+            /// The child was resumed by `SIGCONT`.
+            Continued,
+        }
+
+        /// The resource usage of a child process as reported by `wait4`.
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+        pub struct ResourceUsage {
+            pub user_time: Duration,
+            pub system_time: Duration,
+            /// The maximum resident set size in kilobytes.
+            pub max_rss: i64,
+            pub minor_faults: i64,
+            pub major_faults: i64,
+            pub voluntary_context_switches: i64,
+            pub involuntary_context_switches: i64,
+        }
+
+        fn duration(time: libc::timeval) -> Duration {
+            Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+        }
+
+        impl From<libc::rusage> for ResourceUsage {
+            fn from(rusage: libc::rusage) -> ResourceUsage {
+                ResourceUsage {
+                    user_time: duration(rusage.ru_utime),
+                    system_time: duration(rusage.ru_stime),
+                    max_rss: rusage.ru_maxrss,
+                    minor_faults: rusage.ru_minflt,
+                    major_faults: rusage.ru_majflt,
+                    voluntary_context_switches: rusage.ru_nvcsw,
+                    involuntary_context_switches: rusage.ru_nivcsw,
+                }
+            }
+        }
+
+        /// A state change of a child process, sent to the watcher of every child that is waited on.
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct ChildExit {
+            pub pid: Pid,
+            pub state: ChildState,
+            /// The exit code, if the child exited normally.
+            pub code: Option<i32>,
+            /// The signal that terminated or stopped the child.
+            pub signal: Option<Signal>,
+            pub core_dumped: bool,
+            pub rusage: ResourceUsage,
+        }
+
+        impl ChildExit {
+            /// Returns `true` if the child no longer exists (i.e. it was reaped).
+            pub fn terminated(&self) -> bool {
+                matches!(self.state, ChildState::Exited | ChildState::Signaled)
+            }
+
+            /// Returns `true` if the child exited normally with a zero exit code.
+            pub fn success(&self) -> bool {
+                self.state == ChildState::Exited && self.code == Some(0)
+            }
+        }
+
+        impl fmt::Display for ChildExit {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.state {
+                    ChildState::Exited => write!(
+                        f,
+                        "process {} exited with code {}",
+                        self.pid,
+                        self.code.unwrap_or_default()
+                    )?,
+                    ChildState::Signaled => write!(
+                        f,
+                        "process {} was killed by {}{}",
+                        self.pid,
+                        signal_name(self.signal),
+                        if self.core_dumped {
+                            " (core dumped)"
+                        } else {
+                            ""
+                        }
+                    )?,
+                    ChildState::Stopped => write!(
+                        f,
+                        "process {} was stopped by {}",
+                        self.pid,
+                        signal_name(self.signal)
+                    )?,
+                    ChildState::Continued => write!(f, "process {} was continued", self.pid)?,
+                };
+
+                if self.terminated() {
+                    write!(
+                        f,
+                        " (user: {:?}, system: {:?}, max rss: {} kB)",
+                        self.rusage.user_time, self.rusage.system_time, self.rusage.max_rss
+                    )?;
+                }
+
+                Ok(())
+            }
+        }
+
+        fn signal_name(signal: Option<Signal>) -> &'static str {
+            signal
+                .map(|signal| signal.as_str())
+                .unwrap_or("an unknown signal")
+        }
+
+        #[derive(Clone, Debug, Default)]
         pub struct Reaper {
-            pub rx: Receiver<Pid>,
+            // The senders of the state changes of children, by pid. A child that is not watched
+            // (e.g. an orphan) is reaped all the same.
+            watchers: Arc<Mutex<HashMap<Pid, Sender<ChildExit>>>>,
+            pub tracker: Tracker,
+        }
+
+        pub fn new() -> Reaper {
+            Reaper::default()
         }
 
-        pub fn new() -> (Sender<Pid>, Reaper) {
-            let (tx, rx) = unbounded();
+        // https://man7.org/linux/man-pages/man2/wait4.2.html
+        fn wait4(pid: Pid, options: WaitPidFlag) -> nix::Result<(WaitStatus, ResourceUsage)> {
+            let mut status: i32 = 0;
+            let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+
+            let res =
+                unsafe { libc::wait4(pid.as_raw(), &mut status, options.bits(), &mut rusage) };
 
-            (tx, Reaper { rx })
+            match Errno::result(res)? {
+                0 => Ok((WaitStatus::StillAlive, ResourceUsage::default())),
+                res => Ok((
+                    WaitStatus::from_raw(Pid::from_raw(res), status)?,
+                    ResourceUsage::from(rusage),
+                )),
+            }
         }
 
         impl Reaper {
+            /// Spawns a child with `spawn`, and returns it along with a receiver of its state
+            /// changes.
+            pub fn spawn<F>(&self, spawn: F) -> io::Result<(Child, Receiver<ChildExit>)>
+            where
+                F: FnOnce() -> io::Result<Child>,
+            {
+                // N.B.: The watchers are locked while the child is spawned, so that it cannot be
+                // reaped before it is watched.
+                let mut watchers = self.watchers.lock().unwrap();
+
+                let child = spawn()?;
+
+                let (tx, rx) = unbounded();
+
+                watchers.insert(Pid::from_raw(child.id() as i32), tx);
+
+                Ok((child, rx))
+            }
+
+            // Sends `exit` to the watcher of its child, which is forgotten once the child
+            // terminated.
+            fn notify(&self, exit: &ChildExit) {
+                let mut watchers = self.watchers.lock().unwrap();
+
+                let watched = match watchers.get(&exit.pid) {
+                    // N.B.: A watcher that went away no longer needs to be told.
+                    Some(tx) => tx.send(exit.clone()).is_ok(),
+                    None => return,
+                };
+
+                if !watched || exit.terminated() {
+                    watchers.remove(&exit.pid);
+                }
+            }
+
             // https://man7.org/linux/man-pages/man2/waitpid.2.html
-            pub fn reap(&self, pid: Pid, tx: &Sender<Pid>) -> Result<Vec<Pid>, ReapError> {
-                let mut pids = vec![];
+            pub fn reap(&self, pid: Pid) -> Result<Vec<ChildExit>, ReapError> {
+                let mut exits = vec![];
+
+                let options =
+                    WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED;
 
                 loop {
-                    let result = match waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
+                    let result = match wait4(pid, options) {
                         Ok(s) => Ok(s),
                         Err(err) => {
                             match err {
                                 nix::Error::Sys(nix::errno::Errno::EINTR) => {
-                                    // SIGCHLD was caught, call `waitpid` again.
+                                    // SIGCHLD was caught, call `wait4` again.
                                     continue;
                                 }
 
                                 nix::Error::Sys(nix::errno::Errno::ECHILD) => {
                                     // No un-awaited child processes.
-                                    return Ok(pids);
+                                    return Ok(exits);
                                 }
                                 _ => return Err(ReapError::WaitError(err)),
                             }
                         }
                     };
 
-                    match result {
-                        Ok(s) => match s {
-                            WaitStatus::Exited(pid, _) | WaitStatus::Signaled(pid, _, _) => {
-                                // Process was reaped, notify all listeners.
-                                match tx.send(pid) {
-                                    Ok(_) => pids.push(pid),
-                                    Err(err) => return Err(ReapError::SendError(err)),
-                                }
-                            }
+                    let exit = match result {
+                        Ok((s, rusage)) => match s {
+                            WaitStatus::Exited(pid, code) => ChildExit {
+                                pid,
+                                state: ChildState::Exited,
+                                code: Some(code),
+                                signal: None,
+                                core_dumped: false,
+                                rusage,
+                            },
+                            WaitStatus::Signaled(pid, signal, core_dumped) => ChildExit {
+                                pid,
+                                state: ChildState::Signaled,
+                                code: None,
+                                signal: Some(signal),
+                                core_dumped,
+                                rusage,
+                            },
+                            WaitStatus::Stopped(pid, signal) => ChildExit {
+                                pid,
+                                state: ChildState::Stopped,
+                                code: None,
+                                signal: Some(signal),
+                                core_dumped: false,
+                                rusage,
+                            },
+                            WaitStatus::Continued(pid) => ChildExit {
+                                pid,
+                                state: ChildState::Continued,
+                                code: None,
+                                signal: None,
+                                core_dumped: false,
+                                rusage,
+                            },
                             // If WNOHANG was specified and one or more child(ren)
                             // specified by pid exist, but have not yet changed
                             // state, then 0 (StillAlive) is returned.
                             WaitStatus::StillAlive => {
-                                return Ok(pids);
+                                return Ok(exits);
                             }
                             _ => {
-                                return Ok(pids);
+                                return Ok(exits);
                             }
                         },
                         Err(err) => return Err(err),
                     };
 
+                    self.notify(&exit);
+
+                    exits.push(exit);
+
                     if pid.as_raw() != -1 {
-                        return Ok(pids);
+                        return Ok(exits);
                     }
                 }
             }
@@ -0,0 +1,599 @@
+//! Managing user accounts and groups in `passwd`, `group` and `shadow` on `User` and `Group`
+//! resources.
+//!
+//! https://man7.org/linux/man-pages/man5/passwd.5.html.
+//! https://man7.org/linux/man-pages/man5/group.5.html.
+//! https://man7.org/linux/man-pages/man5/shadow.5.html.
+//! https://man7.org/linux/man-pages/man3/lckpwdf.3.html.
+
+use crate::spec::engine::{Group, User};
+use crate::unix::file;
+use nix::fcntl::{fcntl, FcntlArg};
+use nix::unistd::{chown, getgid, getuid, Gid, Uid};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The IDs of system accounts, which are allocated from the top down as by useradd(8).
+const SYSTEM_IDS: std::ops::RangeInclusive<u32> = 100..=999;
+
+const DEFAULT_HOME: &str = "/";
+const DEFAULT_SHELL: &str = "/usr/sbin/nologin";
+
+#[derive(Debug)]
+pub enum AccountError {
+    /// A user or group name is not made of lowercase letters, digits, underscores and hyphens.
+    InvalidName(String),
+    /// A field of an entry (e.g. a comment or shell) contains a colon or a line break, which would
+    /// split the entry.
+    InvalidField(String),
+    /// A group does not exist.
+    UnknownGroup(String),
+    /// An ID is taken by another user or group.
+    IdInUse(u32),
+    /// Every ID of the system range is taken.
+    NoFreeId,
+    /// A group is the primary group of a user, and cannot be removed.
+    GroupInUse {
+        group: String,
+        user: String,
+    },
+    IoError(io::Error),
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccountError::InvalidName(name) => write!(f, "invalid name {:?}", name),
+            AccountError::InvalidField(field) => write!(f, "invalid field {:?}", field),
+            AccountError::UnknownGroup(name) => write!(f, "unknown group {}", name),
+            AccountError::IdInUse(id) => write!(f, "ID {} is in use", id),
+            AccountError::NoFreeId => write!(f, "no free ID left"),
+            AccountError::GroupInUse { group, user } => {
+                write!(f, "group {} is the primary group of {}", group, user)
+            }
+            AccountError::IoError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+impl From<io::Error> for AccountError {
+    fn from(err: io::Error) -> Self {
+        AccountError::IoError(err)
+    }
+}
+
+fn io_error(err: nix::Error) -> io::Error {
+    match err.as_errno() {
+        Some(errno) => io::Error::from(errno),
+        None => io::Error::new(io::ErrorKind::Other, err),
+    }
+}
+
+// Whether `name` is a valid user or group name, as accepted by useradd(8).
+fn valid(name: &str) -> bool {
+    let name = name.strip_suffix('$').unwrap_or(name);
+
+    !name.is_empty()
+        && name.len() <= 32
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+// Whether `field` can be a field of an entry, which are separated by colons and end with a line
+// break.
+fn valid_field(field: &str) -> bool {
+    !field.contains(&[':', '\n', '\r'][..])
+}
+
+// Database is one of `passwd`, `group` and `shadow`, as lines of colon-separated fields.
+struct Database {
+    path: PathBuf,
+    lines: Vec<String>,
+    changed: bool,
+}
+
+impl Database {
+    fn read(path: PathBuf) -> io::Result<Database> {
+        let lines = match fs::read_to_string(&path) {
+            Ok(contents) => contents.lines().map(String::from).collect(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err),
+        };
+
+        Ok(Database {
+            path,
+            lines,
+            changed: false,
+        })
+    }
+
+    fn entries(&self) -> impl Iterator<Item = Vec<&str>> {
+        self.lines
+            .iter()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|line| line.split(':').collect())
+    }
+
+    fn find(&self, name: &str) -> Option<Vec<&str>> {
+        self.entries().find(|fields| fields[0] == name)
+    }
+
+    // Whether an entry has `id` as its third field, which is the user ID in `passwd` and the
+    // group ID in `group`.
+    fn has_id(&self, id: u32) -> bool {
+        let id = id.to_string();
+
+        self.entries()
+            .any(|fields| fields.get(2) == Some(&id.as_str()))
+    }
+
+    fn push(&mut self, fields: &[&str]) -> Result<(), AccountError> {
+        if let Some(field) = fields.iter().find(|field| !valid_field(field)) {
+            return Err(AccountError::InvalidField(field.to_string()));
+        }
+
+        self.lines.push(fields.join(":"));
+        self.changed = true;
+
+        Ok(())
+    }
+
+    fn remove(&mut self, name: &str) {
+        let before = self.lines.len();
+
+        self.lines
+            .retain(|line| line.split(':').next() != Some(name) || line.starts_with('#'));
+
+        self.changed |= self.lines.len() != before;
+    }
+
+    // Writes the database if it changed, keeping the mode and owner of the file. A new file gets
+    // `mode`, and is owned by the calling process.
+    fn write(&self, mode: u32) -> io::Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        let (mode, uid, gid) = match fs::metadata(&self.path) {
+            Ok(metadata) => (metadata.mode() & 0o7777, metadata.uid(), metadata.gid()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                (mode, getuid().as_raw(), getgid().as_raw())
+            }
+            Err(err) => return Err(err),
+        };
+
+        let mut contents = self.lines.join("\n");
+        contents.push('\n');
+
+        file::write(&self.path, contents.as_bytes(), mode, uid, gid)
+    }
+}
+
+/// Lock is an exclusive lock on the databases under a root, the same as the one taken by
+/// lckpwdf(3). It is released when dropped.
+pub struct Lock {
+    _file: fs::File,
+}
+
+/// Locks the databases under `root`, waiting for the lock if another process holds it.
+pub fn lock(root: &Path) -> io::Result<Lock> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(root.join(".pwd.lock"))?;
+
+    let mut flock: libc::flock = unsafe { std::mem::zeroed() };
+    flock.l_type = libc::F_WRLCK as libc::c_short;
+    flock.l_whence = libc::SEEK_SET as libc::c_short;
+
+    fcntl(file.as_raw_fd(), FcntlArg::F_SETLKW(&flock)).map_err(io_error)?;
+
+    Ok(Lock { _file: file })
+}
+
+/// Accounts is the user and group databases under a root.
+pub struct Accounts {
+    passwd: Database,
+    group: Database,
+    shadow: Database,
+}
+
+impl Accounts {
+    /// Reads the databases under `root`. A database that does not exist is empty.
+    pub fn read(root: &Path) -> io::Result<Accounts> {
+        Ok(Accounts {
+            passwd: Database::read(root.join("passwd"))?,
+            group: Database::read(root.join("group"))?,
+            shadow: Database::read(root.join("shadow"))?,
+        })
+    }
+
+    /// Returns the user ID and primary group ID of a user.
+    pub fn user(&self, name: &str) -> Option<(u32, u32)> {
+        let fields = self.passwd.find(name)?;
+
+        let uid = fields.get(2)?.parse().ok()?;
+        let gid = fields.get(3)?.parse().ok()?;
+
+        Some((uid, gid))
+    }
+
+    /// Returns the group ID of a group.
+    pub fn group(&self, name: &str) -> Option<u32> {
+        self.group.find(name)?.get(2)?.parse().ok()
+    }
+
+    /// Returns the supplementary members of a group.
+    pub fn members(&self, name: &str) -> Vec<String> {
+        self.group
+            .find(name)
+            .and_then(|fields| fields.get(3).copied())
+            .unwrap_or_default()
+            .split(',')
+            .filter(|member| !member.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    // Returns the highest free ID of the system range that is free in all of `databases`.
+    fn allocate(databases: &[&Database]) -> Result<u32, AccountError> {
+        SYSTEM_IDS
+            .rev()
+            .find(|id| databases.iter().all(|database| !database.has_id(*id)))
+            .ok_or(AccountError::NoFreeId)
+    }
+
+    fn add_group(&mut self, name: &str, gid: u32) -> Result<u32, AccountError> {
+        if !valid(name) {
+            return Err(AccountError::InvalidName(name.to_owned()));
+        }
+
+        let gid = match gid {
+            0 => Accounts::allocate(&[&self.group])?,
+            gid if self.group.has_id(gid) => return Err(AccountError::IdInUse(gid)),
+            gid => gid,
+        };
+
+        self.group.push(&[name, "x", &gid.to_string(), ""])?;
+
+        Ok(gid)
+    }
+
+    fn remove_group(&mut self, name: &str) -> Result<(), AccountError> {
+        if let Some(gid) = self.group(name) {
+            let gid = gid.to_string();
+
+            if let Some(fields) = self
+                .passwd
+                .entries()
+                .find(|f| f.get(3) == Some(&gid.as_str()))
+            {
+                return Err(AccountError::GroupInUse {
+                    group: name.to_owned(),
+                    user: fields[0].to_owned(),
+                });
+            }
+        }
+
+        self.group.remove(name);
+
+        Ok(())
+    }
+
+    // Makes `user` a supplementary member of exactly `groups`, and returns whether that changed
+    // anything.
+    fn set_groups(&mut self, user: &str, groups: &[String]) -> Result<bool, AccountError> {
+        if let Some(group) = groups.iter().find(|group| self.group(group).is_none()) {
+            return Err(AccountError::UnknownGroup(group.clone()));
+        }
+
+        let mut changed = false;
+
+        for line in self.group.lines.iter_mut() {
+            let mut fields: Vec<String> = line.split(':').map(String::from).collect();
+
+            if line.starts_with('#') || fields.len() < 4 {
+                continue;
+            }
+
+            let mut members: Vec<&str> = fields[3].split(',').filter(|m| !m.is_empty()).collect();
+
+            let member = members.contains(&user);
+            let desired = groups.contains(&fields[0]);
+
+            match (member, desired) {
+                (false, true) => members.push(user),
+                (true, false) => members.retain(|m| *m != user),
+                _ => continue,
+            }
+
+            fields[3] = members.join(",");
+
+            *line = fields.join(":");
+            changed = true;
+        }
+
+        self.group.changed |= changed;
+
+        Ok(changed)
+    }
+
+    // Adds `user`, along with a group named after it if it has no primary group, and returns
+    // whether that group was created.
+    fn add_user(&mut self, user: &User) -> Result<bool, AccountError> {
+        if !valid(&user.name) {
+            return Err(AccountError::InvalidName(user.name.clone()));
+        }
+
+        // N.B.: The fields are checked before anything is added, so that a user that fails to be
+        // added leaves no private group behind.
+        for field in &[&user.comment, &user.home, &user.shell] {
+            if !valid_field(field) {
+                return Err(AccountError::InvalidField(field.to_string()));
+            }
+        }
+
+        let private = user.group.is_empty() && self.group(&user.name).is_none();
+
+        let primary = match user.group.as_str() {
+            "" => &user.name,
+            group => group,
+        };
+
+        if !private && self.group(primary).is_none() {
+            return Err(AccountError::UnknownGroup(primary.to_owned()));
+        }
+
+        if let Some(group) = user.groups.iter().find(|group| self.group(group).is_none()) {
+            return Err(AccountError::UnknownGroup(group.clone()));
+        }
+
+        // N.B.: A group created along with a user gets the same ID if it is free.
+        let uid = match user.uid {
+            0 if private => Accounts::allocate(&[&self.passwd, &self.group])
+                .or_else(|_| Accounts::allocate(&[&self.passwd]))?,
+            0 => Accounts::allocate(&[&self.passwd])?,
+            uid if self.passwd.has_id(uid) => return Err(AccountError::IdInUse(uid)),
+            uid => uid,
+        };
+
+        let gid = match private {
+            true if self.group.has_id(uid) => self.add_group(primary, 0)?,
+            true => self.add_group(primary, uid)?,
+            false => self.group(primary).unwrap_or_default(),
+        };
+
+        let home = match user.home.as_str() {
+            "" => DEFAULT_HOME,
+            home => home,
+        };
+
+        let shell = match user.shell.as_str() {
+            "" => DEFAULT_SHELL,
+            shell => shell,
+        };
+
+        self.passwd.push(&[
+            &user.name,
+            "x",
+            &uid.to_string(),
+            &gid.to_string(),
+            &user.comment,
+            home,
+            shell,
+        ])?;
+
+        let days = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs() / 86400)
+            .unwrap_or_default();
+
+        // N.B.: The password is locked, so that the account cannot be logged in to.
+        self.shadow
+            .push(&[&user.name, "!", &days.to_string(), "", "", "", "", "", ""])?;
+
+        self.set_groups(&user.name, &user.groups)?;
+
+        Ok(private)
+    }
+
+    fn remove_user(&mut self, name: &str) -> Result<(), AccountError> {
+        self.set_groups(name, &[])?;
+        self.passwd.remove(name);
+        self.shadow.remove(name);
+
+        Ok(())
+    }
+
+    fn write(&self) -> io::Result<()> {
+        self.group.write(0o644)?;
+        self.passwd.write(0o644)?;
+        self.shadow.write(0o600)
+    }
+}
+
+/// Managed is the users and groups created by `converge`, which it removes once they are no
+/// longer desired.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Managed {
+    pub users: BTreeSet<String>,
+    pub groups: BTreeSet<String>,
+}
+
+/// Action is a step towards the desired users and groups.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    AddGroup(String),
+    RemoveGroup(String),
+    AddUser(String),
+    RemoveUser(String),
+    UpdateGroups(String),
+    CreateHome(String),
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::AddGroup(name) => write!(f, "add group {}", name),
+            Action::RemoveGroup(name) => write!(f, "remove group {}", name),
+            Action::AddUser(name) => write!(f, "add user {}", name),
+            Action::RemoveUser(name) => write!(f, "remove user {}", name),
+            Action::UpdateGroups(name) => write!(f, "update the groups of {}", name),
+            Action::CreateHome(name) => write!(f, "create the home of {}", name),
+        }
+    }
+}
+
+fn create_home(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    DirBuilder::new().mode(0o700).create(path)?;
+
+    chown(path, Some(Uid::from_raw(uid)), Some(Gid::from_raw(gid))).map_err(io_error)
+}
+
+/// Converges the databases under `root` on the desired users and groups, and returns the
+/// actions taken along with their results. Users and groups that already exist are left alone,
+/// and only those in `managed` are removed once they are no longer desired. The databases are
+/// locked while they are read and written.
+pub fn converge(
+    root: &Path,
+    users: &[User],
+    groups: &[Group],
+    managed: &mut Managed,
+) -> io::Result<Vec<(Action, Result<(), AccountError>)>> {
+    let lock = lock(root)?;
+
+    let mut accounts = Accounts::read(root)?;
+    let mut next = managed.clone();
+    let mut results = vec![];
+
+    // N.B.: Users are removed before groups, since a group cannot be removed while it is the
+    // primary group of a user.
+    for name in &managed.users {
+        if users.iter().any(|user| user.name == *name) {
+            continue;
+        }
+
+        // N.B.: A user that was removed by someone else is forgotten.
+        if accounts.user(name).is_none() {
+            next.users.remove(name);
+            continue;
+        }
+
+        let result = accounts.remove_user(name);
+
+        if result.is_ok() {
+            next.users.remove(name);
+        }
+
+        results.push((Action::RemoveUser(name.clone()), result));
+    }
+
+    let needed: BTreeSet<&str> = groups
+        .iter()
+        .map(|group| group.name.as_str())
+        .chain(users.iter().map(|user| match user.group.as_str() {
+            "" => user.name.as_str(),
+            group => group,
+        }))
+        .collect();
+
+    for name in &managed.groups {
+        if needed.contains(name.as_str()) {
+            continue;
+        }
+
+        if accounts.group(name).is_none() {
+            next.groups.remove(name);
+            continue;
+        }
+
+        let result = accounts.remove_group(name);
+
+        if result.is_ok() {
+            next.groups.remove(name);
+        }
+
+        results.push((Action::RemoveGroup(name.clone()), result));
+    }
+
+    for group in groups {
+        if accounts.group(&group.name).is_some() {
+            continue;
+        }
+
+        let result = accounts.add_group(&group.name, group.gid);
+
+        if result.is_ok() {
+            next.groups.insert(group.name.clone());
+        }
+
+        results.push((Action::AddGroup(group.name.clone()), result.map(|_| ())));
+    }
+
+    for user in users {
+        if accounts.user(&user.name).is_some() {
+            if managed.users.contains(&user.name) {
+                match accounts.set_groups(&user.name, &user.groups) {
+                    Ok(false) => {}
+                    result => {
+                        results.push((Action::UpdateGroups(user.name.clone()), result.map(|_| ())))
+                    }
+                }
+            }
+
+            continue;
+        }
+
+        let result = accounts.add_user(user).map(|private| {
+            next.users.insert(user.name.clone());
+
+            if private {
+                next.groups.insert(user.name.clone());
+            }
+        });
+
+        results.push((Action::AddUser(user.name.clone()), result));
+    }
+
+    accounts.write()?;
+
+    drop(lock);
+
+    *managed = next;
+
+    for user in users {
+        if !user.create_home || user.home.is_empty() || !managed.users.contains(&user.name) {
+            continue;
+        }
+
+        let home = Path::new(&user.home);
+
+        if home.exists() {
+            continue;
+        }
+
+        let (uid, gid) = accounts.user(&user.name).unwrap_or_default();
+
+        let result = create_home(home, uid, gid).map_err(AccountError::from);
+
+        results.push((Action::CreateHome(user.name.clone()), result));
+    }
+
+    Ok(results)
+}
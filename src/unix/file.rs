@@ -0,0 +1,232 @@
+//! Writing files on `File` resources, and restoring them once they are no longer described.
+//!
+//! https://man7.org/linux/man-pages/man2/rename.2.html.
+//! https://man7.org/linux/man-pages/man2/chown.2.html.
+//! https://man7.org/linux/man-pages/man2/readlink.2.html.
+
+use crate::spec::engine::{File, FileOriginal};
+use nix::unistd::{fchown, Gid, Uid};
+use std::fmt;
+use std::fs::{self, Permissions};
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+// The mode of files that do not set one.
+const DEFAULT_MODE: u32 = 0o644;
+
+#[derive(Debug, PartialEq)]
+pub enum FileError {
+    /// A path is not absolute, or not normalized (e.g. `/etc/../etc/hosts`).
+    InvalidPath(String),
+    /// Both `content` and `base64_content` are set.
+    AmbiguousContent(String),
+    /// The contents are not valid base64.
+    InvalidBase64(String),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileError::InvalidPath(path) => write!(f, "invalid path {:?}", path),
+            FileError::AmbiguousContent(path) => {
+                write!(f, "{} sets both content and base64_content", path)
+            }
+            FileError::InvalidBase64(path) => write!(f, "invalid base64 content for {}", path),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+impl From<FileError> for io::Error {
+    fn from(error: FileError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, error)
+    }
+}
+
+fn io_error(err: nix::Error) -> io::Error {
+    match err.as_errno() {
+        Some(errno) => io::Error::from(errno),
+        None => io::Error::new(io::ErrorKind::Other, err),
+    }
+}
+
+/// Validates the path of `spec`.
+pub fn validate(spec: &File) -> Result<(), FileError> {
+    // N.B.: The path is checked as a string, since `Path::components` drops `.` components.
+    let valid = match spec.path.strip_prefix('/') {
+        Some(relative) => relative
+            .split('/')
+            .all(|component| !matches!(component, "" | "." | "..")),
+        None => false,
+    };
+
+    match valid {
+        true => Ok(()),
+        false => Err(FileError::InvalidPath(spec.path.clone())),
+    }
+}
+
+/// Returns the mode of `spec`.
+pub fn mode(spec: &File) -> u32 {
+    match spec.mode & 0o7777 {
+        0 => DEFAULT_MODE,
+        mode => mode,
+    }
+}
+
+/// Returns the contents of `spec`, appended to the contents of `original` if `spec.append` is
+/// set.
+pub fn contents(spec: &File, original: &FileOriginal) -> Result<Vec<u8>, FileError> {
+    let invalid = |_| FileError::InvalidBase64(spec.path.clone());
+
+    let contents = match (spec.content.as_str(), spec.base64_content.trim()) {
+        (content, "") => content.as_bytes().to_vec(),
+        ("", encoded) => base64::decode(encoded).map_err(invalid)?,
+        _ => return Err(FileError::AmbiguousContent(spec.path.clone())),
+    };
+
+    match spec.append && original.existed {
+        true => {
+            let mut appended = base64::decode(&original.base64_content).map_err(invalid)?;
+
+            appended.extend(contents);
+
+            Ok(appended)
+        }
+        false => Ok(contents),
+    }
+}
+
+/// Records the file at `path` as it is now. A symbolic link is recorded along with the file it
+/// points to, since writing the file replaces the link.
+pub fn original(path: &Path) -> io::Result<FileOriginal> {
+    let link = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::read_link(path)?,
+        Ok(_) => PathBuf::new(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(FileOriginal::default()),
+        Err(err) => return Err(err),
+    };
+
+    let link = link.into_os_string().into_string().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} links to a non UTF-8 path", path.display()),
+        )
+    })?;
+
+    // N.B.: A dangling link has no contents.
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound && !link.is_empty() => {
+            return Ok(FileOriginal {
+                existed: true,
+                link,
+                ..Default::default()
+            })
+        }
+        Err(err) => return Err(err),
+    };
+
+    let metadata = fs::metadata(path)?;
+
+    Ok(FileOriginal {
+        existed: true,
+        base64_content: base64::encode(contents),
+        mode: metadata.mode() & 0o7777,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        link,
+    })
+}
+
+/// Whether the file at `path` has the given contents, mode and owner.
+pub fn is_current(path: &Path, contents: &[u8], mode: u32, uid: u32, gid: u32) -> io::Result<bool> {
+    let current = match fs::read(path) {
+        Ok(current) => current,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+
+    let metadata = fs::metadata(path)?;
+
+    Ok(current == contents
+        && metadata.mode() & 0o7777 == mode
+        && metadata.uid() == uid
+        && metadata.gid() == gid)
+}
+
+/// Writes the file at `path` atomically with the given contents, mode and owner, creating its
+/// parent directories as needed.
+pub fn write(path: &Path, contents: &[u8], mode: u32, uid: u32, gid: u32) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    crate::unix::write_atomically_with(path, contents, |file| {
+        file.set_permissions(Permissions::from_mode(mode))?;
+
+        fchown(
+            file.as_raw_fd(),
+            Some(Uid::from_raw(uid)),
+            Some(Gid::from_raw(gid)),
+        )
+        .map_err(io_error)
+    })
+}
+
+/// Whether the file of `spec` is as described.
+pub fn in_sync(spec: &File, original: &FileOriginal) -> io::Result<bool> {
+    validate(spec)?;
+
+    let contents = contents(spec, original)?;
+
+    is_current(
+        Path::new(&spec.path),
+        &contents,
+        mode(spec),
+        spec.uid,
+        spec.gid,
+    )
+}
+
+/// Writes the file of `spec` unless it is already as described, and returns whether it did.
+pub fn apply(spec: &File, original: &FileOriginal) -> io::Result<bool> {
+    if in_sync(spec, original)? {
+        return Ok(false);
+    }
+
+    let contents = contents(spec, original)?;
+
+    write(
+        Path::new(&spec.path),
+        &contents,
+        mode(spec),
+        spec.uid,
+        spec.gid,
+    )?;
+
+    Ok(true)
+}
+
+/// Restores the file at `path` to `original`, or removes it if it did not exist.
+pub fn restore(path: &Path, original: &FileOriginal) -> io::Result<()> {
+    if !original.existed {
+        return match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        };
+    }
+
+    // N.B.: The file the link pointed to was never written, so only the link is restored.
+    if !original.link.is_empty() {
+        return crate::unix::symlink_atomically(&original.link, path);
+    }
+
+    let contents = base64::decode(&original.base64_content)
+        .map_err(|_| FileError::InvalidBase64(path.display().to_string()))?;
+
+    write(path, &contents, original.mode, original.uid, original.gid)
+}
@@ -0,0 +1,199 @@
+//! Setting the hostname and NIS domain name, and keeping `/etc/hostname` and `/etc/hosts` in sync
+//! with them.
+//!
+//! https://man7.org/linux/man-pages/man2/sethostname.2.html.
+//! https://man7.org/linux/man-pages/man2/setdomainname.2.html.
+//! https://man7.org/linux/man-pages/man5/hostname.5.html.
+//! https://man7.org/linux/man-pages/man5/hosts.5.html.
+
+use crate::spec::engine::Hostname;
+use std::ffi::CStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The address the hostname resolves to in `/etc/hosts`, as on Debian.
+pub static HOSTS_ADDRESS: &str = "127.0.1.1";
+
+// The length of the names is limited by the fields of `struct utsname`.
+const MAX_LEN: usize = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum HostnameError {
+    /// A hostname is not made of labels of letters, digits and hyphens.
+    InvalidHostname(String),
+    /// A domain name is not made of labels of letters, digits and hyphens.
+    InvalidDomainname(String),
+}
+
+impl fmt::Display for HostnameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HostnameError::InvalidHostname(name) => write!(f, "invalid hostname {:?}", name),
+            HostnameError::InvalidDomainname(name) => write!(f, "invalid domain name {:?}", name),
+        }
+    }
+}
+
+impl std::error::Error for HostnameError {}
+
+impl From<HostnameError> for io::Error {
+    fn from(error: HostnameError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, error)
+    }
+}
+
+// Whether `name` is made of labels of at most 63 letters, digits and hyphens, that neither start
+// nor end with a hyphen.
+//
+// https://datatracker.ietf.org/doc/html/rfc1123#section-2.1.
+fn valid(name: &str) -> bool {
+    name.len() < MAX_LEN
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() < 64
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// Validates `spec`, and returns the hostname and domain name it sets. An empty domain name
+/// leaves the current one alone.
+pub fn resolve(spec: &Hostname) -> Result<(String, String), HostnameError> {
+    let hostname = spec.hostname.trim();
+    let domainname = spec.domainname.trim();
+
+    if !valid(hostname) {
+        return Err(HostnameError::InvalidHostname(spec.hostname.clone()));
+    }
+
+    let (hostname, rest) = match hostname.find('.') {
+        Some(dot) => (&hostname[..dot], &hostname[dot + 1..]),
+        None => (hostname, ""),
+    };
+
+    let domainname = match domainname {
+        "" => rest,
+        domainname => domainname,
+    };
+
+    if !domainname.is_empty() && !valid(domainname) {
+        return Err(HostnameError::InvalidDomainname(spec.domainname.clone()));
+    }
+
+    Ok((hostname.to_owned(), domainname.to_owned()))
+}
+
+fn string(field: &[libc::c_char]) -> String {
+    unsafe { CStr::from_ptr(field.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Returns the hostname and domain name of the UTS namespace of the calling process. A domain
+/// name that was never set is empty.
+///
+/// https://man7.org/linux/man-pages/man2/uname.2.html.
+pub fn current() -> io::Result<(String, String)> {
+    let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::uname(&mut name) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let domainname = match string(&name.domainname) {
+        domainname if domainname == "(none)" => String::new(),
+        domainname => domainname,
+    };
+
+    Ok((string(&name.nodename), domainname))
+}
+
+/// Sets the hostname and, unless it is empty, the domain name of the UTS namespace of the calling
+/// process.
+pub fn set(hostname: &str, domainname: &str) -> io::Result<()> {
+    nix::unistd::sethostname(hostname).map_err(|err| match err.as_errno() {
+        Some(errno) => io::Error::from(errno),
+        None => io::Error::new(io::ErrorKind::Other, err),
+    })?;
+
+    if domainname.is_empty() {
+        return Ok(());
+    }
+
+    let result = unsafe {
+        libc::setdomainname(domainname.as_ptr() as *const libc::c_char, domainname.len())
+    };
+
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Renders `hosts` with the hostname resolving to `HOSTS_ADDRESS`, replacing any other entry of
+/// that address. Without existing entries, the loopback addresses resolve to `localhost`.
+pub fn render_hosts(hosts: &str, hostname: &str, domainname: &str) -> String {
+    let mut rendered = String::new();
+
+    for line in hosts.lines() {
+        if line.split_whitespace().next() == Some(HOSTS_ADDRESS) {
+            continue;
+        }
+
+        rendered.push_str(line);
+        rendered.push('\n');
+    }
+
+    if rendered.trim().is_empty() {
+        rendered =
+            String::from("127.0.0.1\tlocalhost\n::1\tlocalhost ip6-localhost ip6-loopback\n");
+    }
+
+    let names = match domainname {
+        "" => hostname.to_owned(),
+        domainname => format!("{}.{} {}", hostname, domainname, hostname),
+    };
+
+    rendered.push_str(&format!("{}\t{}\n", HOSTS_ADDRESS, names));
+
+    rendered
+}
+
+// Replaces the contents of `path` unless they are already `contents`, and returns whether it did.
+fn replace(path: &Path, contents: &str) -> io::Result<bool> {
+    if fs::read_to_string(path).ok().as_deref() == Some(contents) {
+        return Ok(false);
+    }
+
+    crate::unix::write_atomically(path, contents.as_bytes())?;
+
+    Ok(true)
+}
+
+/// Writes `hostname` and `hosts` under `root`, and returns the paths that changed.
+pub fn sync(root: &Path, hostname: &str, domainname: &str) -> io::Result<Vec<PathBuf>> {
+    let mut written = vec![];
+
+    let path = root.join("hostname");
+
+    if replace(&path, &format!("{}\n", hostname))? {
+        written.push(path);
+    }
+
+    let path = root.join("hosts");
+
+    let hosts = match fs::read_to_string(&path) {
+        Ok(hosts) => hosts,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+
+    if replace(&path, &render_hosts(&hosts, hostname, domainname))? {
+        written.push(path);
+    }
+
+    Ok(written)
+}
@@ -0,0 +1,432 @@
+//! Loading and unloading kernel modules to converge on `KernelModule` resources.
+//!
+//! https://man7.org/linux/man-pages/man2/finit_module.2.html.
+//! https://man7.org/linux/man-pages/man2/delete_module.2.html.
+//! https://man7.org/linux/man-pages/man5/modules.dep.5.html.
+
+use crate::spec::engine::KernelModule;
+use nix::kmod::{self, DeleteModuleFlags};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// The environment variable that overrides the modules directory, e.g. to use a fake one in tests.
+pub static MODULES_ENV: &str = "COSI_MODULES";
+
+// The kernel decompresses the module itself (Linux 5.17+).
+const MODULE_INIT_COMPRESSED_FILE: libc::c_uint = 4;
+
+/// Returns the directory of the running kernel's modules (e.g. `/lib/modules/5.10.0`).
+pub fn root() -> PathBuf {
+    match std::env::var(MODULES_ENV) {
+        Ok(root) if !root.is_empty() => PathBuf::from(root),
+        _ => Path::new(crate::consts::MODULES).join(nix::sys::utsname::uname().release()),
+    }
+}
+
+#[derive(Debug)]
+pub enum KmodError {
+    /// The module is neither in `modules.dep` nor built in.
+    UnknownModule(String),
+    /// The module, or one it depends on, is blacklisted.
+    Blacklisted(String),
+    /// A parameter is not of the form `name` or `name=value`.
+    InvalidParameter(String),
+    IoError(io::Error),
+}
+
+impl fmt::Display for KmodError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KmodError::UnknownModule(name) => write!(f, "unknown module {}", name),
+            KmodError::Blacklisted(name) => write!(f, "module {} is blacklisted", name),
+            KmodError::InvalidParameter(parameter) => {
+                write!(f, "invalid module parameter {:?}", parameter)
+            }
+            KmodError::IoError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for KmodError {}
+
+impl From<io::Error> for KmodError {
+    fn from(error: io::Error) -> KmodError {
+        KmodError::IoError(error)
+    }
+}
+
+/// Returns the canonical name of a module, in which dashes are underscores (as in
+/// `/proc/modules`).
+pub fn name(module: &str) -> String {
+    module.replace('-', "_")
+}
+
+// Returns the name of the module at `path` (e.g. `kernel/fs/fuse/fuse.ko.xz`).
+fn module_name(path: &str) -> String {
+    let file = path.rsplit('/').next().unwrap_or(path);
+
+    name(file.split(".ko").next().unwrap_or(file))
+}
+
+/// Index is the modules of a kernel and their dependencies, as listed by depmod(8).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Index {
+    // The path of each module, relative to the modules directory, and its dependencies.
+    modules: BTreeMap<String, (PathBuf, Vec<String>)>,
+    builtin: BTreeSet<String>,
+}
+
+impl Index {
+    /// Parses the contents of `modules.dep` and `modules.builtin`.
+    pub fn parse(dep: &str, builtin: &str) -> Index {
+        let modules = dep
+            .lines()
+            .filter_map(|line| {
+                let (module, dependencies) = line.split_at(line.find(':')?);
+
+                let dependencies = dependencies[1..].split_whitespace().map(module_name);
+
+                Some((
+                    module_name(module.trim()),
+                    (PathBuf::from(module.trim()), dependencies.collect()),
+                ))
+            })
+            .collect();
+
+        let builtin = builtin
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| module_name(line.trim()))
+            .collect();
+
+        Index { modules, builtin }
+    }
+
+    /// Reads the index of the modules in `root`.
+    pub fn read(root: &Path) -> io::Result<Index> {
+        let dep = fs::read_to_string(root.join("modules.dep"))?;
+
+        let builtin = match fs::read_to_string(root.join("modules.builtin")) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            result => result?,
+        };
+
+        Ok(Index::parse(&dep, &builtin))
+    }
+
+    /// Whether the module is built into the kernel, and so is always loaded.
+    pub fn is_builtin(&self, module: &str) -> bool {
+        self.builtin.contains(&name(module))
+    }
+
+    /// Returns the path of the module, relative to the modules directory.
+    pub fn path(&self, module: &str) -> Option<&Path> {
+        self.modules
+            .get(&name(module))
+            .map(|(path, _)| path.as_path())
+    }
+
+    /// Returns the modules that `module` depends on, directly or not.
+    pub fn dependencies(&self, module: &str) -> &[String] {
+        self.modules
+            .get(&name(module))
+            .map(|(_, dependencies)| dependencies.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns the modules to load for `module`, in order, ending with `module` itself.
+    pub fn resolve(&self, module: &str) -> Result<Vec<String>, KmodError> {
+        let module = name(module);
+
+        if self.is_builtin(&module) {
+            return Ok(vec![module]);
+        }
+
+        let (_, dependencies) = self
+            .modules
+            .get(&module)
+            .ok_or_else(|| KmodError::UnknownModule(module.clone()))?;
+
+        // N.B.: A module is listed before the modules it depends on, so they are loaded in reverse.
+        let mut order: Vec<String> = dependencies.iter().rev().cloned().collect();
+
+        order.push(module);
+
+        Ok(order)
+    }
+
+    /// Orders `modules` for unloading, so that every module comes before the modules it depends on.
+    pub fn unload_order(&self, modules: &BTreeSet<String>) -> Vec<String> {
+        let mut pending = modules.clone();
+        let mut ordered = vec![];
+
+        while !pending.is_empty() {
+            let next = pending
+                .iter()
+                .find(|module| {
+                    !pending
+                        .iter()
+                        .any(|other| self.dependencies(other).contains(module))
+                })
+                .or_else(|| pending.iter().next())
+                .cloned()
+                .unwrap();
+
+            pending.remove(&next);
+            ordered.push(next);
+        }
+
+        ordered
+    }
+}
+
+/// Validates `parameters` and joins them as `finit_module(2)` expects them. Values that contain
+/// whitespace are quoted, as modprobe(8) does. The kernel does not unescape values, so they
+/// cannot contain double quotes.
+///
+/// https://man7.org/linux/man-pages/man8/modprobe.8.html.
+pub fn parameters(parameters: &[String]) -> Result<String, KmodError> {
+    let mut joined = vec![];
+
+    for parameter in parameters {
+        let mut parts = parameter.splitn(2, '=');
+        let name = parts.next().unwrap_or_default();
+        let value = parts.next();
+
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+            && !value.unwrap_or_default().contains('"');
+
+        if !valid {
+            return Err(KmodError::InvalidParameter(parameter.clone()));
+        }
+
+        match value {
+            Some(value) if value.contains(char::is_whitespace) => {
+                joined.push(format!("{}=\"{}\"", name, value))
+            }
+            _ => joined.push(parameter.clone()),
+        }
+    }
+
+    Ok(joined.join(" "))
+}
+
+/// Loader loads and unloads modules, and can be replaced in tests.
+pub trait Loader {
+    /// Returns the names of the loaded modules.
+    fn loaded(&self) -> io::Result<BTreeSet<String>>;
+
+    /// Loads the module at `path` with `parameters`.
+    fn load(&mut self, path: &Path, parameters: &str) -> io::Result<()>;
+
+    /// Unloads the module named `name`.
+    fn unload(&mut self, name: &str) -> io::Result<()>;
+}
+
+/// KernelLoader loads modules into the running kernel.
+#[derive(Debug, Default)]
+pub struct KernelLoader;
+
+impl Loader for KernelLoader {
+    fn loaded(&self) -> io::Result<BTreeSet<String>> {
+        let modules = fs::read_to_string("/proc/modules")?;
+
+        Ok(modules
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(name)
+            .collect())
+    }
+
+    fn load(&mut self, path: &Path, parameters: &str) -> io::Result<()> {
+        let file = File::open(path)?;
+
+        let parameters = CString::new(parameters)?;
+
+        let flags = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ko") | None => 0,
+            Some(_) => MODULE_INIT_COMPRESSED_FILE,
+        };
+
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_finit_module,
+                file.as_raw_fd(),
+                parameters.as_ptr(),
+                flags,
+            )
+        };
+
+        match result {
+            -1 => match io::Error::last_os_error() {
+                err if err.raw_os_error() == Some(libc::EEXIST) => Ok(()),
+                err => Err(err),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn unload(&mut self, name: &str) -> io::Result<()> {
+        let name = CString::new(name)?;
+
+        // N.B.: A module that is in use is not unloaded, rather than waiting for it.
+        kmod::delete_module(&name, DeleteModuleFlags::O_NONBLOCK).map_err(|err| {
+            match err.as_errno() {
+                Some(errno) => io::Error::from_raw_os_error(errno as i32),
+                None => io::Error::new(io::ErrorKind::Other, err),
+            }
+        })
+    }
+}
+
+/// Action is a step towards the desired modules.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Load(String),
+    Unload(String),
+}
+
+/// Modules converges the loaded modules on `KernelModule` resources.
+pub struct Modules<L> {
+    root: PathBuf,
+    loader: L,
+    // The modules loaded by previous calls, which are unloaded once they are no longer needed.
+    managed: BTreeSet<String>,
+}
+
+impl<L> Modules<L>
+where
+    L: Loader,
+{
+    pub fn new(root: PathBuf, loader: L) -> Modules<L> {
+        Modules {
+            root,
+            loader,
+            managed: BTreeSet::new(),
+        }
+    }
+
+    pub fn loader(&self) -> &L {
+        &self.loader
+    }
+
+    pub fn loader_mut(&mut self) -> &mut L {
+        &mut self.loader
+    }
+
+    /// Reads the index of the modules directory.
+    pub fn index(&self) -> io::Result<Index> {
+        Index::read(&self.root)
+    }
+
+    /// Loads the modules in `desired` along with their dependencies, unloads blacklisted modules
+    /// and those loaded by previous calls that are no longer needed, and returns the actions
+    /// taken along with their results. Modules are loaded after their dependencies, and unloaded
+    /// before them. A module that cannot be resolved is reported as a failed load.
+    ///
+    /// N.B.: The parameters of a module that is already loaded are not changed.
+    pub fn converge(
+        &mut self,
+        desired: &[KernelModule],
+    ) -> io::Result<Vec<(Action, Result<(), KmodError>)>> {
+        let index = self.index()?;
+
+        let mut loaded = self.loader.loaded()?;
+
+        // N.B.: Modules that were unloaded by someone else are no longer managed.
+        let managed = &mut self.managed;
+        managed.retain(|module| loaded.contains(module));
+
+        let blacklist: BTreeSet<String> = desired
+            .iter()
+            .filter(|spec| spec.blacklist)
+            .map(|spec| name(&spec.name))
+            .collect();
+
+        let mut results = vec![];
+        let mut needed = BTreeSet::new();
+        let mut loads = vec![];
+
+        for spec in desired.iter().filter(|spec| !spec.blacklist) {
+            let resolved = index.resolve(&spec.name).and_then(|order| {
+                match order.iter().find(|module| blacklist.contains(*module)) {
+                    Some(module) => Err(KmodError::Blacklisted(module.clone())),
+                    None => Ok((order, parameters(&spec.parameters)?)),
+                }
+            });
+
+            match resolved {
+                Ok((order, parameters)) => {
+                    needed.extend(order.iter().cloned());
+                    loads.push((order, parameters));
+                }
+                Err(err) => results.push((Action::Load(name(&spec.name)), Err(err))),
+            }
+        }
+
+        let unloads: BTreeSet<String> = managed
+            .iter()
+            .filter(|module| !needed.contains(*module))
+            .chain(blacklist.iter().filter(|module| loaded.contains(*module)))
+            .cloned()
+            .collect();
+
+        for module in index.unload_order(&unloads) {
+            let result = self.loader.unload(&module).map_err(KmodError::from);
+
+            if result.is_ok() {
+                loaded.remove(&module);
+            }
+
+            // N.B.: A module that is still in use by others is no longer managed either way.
+            managed.remove(&module);
+
+            results.push((Action::Unload(module), result));
+        }
+
+        for (order, parameters) in loads {
+            for (i, module) in order.iter().enumerate() {
+                if loaded.contains(module) || index.is_builtin(module) {
+                    continue;
+                }
+
+                // N.B.: Only the module itself is given parameters, not its dependencies.
+                let parameters = match i + 1 == order.len() {
+                    true => parameters.as_str(),
+                    false => "",
+                };
+
+                let result = match index.path(module) {
+                    Some(path) => self
+                        .loader
+                        .load(&self.root.join(path), parameters)
+                        .map_err(KmodError::from),
+                    None => Err(KmodError::UnknownModule(module.clone())),
+                };
+
+                let failed = result.is_err();
+
+                if !failed {
+                    loaded.insert(module.clone());
+                    managed.insert(module.clone());
+                }
+
+                results.push((Action::Load(module.clone()), result));
+
+                if failed {
+                    break;
+                }
+            }
+        }
+
+        Ok(results)
+    }
+}
@@ -0,0 +1,503 @@
+//! Mounting and unmounting file systems to converge on `Mount` resources.
+//!
+//! https://man7.org/linux/man-pages/man2/mount.2.html.
+//! https://man7.org/linux/man-pages/man5/proc.5.html (`/proc/[pid]/mountinfo`).
+
+use crate::spec::engine::Mount;
+use nix::errno::Errno;
+use nix::mount::{self, MntFlags, MsFlags};
+use nix::unistd::{self, Gid, Uid};
+use std::collections::{BTreeMap, BTreeSet};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::{fs, io};
+
+/// MountInfo is a line of `/proc/self/mountinfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    pub id: u32,
+    pub parent: u32,
+    /// The `major:minor` of the device.
+    pub device: String,
+    /// The root of the mount within its file system.
+    pub root: String,
+    pub mount_point: String,
+    /// The per-mount options (e.g. `rw,nosuid`).
+    pub options: Vec<String>,
+    /// The optional fields (e.g. `shared:1`).
+    pub fields: Vec<String>,
+    pub fs_type: String,
+    pub source: String,
+    /// The per-superblock options.
+    pub super_options: Vec<String>,
+}
+
+impl MountInfo {
+    /// Parses a line of `/proc/self/mountinfo`.
+    pub fn parse(line: &str) -> io::Result<MountInfo> {
+        let mut parts = line.split(' ');
+
+        let mut next = || {
+            parts
+                .next()
+                .map(unescape)
+                .ok_or_else(|| invalid(format!("truncated mountinfo line: {:?}", line)))
+        };
+
+        let id = next()?.parse().map_err(invalid)?;
+        let parent = next()?.parse().map_err(invalid)?;
+        let device = next()?;
+        let root = next()?;
+        let mount_point = next()?;
+        let options = split(&next()?);
+
+        let mut fields = vec![];
+
+        loop {
+            match next()? {
+                field if field == "-" => break,
+                field => fields.push(field),
+            }
+        }
+
+        let fs_type = next()?;
+        let source = next()?;
+        let super_options = split(&next()?);
+
+        Ok(MountInfo {
+            id,
+            parent,
+            device,
+            root,
+            mount_point,
+            options,
+            fields,
+            fs_type,
+            source,
+            super_options,
+        })
+    }
+}
+
+fn split(options: &str) -> Vec<String> {
+    options.split(',').map(|option| option.to_owned()).collect()
+}
+
+// Decodes the octal escapes (e.g. `\040` for a space) of a mountinfo field.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 4).and_then(|digits| {
+            match digits.iter().all(|d| (b'0'..=b'7').contains(d)) {
+                true => u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok(),
+                false => None,
+            }
+        });
+
+        match (bytes[i], escape) {
+            (b'\\', Some(byte)) => {
+                unescaped.push(byte);
+                i += 4;
+            }
+            (byte, _) => {
+                unescaped.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+/// Parses mountinfo, e.g. the contents of `/proc/self/mountinfo`.
+pub fn parse(mountinfo: &str) -> io::Result<Vec<MountInfo>> {
+    mountinfo
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(MountInfo::parse)
+        .collect()
+}
+
+/// Returns the mounts of the calling process's mount namespace.
+pub fn mountinfo() -> io::Result<Vec<MountInfo>> {
+    parse(&fs::read_to_string("/proc/self/mountinfo")?)
+}
+
+/// Options are the options of a `Mount`, split into the flags of `mount(2)` and the data that is
+/// passed to the file system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub flags: MsFlags,
+    /// The propagation type (e.g. `MS_SHARED`), which is changed by a `mount(2)` call of its own.
+    pub propagation: MsFlags,
+    pub data: String,
+}
+
+impl Options {
+    /// Whether the mount is a bind mount.
+    pub fn is_bind(&self) -> bool {
+        self.flags.contains(MsFlags::MS_BIND)
+    }
+}
+
+/// Splits `options` into flags (e.g. `ro`, `nosuid`) and file system specific data (e.g.
+/// `size=64m`).
+pub fn options(options: &[String]) -> Options {
+    let mut flags = MsFlags::empty();
+    let mut propagation = MsFlags::empty();
+    let mut data = vec![];
+
+    for option in options {
+        let (set, clear) = match option.as_str() {
+            "shared" | "private" | "slave" | "unbindable" | "rshared" | "rprivate" | "rslave"
+            | "runbindable" => {
+                propagation = match option.strip_prefix('r') {
+                    Some(option) => self::propagation(option) | MsFlags::MS_REC,
+                    None => self::propagation(option),
+                };
+
+                continue;
+            }
+            "bind" => (MsFlags::MS_BIND, MsFlags::empty()),
+            "rbind" => (MsFlags::MS_BIND | MsFlags::MS_REC, MsFlags::empty()),
+            "defaults" => (MsFlags::empty(), MsFlags::empty()),
+            "ro" => (MsFlags::MS_RDONLY, MsFlags::empty()),
+            "rw" => (MsFlags::empty(), MsFlags::MS_RDONLY),
+            "nosuid" => (MsFlags::MS_NOSUID, MsFlags::empty()),
+            "suid" => (MsFlags::empty(), MsFlags::MS_NOSUID),
+            "nodev" => (MsFlags::MS_NODEV, MsFlags::empty()),
+            "dev" => (MsFlags::empty(), MsFlags::MS_NODEV),
+            "noexec" => (MsFlags::MS_NOEXEC, MsFlags::empty()),
+            "exec" => (MsFlags::empty(), MsFlags::MS_NOEXEC),
+            "sync" => (MsFlags::MS_SYNCHRONOUS, MsFlags::empty()),
+            "async" => (MsFlags::empty(), MsFlags::MS_SYNCHRONOUS),
+            "dirsync" => (MsFlags::MS_DIRSYNC, MsFlags::empty()),
+            "mand" => (MsFlags::MS_MANDLOCK, MsFlags::empty()),
+            "nomand" => (MsFlags::empty(), MsFlags::MS_MANDLOCK),
+            "noatime" => (MsFlags::MS_NOATIME, MsFlags::empty()),
+            "atime" => (MsFlags::empty(), MsFlags::MS_NOATIME),
+            "nodiratime" => (MsFlags::MS_NODIRATIME, MsFlags::empty()),
+            "diratime" => (MsFlags::empty(), MsFlags::MS_NODIRATIME),
+            "relatime" => (MsFlags::MS_RELATIME, MsFlags::empty()),
+            "norelatime" => (MsFlags::empty(), MsFlags::MS_RELATIME),
+            "strictatime" => (MsFlags::MS_STRICTATIME, MsFlags::empty()),
+            "lazytime" => (
+                MsFlags::from_bits_truncate(libc::MS_LAZYTIME),
+                MsFlags::empty(),
+            ),
+            "silent" => (MsFlags::MS_SILENT, MsFlags::empty()),
+            "loud" => (MsFlags::empty(), MsFlags::MS_SILENT),
+            _ => {
+                data.push(option.as_str());
+                continue;
+            }
+        };
+
+        flags.insert(set);
+        flags.remove(clear);
+    }
+
+    Options {
+        flags,
+        propagation,
+        data: data.join(","),
+    }
+}
+
+fn propagation(option: &str) -> MsFlags {
+    match option {
+        "shared" => MsFlags::MS_SHARED,
+        "private" => MsFlags::MS_PRIVATE,
+        "slave" => MsFlags::MS_SLAVE,
+        _ => MsFlags::MS_UNBINDABLE,
+    }
+}
+
+// Mount points are compared without trailing slashes, other than that of `/`.
+fn normalize(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    }
+}
+
+/// Returns the topmost mount at `target`, if any.
+pub fn find<'a>(mounts: &'a [MountInfo], target: &str) -> Option<&'a MountInfo> {
+    mounts
+        .iter()
+        .rev()
+        .find(|info| normalize(&info.mount_point) == normalize(target))
+}
+
+// Whether the directory `parent` contains `path`, compared by path components.
+fn contains(parent: &str, path: &str) -> bool {
+    normalize(parent) != normalize(path) && Path::new(path).starts_with(parent)
+}
+
+/// Returns the targets that must be mounted before `spec`: the targets in `desired` that contain
+/// its own, and the targets it explicitly depends on.
+pub fn dependencies<'a>(spec: &'a Mount, desired: &'a [Mount]) -> BTreeSet<&'a str> {
+    desired
+        .iter()
+        .map(|m| normalize(&m.target))
+        .filter(|target| contains(target, &spec.target))
+        .chain(spec.depends_on.iter().map(|target| normalize(target)))
+        .collect()
+}
+
+/// Orders `mounts` so that every mount comes after its dependencies, breaking ties by target so
+/// that the order is deterministic.
+pub fn order(mounts: &[Mount]) -> io::Result<Vec<&Mount>> {
+    let mut pending = BTreeMap::new();
+
+    for spec in mounts {
+        if pending.insert(normalize(&spec.target), spec).is_some() {
+            return Err(invalid_input(format!(
+                "{} is mounted more than once",
+                spec.target
+            )));
+        }
+    }
+
+    let mut ordered = Vec::with_capacity(mounts.len());
+
+    while !pending.is_empty() {
+        // N.B.: Dependencies on targets that are not in `mounts` are checked when mounting.
+        let next = pending
+            .iter()
+            .find(|(_, spec)| {
+                dependencies(spec, mounts)
+                    .iter()
+                    .all(|target| !pending.contains_key(target))
+            })
+            .map(|(target, _)| *target);
+
+        match next {
+            Some(target) => ordered.extend(pending.remove(target)),
+            None => {
+                return Err(invalid_input(format!(
+                    "the mounts of {:?} depend on each other",
+                    pending.keys().collect::<Vec<_>>()
+                )))
+            }
+        }
+    }
+
+    Ok(ordered)
+}
+
+/// Action is a step towards the desired mounts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Mount(Mount),
+    Unmount(String),
+}
+
+/// Returns the actions that converge `mounted` on `desired`. Mounts in `managed` that are no
+/// longer desired are unmounted in the reverse of their order, other mounts are left alone.
+pub fn plan(
+    desired: &[Mount],
+    mounted: &[MountInfo],
+    managed: &BTreeMap<String, Mount>,
+) -> io::Result<Vec<Action>> {
+    let targets: BTreeSet<&str> = desired.iter().map(|m| normalize(&m.target)).collect();
+
+    let managed: Vec<Mount> = managed.values().cloned().collect();
+
+    let unmounts = order(&managed)?
+        .into_iter()
+        .rev()
+        .filter(|m| !targets.contains(normalize(&m.target)))
+        .filter(|m| find(mounted, &m.target).is_some())
+        .map(|m| Action::Unmount(m.target.clone()));
+
+    let mounts = order(desired)?
+        .into_iter()
+        .filter(|m| find(mounted, &m.target).is_none())
+        .map(|m| Action::Mount(m.clone()));
+
+    Ok(unmounts.chain(mounts).collect())
+}
+
+/// Converges the mounts of the calling process's mount namespace on `desired`, and returns the
+/// actions taken along with their results. `managed` holds the mounts made by previous calls, by
+/// target, and is updated to reflect the actions that succeeded. A mount is only attempted once its
+/// dependencies are mounted.
+pub fn converge(
+    desired: &[Mount],
+    managed: &mut BTreeMap<String, Mount>,
+) -> io::Result<Vec<(Action, io::Result<()>)>> {
+    let mounted = mountinfo()?;
+
+    // N.B.: Targets that were unmounted by someone else are no longer managed.
+    managed.retain(|target, _| find(&mounted, target).is_some());
+
+    let mut present: BTreeSet<String> = mounted
+        .iter()
+        .map(|info| normalize(&info.mount_point).to_owned())
+        .collect();
+
+    let mut results = vec![];
+
+    for action in plan(desired, &mounted, managed)? {
+        let result = match &action {
+            Action::Mount(spec) => match dependencies(spec, desired)
+                .into_iter()
+                .find(|target| !present.contains(*target))
+            {
+                Some(target) => Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("{} is not mounted", target),
+                )),
+                None => mount(spec).map(|_| {
+                    present.insert(normalize(&spec.target).to_owned());
+                    managed.insert(spec.target.clone(), spec.clone());
+                }),
+            },
+            Action::Unmount(target) => unmount(target).map(|_| {
+                present.remove(normalize(target));
+                managed.remove(target);
+            }),
+        };
+
+        results.push((action, result));
+    }
+
+    Ok(results)
+}
+
+/// Mounts `spec` with `mount(2)`, creating its target if it is missing.
+pub fn mount(spec: &Mount) -> io::Result<()> {
+    let options = options(&spec.options);
+
+    let source = match spec.source.as_str() {
+        "" => None,
+        source => Some(source),
+    };
+
+    let fs_type = match spec.r#type.as_str() {
+        "" => None,
+        fs_type => Some(fs_type),
+    };
+
+    let data = match options.data.as_str() {
+        "" => None,
+        data => Some(data),
+    };
+
+    let target = Path::new(&spec.target);
+
+    if fs::symlink_metadata(target).is_err() {
+        create_target(spec, &options)?;
+    }
+
+    mount::mount(source, target, fs_type, options.flags, data).map_err(errno)?;
+
+    // N.B.: A bind mount ignores the flags other than `MS_REC`, which are applied by remounting
+    // it, and the propagation type is changed by a call of its own.
+    let remount = options.flags - (MsFlags::MS_BIND | MsFlags::MS_REC);
+
+    let mut result = Ok(());
+
+    if options.is_bind() && !remount.is_empty() {
+        let flags = MsFlags::MS_REMOUNT | MsFlags::MS_BIND | remount;
+
+        result = mount::mount(None::<&str>, target, None::<&str>, flags, None::<&str>);
+    }
+
+    if result.is_ok() && !options.propagation.is_empty() {
+        result = mount::mount(
+            None::<&str>,
+            target,
+            None::<&str>,
+            options.propagation,
+            None::<&str>,
+        );
+    }
+
+    // N.B.: A mount that is only partially set up is undone, so that it is retried.
+    if let Err(err) = result {
+        let _ = mount::umount2(target, MntFlags::MNT_DETACH);
+
+        return Err(errno(err));
+    }
+
+    Ok(())
+}
+
+// Creates the missing components of the target of `spec` with its mode and ownership. The target
+// of a bind mount of a file is created as a file.
+fn create_target(spec: &Mount, options: &Options) -> io::Result<()> {
+    let target = Path::new(&spec.target);
+
+    let mode = match spec.mode {
+        0 => 0o755,
+        mode => mode,
+    };
+
+    let file = options.is_bind() && fs::metadata(&spec.source)?.is_file();
+
+    let missing: Vec<&Path> = target
+        .ancestors()
+        .take_while(|path| fs::symlink_metadata(path).is_err())
+        .collect();
+
+    for path in missing.into_iter().rev() {
+        if path == target && file {
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(mode)
+                .open(path)?;
+        } else {
+            fs::DirBuilder::new().mode(mode).create(path)?;
+        }
+
+        // N.B.: The mode given at creation is subject to the umask.
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+
+        unistd::chown(
+            path,
+            Some(Uid::from_raw(spec.uid)),
+            Some(Gid::from_raw(spec.gid)),
+        )
+        .map_err(errno)?;
+    }
+
+    Ok(())
+}
+
+/// Unmounts `target` with `umount2(2)`, falling back to a lazy unmount (`MNT_DETACH`) when it is
+/// busy.
+pub fn unmount(target: &str) -> io::Result<()> {
+    match mount::umount2(Path::new(target), MntFlags::empty()) {
+        Err(nix::Error::Sys(Errno::EBUSY)) => {
+            mount::umount2(Path::new(target), MntFlags::MNT_DETACH).map_err(errno)
+        }
+        result => result.map_err(errno),
+    }
+}
+
+fn errno(err: nix::Error) -> io::Error {
+    match err.as_errno() {
+        Some(errno) => io::Error::from_raw_os_error(errno as i32),
+        None => io::Error::new(io::ErrorKind::Other, err),
+    }
+}
+
+fn invalid<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn invalid_input<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidInput, err)
+}
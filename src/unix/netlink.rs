@@ -0,0 +1,592 @@
+//! A minimal rtnetlink client for links, addresses and routes.
+//!
+//! https://man7.org/linux/man-pages/man7/netlink.7.html.
+//! https://man7.org/linux/man-pages/man7/rtnetlink.7.html.
+
+use std::convert::TryInto;
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::RawFd;
+
+// The size of `struct nlmsghdr`.
+const HEADER_LEN: usize = 16;
+
+// The attribute of a veth link's peer, within `IFLA_INFO_DATA` (`linux/veth.h`).
+const VETH_INFO_PEER: u16 = 1;
+
+/// LinkInfo is a network link, as reported by the kernel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkInfo {
+    pub index: u32,
+    pub name: String,
+    pub up: bool,
+    pub mtu: u32,
+    /// The hardware address (e.g. `aa:bb:cc:dd:ee:ff`).
+    pub address: String,
+    /// The RFC 2863 operational state (e.g. `up`, `down`, `lowerlayerdown`).
+    pub operational_state: String,
+    /// The kind of a virtual link (e.g. `veth`, `bridge`).
+    pub kind: String,
+}
+
+/// AddressInfo is an address assigned to a link.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressInfo {
+    pub index: u32,
+    pub address: IpAddr,
+    pub prefix: u8,
+}
+
+/// RouteInfo is a unicast route of the main routing table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteInfo {
+    pub destination: IpAddr,
+    pub prefix: u8,
+    pub gateway: Option<IpAddr>,
+    /// The index of the outgoing link.
+    pub index: Option<u32>,
+    pub metric: u32,
+}
+
+/// Socket is an rtnetlink socket of the calling process's network namespace.
+pub struct Socket {
+    fd: RawFd,
+    sequence: u32,
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn pad(buffer: &mut Vec<u8>) {
+    buffer.resize(align(buffer.len()), 0);
+}
+
+// Appends an attribute (`struct rtattr`) to `buffer`.
+fn attribute(buffer: &mut Vec<u8>, kind: u16, data: &[u8]) {
+    buffer.extend_from_slice(&((4 + data.len()) as u16).to_ne_bytes());
+    buffer.extend_from_slice(&kind.to_ne_bytes());
+    buffer.extend_from_slice(data);
+    pad(buffer);
+}
+
+// Appends an attribute that nests the attributes appended by `f`.
+fn nested<F>(buffer: &mut Vec<u8>, kind: u16, f: F)
+where
+    F: FnOnce(&mut Vec<u8>),
+{
+    let start = buffer.len();
+
+    attribute(buffer, kind | libc::NLA_F_NESTED as u16, &[]);
+
+    f(buffer);
+
+    let len = (buffer.len() - start) as u16;
+
+    buffer[start..start + 2].copy_from_slice(&len.to_ne_bytes());
+}
+
+// Returns the attributes in `data`, by type.
+fn attributes(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut attributes = vec![];
+
+    while data.len() >= 4 {
+        let len = u16::from_ne_bytes([data[0], data[1]]) as usize;
+        let kind = u16::from_ne_bytes([data[2], data[3]]) & libc::NLA_TYPE_MASK as u16;
+
+        if len < 4 || len > data.len() {
+            break;
+        }
+
+        attributes.push((kind, &data[4..len]));
+
+        data = &data[align(len).min(data.len())..];
+    }
+
+    attributes
+}
+
+fn find(attributes: &[(u16, &[u8])], kind: u16) -> Option<Vec<u8>> {
+    attributes
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, data)| data.to_vec())
+}
+
+fn string(data: &[u8]) -> String {
+    String::from_utf8_lossy(data.split(|b| *b == 0).next().unwrap_or_default()).into_owned()
+}
+
+fn c_string(value: &str) -> Vec<u8> {
+    let mut data = value.as_bytes().to_vec();
+    data.push(0);
+    data
+}
+
+fn u32_of(data: &[u8]) -> Option<u32> {
+    Some(u32::from_ne_bytes(data.get(..4)?.try_into().ok()?))
+}
+
+fn address_of(data: &[u8]) -> Option<IpAddr> {
+    match data.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::new(
+            data[0], data[1], data[2], data[3],
+        ))),
+        16 => {
+            let octets: [u8; 16] = data.try_into().ok()?;
+
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+fn octets(address: &IpAddr) -> Vec<u8> {
+    match address {
+        IpAddr::V4(address) => address.octets().to_vec(),
+        IpAddr::V6(address) => address.octets().to_vec(),
+    }
+}
+
+fn family(address: &IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(_) => libc::AF_INET as u8,
+        IpAddr::V6(_) => libc::AF_INET6 as u8,
+    }
+}
+
+// `struct ifinfomsg`.
+fn ifinfomsg(index: u32, flags: u32, change: u32) -> Vec<u8> {
+    let mut message = vec![libc::AF_UNSPEC as u8, 0, 0, 0];
+    message.extend_from_slice(&(index as i32).to_ne_bytes());
+    message.extend_from_slice(&flags.to_ne_bytes());
+    message.extend_from_slice(&change.to_ne_bytes());
+    message
+}
+
+// `struct ifaddrmsg`.
+fn ifaddrmsg(family: u8, prefix: u8, index: u32) -> Vec<u8> {
+    let mut message = vec![family, prefix, 0, libc::RT_SCOPE_UNIVERSE];
+    message.extend_from_slice(&index.to_ne_bytes());
+    message
+}
+
+// `struct rtmsg`.
+fn rtmsg(family: u8, prefix: u8, scope: u8) -> Vec<u8> {
+    vec![
+        family,
+        prefix,
+        0,
+        0,
+        libc::RT_TABLE_MAIN,
+        libc::RTPROT_STATIC,
+        scope,
+        libc::RTN_UNICAST,
+        0,
+        0,
+        0,
+        0,
+    ]
+}
+
+fn operational_state(state: u8) -> &'static str {
+    match state {
+        1 => "notpresent",
+        2 => "down",
+        3 => "lowerlayerdown",
+        4 => "testing",
+        5 => "dormant",
+        6 => "up",
+        _ => "unknown",
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Socket {
+    /// Opens an rtnetlink socket.
+    pub fn new() -> io::Result<Socket> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let socket = Socket { fd, sequence: 0 };
+
+        let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+
+        let result = unsafe {
+            libc::bind(
+                fd,
+                &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+
+        match result {
+            0 => Ok(socket),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    // Sends a request, and returns the type and payload of the messages of the reply. A request
+    // that is not a dump is acknowledged, or fails with the error of the acknowledgement.
+    fn request(
+        &mut self,
+        kind: u16,
+        flags: u16,
+        payload: &[u8],
+    ) -> io::Result<Vec<(u16, Vec<u8>)>> {
+        // N.B.: NLM_F_DUMP shares a bit with NLM_F_EXCL, which is set when creating.
+        let dump = flags & libc::NLM_F_DUMP as u16 == libc::NLM_F_DUMP as u16;
+
+        let flags = match dump {
+            true => flags | libc::NLM_F_REQUEST as u16,
+            false => flags | (libc::NLM_F_REQUEST | libc::NLM_F_ACK) as u16,
+        };
+
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let mut message = Vec::with_capacity(HEADER_LEN + payload.len());
+        message.extend_from_slice(&((HEADER_LEN + payload.len()) as u32).to_ne_bytes());
+        message.extend_from_slice(&kind.to_ne_bytes());
+        message.extend_from_slice(&flags.to_ne_bytes());
+        message.extend_from_slice(&self.sequence.to_ne_bytes());
+        message.extend_from_slice(&0u32.to_ne_bytes());
+        message.extend_from_slice(payload);
+
+        let sent = unsafe {
+            libc::send(
+                self.fd,
+                message.as_ptr() as *const libc::c_void,
+                message.len(),
+                0,
+            )
+        };
+
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut replies = vec![];
+        let mut buffer = vec![0u8; 1 << 16];
+
+        loop {
+            let received = unsafe {
+                libc::recv(
+                    self.fd,
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                    0,
+                )
+            };
+
+            if received < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut data = &buffer[..received as usize];
+
+            while data.len() >= HEADER_LEN {
+                let len = u32_of(&data[0..4]).unwrap_or_default() as usize;
+                let kind = u16::from_ne_bytes([data[4], data[5]]);
+                let sequence = u32_of(&data[8..12]).unwrap_or_default();
+
+                if len < HEADER_LEN || len > data.len() {
+                    return Err(invalid("truncated netlink message"));
+                }
+
+                let body = &data[HEADER_LEN..len];
+
+                data = &data[align(len).min(data.len())..];
+
+                // N.B.: Replies to earlier requests that were abandoned are skipped.
+                if sequence != self.sequence {
+                    continue;
+                }
+
+                match kind as i32 {
+                    libc::NLMSG_DONE | libc::NLMSG_ERROR => {
+                        let errno = body
+                            .get(..4)
+                            .and_then(u32_of)
+                            .map(|errno| errno as i32)
+                            .unwrap_or_default();
+
+                        return match errno {
+                            0 => Ok(replies),
+                            errno => Err(io::Error::from_raw_os_error(-errno)),
+                        };
+                    }
+                    _ => replies.push((kind, body.to_vec())),
+                }
+            }
+        }
+    }
+
+    /// Returns the links.
+    pub fn links(&mut self) -> io::Result<Vec<LinkInfo>> {
+        let replies = self.request(
+            libc::RTM_GETLINK,
+            libc::NLM_F_DUMP as u16,
+            &ifinfomsg(0, 0, 0),
+        )?;
+
+        let mut links = vec![];
+
+        for (kind, body) in replies {
+            if kind != libc::RTM_NEWLINK || body.len() < 16 {
+                continue;
+            }
+
+            let attributes = attributes(&body[16..]);
+
+            let kind = find(&attributes, libc::IFLA_LINKINFO)
+                .and_then(|info| find(&self::attributes(&info), libc::IFLA_INFO_KIND))
+                .map(|kind| string(&kind))
+                .unwrap_or_default();
+
+            let address = find(&attributes, libc::IFLA_ADDRESS)
+                .unwrap_or_default()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(":");
+
+            links.push(LinkInfo {
+                index: u32_of(&body[4..8]).unwrap_or_default(),
+                name: find(&attributes, libc::IFLA_IFNAME)
+                    .map(|name| string(&name))
+                    .unwrap_or_default(),
+                up: u32_of(&body[8..12]).unwrap_or_default() & libc::IFF_UP as u32 != 0,
+                mtu: find(&attributes, libc::IFLA_MTU)
+                    .and_then(|mtu| u32_of(&mtu))
+                    .unwrap_or_default(),
+                address,
+                operational_state: find(&attributes, libc::IFLA_OPERSTATE)
+                    .and_then(|state| state.first().copied())
+                    .map(operational_state)
+                    .unwrap_or("unknown")
+                    .to_owned(),
+                kind,
+            });
+        }
+
+        Ok(links)
+    }
+
+    /// Creates a virtual link of `kind` (e.g. `veth`, `bridge`). A veth link is created along with
+    /// its `peer`.
+    pub fn add_link(&mut self, name: &str, kind: &str, peer: Option<&str>) -> io::Result<()> {
+        let mut payload = ifinfomsg(0, 0, 0);
+
+        attribute(&mut payload, libc::IFLA_IFNAME, &c_string(name));
+
+        nested(&mut payload, libc::IFLA_LINKINFO, |info| {
+            attribute(info, libc::IFLA_INFO_KIND, kind.as_bytes());
+
+            if let Some(peer) = peer {
+                nested(info, libc::IFLA_INFO_DATA, |data| {
+                    nested(data, VETH_INFO_PEER, |peer_info| {
+                        peer_info.extend_from_slice(&ifinfomsg(0, 0, 0));
+
+                        attribute(peer_info, libc::IFLA_IFNAME, &c_string(peer));
+                    });
+                });
+            }
+        });
+
+        let flags = (libc::NLM_F_CREATE | libc::NLM_F_EXCL) as u16;
+
+        self.request(libc::RTM_NEWLINK, flags, &payload).map(|_| ())
+    }
+
+    /// Sets a link up or down, and sets its MTU unless it is 0.
+    pub fn set_link(&mut self, index: u32, up: bool, mtu: u32) -> io::Result<()> {
+        let flags = match up {
+            true => libc::IFF_UP as u32,
+            false => 0,
+        };
+
+        let mut payload = ifinfomsg(index, flags, libc::IFF_UP as u32);
+
+        if mtu != 0 {
+            attribute(&mut payload, libc::IFLA_MTU, &mtu.to_ne_bytes());
+        }
+
+        self.request(libc::RTM_NEWLINK, 0, &payload).map(|_| ())
+    }
+
+    /// Deletes a link.
+    pub fn delete_link(&mut self, index: u32) -> io::Result<()> {
+        self.request(libc::RTM_DELLINK, 0, &ifinfomsg(index, 0, 0))
+            .map(|_| ())
+    }
+
+    /// Returns the addresses of all links.
+    pub fn addresses(&mut self) -> io::Result<Vec<AddressInfo>> {
+        let replies = self.request(
+            libc::RTM_GETADDR,
+            libc::NLM_F_DUMP as u16,
+            &ifaddrmsg(libc::AF_UNSPEC as u8, 0, 0),
+        )?;
+
+        let mut addresses = vec![];
+
+        for (kind, body) in replies {
+            if kind != libc::RTM_NEWADDR || body.len() < 8 {
+                continue;
+            }
+
+            let attributes = attributes(&body[8..]);
+
+            // N.B.: For point-to-point links `IFA_ADDRESS` is the peer's address.
+            let address = find(&attributes, libc::IFA_LOCAL)
+                .or_else(|| find(&attributes, libc::IFA_ADDRESS))
+                .and_then(|address| address_of(&address));
+
+            if let Some(address) = address {
+                addresses.push(AddressInfo {
+                    index: u32_of(&body[4..8]).unwrap_or_default(),
+                    address,
+                    prefix: body[1],
+                });
+            }
+        }
+
+        Ok(addresses)
+    }
+
+    fn address_request(&mut self, kind: u16, flags: u16, address: &AddressInfo) -> io::Result<()> {
+        let mut payload = ifaddrmsg(family(&address.address), address.prefix, address.index);
+
+        if address.address.is_ipv4() {
+            attribute(&mut payload, libc::IFA_LOCAL, &octets(&address.address));
+        }
+
+        attribute(&mut payload, libc::IFA_ADDRESS, &octets(&address.address));
+
+        self.request(kind, flags, &payload).map(|_| ())
+    }
+
+    /// Assigns an address to a link.
+    pub fn add_address(&mut self, address: &AddressInfo) -> io::Result<()> {
+        let flags = (libc::NLM_F_CREATE | libc::NLM_F_EXCL) as u16;
+
+        self.address_request(libc::RTM_NEWADDR, flags, address)
+    }
+
+    /// Removes an address from a link.
+    pub fn delete_address(&mut self, address: &AddressInfo) -> io::Result<()> {
+        self.address_request(libc::RTM_DELADDR, 0, address)
+    }
+
+    /// Returns the unicast routes of the main routing table.
+    pub fn routes(&mut self) -> io::Result<Vec<RouteInfo>> {
+        let replies = self.request(
+            libc::RTM_GETROUTE,
+            libc::NLM_F_DUMP as u16,
+            &rtmsg(libc::AF_UNSPEC as u8, 0, 0),
+        )?;
+
+        let mut routes = vec![];
+
+        for (kind, body) in replies {
+            if kind != libc::RTM_NEWROUTE || body.len() < 12 {
+                continue;
+            }
+
+            let attributes = attributes(&body[12..]);
+
+            let table = find(&attributes, libc::RTA_TABLE)
+                .and_then(|table| u32_of(&table))
+                .unwrap_or(body[4] as u32);
+
+            if table != libc::RT_TABLE_MAIN as u32 || body[7] != libc::RTN_UNICAST {
+                continue;
+            }
+
+            let destination = match find(&attributes, libc::RTA_DST) {
+                Some(destination) => address_of(&destination),
+                None if body[0] == libc::AF_INET6 as u8 => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+                None => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            };
+
+            if let Some(destination) = destination {
+                routes.push(RouteInfo {
+                    destination,
+                    prefix: body[1],
+                    gateway: find(&attributes, libc::RTA_GATEWAY)
+                        .and_then(|gateway| address_of(&gateway)),
+                    index: find(&attributes, libc::RTA_OIF).and_then(|index| u32_of(&index)),
+                    metric: find(&attributes, libc::RTA_PRIORITY)
+                        .and_then(|metric| u32_of(&metric))
+                        .unwrap_or_default(),
+                });
+            }
+        }
+
+        Ok(routes)
+    }
+
+    fn route_request(&mut self, kind: u16, flags: u16, route: &RouteInfo) -> io::Result<()> {
+        // N.B.: A route without a gateway is directly reachable on its link.
+        let scope = match route.gateway {
+            Some(_) => libc::RT_SCOPE_UNIVERSE,
+            None => libc::RT_SCOPE_LINK,
+        };
+
+        let mut payload = rtmsg(family(&route.destination), route.prefix, scope);
+
+        if route.prefix != 0 {
+            attribute(&mut payload, libc::RTA_DST, &octets(&route.destination));
+        }
+
+        if let Some(gateway) = &route.gateway {
+            attribute(&mut payload, libc::RTA_GATEWAY, &octets(gateway));
+        }
+
+        if let Some(index) = route.index {
+            attribute(&mut payload, libc::RTA_OIF, &index.to_ne_bytes());
+        }
+
+        if route.metric != 0 {
+            attribute(
+                &mut payload,
+                libc::RTA_PRIORITY,
+                &route.metric.to_ne_bytes(),
+            );
+        }
+
+        self.request(kind, flags, &payload).map(|_| ())
+    }
+
+    /// Adds a route to the main routing table.
+    pub fn add_route(&mut self, route: &RouteInfo) -> io::Result<()> {
+        let flags = (libc::NLM_F_CREATE | libc::NLM_F_EXCL) as u16;
+
+        self.route_request(libc::RTM_NEWROUTE, flags, route)
+    }
+
+    /// Deletes a route from the main routing table.
+    pub fn delete_route(&mut self, route: &RouteInfo) -> io::Result<()> {
+        self.route_request(libc::RTM_DELROUTE, 0, route)
+    }
+}
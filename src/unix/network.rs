@@ -0,0 +1,417 @@
+//! Converging network links, addresses and routes on `Link`, `Address` and `Route` resources.
+
+use crate::spec::engine::{Address, Link, Route};
+use crate::unix::netlink::{AddressInfo, LinkInfo, RouteInfo, Socket};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+#[derive(Debug, PartialEq)]
+pub enum NetworkError {
+    /// An address is not an IP address, optionally followed by a prefix length.
+    InvalidAddress(String),
+    /// A link does not exist.
+    UnknownLink(String),
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkError::InvalidAddress(address) => write!(f, "invalid address {:?}", address),
+            NetworkError::UnknownLink(name) => write!(f, "unknown link {}", name),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl From<NetworkError> for io::Error {
+    fn from(error: NetworkError) -> io::Error {
+        let kind = match error {
+            NetworkError::InvalidAddress(_) => io::ErrorKind::InvalidInput,
+            NetworkError::UnknownLink(_) => io::ErrorKind::NotFound,
+        };
+
+        io::Error::new(kind, error)
+    }
+}
+
+/// Parses an address and its prefix length (e.g. `192.0.2.1/24`). An address without a prefix
+/// length is a host address.
+pub fn cidr(value: &str) -> Result<(IpAddr, u8), NetworkError> {
+    let invalid = || NetworkError::InvalidAddress(value.to_owned());
+
+    let mut parts = value.trim().splitn(2, '/');
+
+    let address: IpAddr = parts
+        .next()
+        .unwrap_or_default()
+        .parse()
+        .map_err(|_| invalid())?;
+
+    let max = match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+
+    let prefix = match parts.next() {
+        Some(prefix) => prefix.parse().map_err(|_| invalid())?,
+        None => max,
+    };
+
+    match prefix <= max {
+        true => Ok((address, prefix)),
+        false => Err(invalid()),
+    }
+}
+
+fn index(links: &[LinkInfo], name: &str) -> Result<u32, NetworkError> {
+    links
+        .iter()
+        .find(|link| link.name == name)
+        .map(|link| link.index)
+        .ok_or_else(|| NetworkError::UnknownLink(name.to_owned()))
+}
+
+/// Resolves `address` against `links`.
+pub fn address_info(address: &Address, links: &[LinkInfo]) -> Result<AddressInfo, NetworkError> {
+    let (ip, prefix) = cidr(&address.address)?;
+
+    Ok(AddressInfo {
+        index: index(links, &address.link)?,
+        address: ip,
+        prefix,
+    })
+}
+
+/// Resolves `route` against `links`.
+pub fn route_info(route: &Route, links: &[LinkInfo]) -> Result<RouteInfo, NetworkError> {
+    let gateway = match route.gateway.trim() {
+        "" => None,
+        gateway => Some(cidr(gateway)?.0),
+    };
+
+    let (destination, prefix) = match route.destination.trim() {
+        "" | "default" => match gateway {
+            Some(IpAddr::V6(_)) => (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+            _ => (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        },
+        destination => cidr(destination)?,
+    };
+
+    let index = match route.link.as_str() {
+        "" => None,
+        link => Some(index(links, link)?),
+    };
+
+    Ok(RouteInfo {
+        destination,
+        prefix,
+        gateway,
+        index,
+        metric: route.metric,
+    })
+}
+
+/// Whether `route` is among `routes`. Unset fields of `route` match any value, since the kernel
+/// fills them in (e.g. the outgoing link, or the default metric of IPv6 routes).
+pub fn installed(routes: &[RouteInfo], route: &RouteInfo) -> bool {
+    routes.iter().any(|r| {
+        r.destination == route.destination
+            && r.prefix == route.prefix
+            && r.gateway == route.gateway
+            && (route.index.is_none() || r.index == route.index)
+            && (route.metric == 0 || r.metric == route.metric)
+    })
+}
+
+/// Action is a step towards the desired network configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    CreateLink(Link),
+    ConfigureLink(Link),
+    DeleteLink(String),
+    AddAddress(Address),
+    DeleteAddress(Address),
+    AddRoute(Route),
+    DeleteRoute(Route),
+}
+
+fn describe(route: &Route) -> String {
+    let mut description = match route.destination.as_str() {
+        "" => String::from("default"),
+        destination => destination.to_owned(),
+    };
+
+    if !route.gateway.is_empty() {
+        description += &format!(" via {}", route.gateway);
+    }
+
+    if !route.link.is_empty() {
+        description += &format!(" dev {}", route.link);
+    }
+
+    description
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::CreateLink(link) => write!(f, "create link {}", link.name),
+            Action::ConfigureLink(link) => write!(f, "configure link {}", link.name),
+            Action::DeleteLink(name) => write!(f, "delete link {}", name),
+            Action::AddAddress(address) => {
+                write!(f, "add address {} to {}", address.address, address.link)
+            }
+            Action::DeleteAddress(address) => {
+                write!(
+                    f,
+                    "delete address {} from {}",
+                    address.address, address.link
+                )
+            }
+            Action::AddRoute(route) => write!(f, "add route {}", describe(route)),
+            Action::DeleteRoute(route) => write!(f, "delete route {}", describe(route)),
+        }
+    }
+}
+
+/// Network converges the links, addresses and routes of the calling process's network namespace.
+pub struct Network {
+    socket: Socket,
+    // The links, addresses and routes created by previous calls, which are removed once they are
+    // no longer desired.
+    links: BTreeMap<String, Link>,
+    addresses: Vec<Address>,
+    routes: Vec<Route>,
+}
+
+impl Network {
+    pub fn new() -> io::Result<Network> {
+        Ok(Network {
+            socket: Socket::new()?,
+            links: BTreeMap::new(),
+            addresses: vec![],
+            routes: vec![],
+        })
+    }
+
+    pub fn socket(&mut self) -> &mut Socket {
+        &mut self.socket
+    }
+
+    /// Takes over links, addresses and routes created by a previous process (e.g. before a
+    /// restart), so that they are removed once they are no longer desired.
+    pub fn adopt(&mut self, links: &[Link], addresses: &[Address], routes: &[Route]) {
+        for link in links {
+            self.links.insert(link.name.clone(), link.clone());
+        }
+
+        for address in addresses {
+            if !self.addresses.contains(address) {
+                self.addresses.push(address.clone());
+            }
+        }
+
+        for route in routes {
+            if !self.routes.contains(route) {
+                self.routes.push(route.clone());
+            }
+        }
+    }
+
+    /// Returns whether the link named `name` was created, and is removed once no longer desired.
+    pub fn created_link(&self, name: &str) -> bool {
+        self.links.contains_key(name)
+    }
+
+    /// Returns whether `address` was added, and is removed once no longer desired.
+    pub fn created_address(&self, address: &Address) -> bool {
+        self.addresses.contains(address)
+    }
+
+    /// Returns whether `route` was added, and is removed once no longer desired.
+    pub fn created_route(&self, route: &Route) -> bool {
+        self.routes.contains(route)
+    }
+
+    /// Converges on the desired links, addresses and routes, and returns the actions taken along
+    /// with their results. Routes, addresses and links that are no longer desired are removed in
+    /// that order, before links, addresses and routes are created, configured and added.
+    pub fn converge(
+        &mut self,
+        links: &[Link],
+        addresses: &[Address],
+        routes: &[Route],
+    ) -> io::Result<Vec<(Action, io::Result<()>)>> {
+        let mut results = vec![];
+
+        self.remove(links, addresses, routes, &mut results)?;
+
+        let mut current = self.socket.links()?;
+
+        for link in links {
+            if current.iter().any(|info| info.name == link.name) {
+                continue;
+            }
+
+            let result = match link.kind.as_str() {
+                "" => Err(NetworkError::UnknownLink(link.name.clone()).into()),
+                kind => {
+                    let peer = Some(link.peer.as_str()).filter(|peer| !peer.is_empty());
+
+                    self.socket.add_link(&link.name, kind, peer)
+                }
+            };
+
+            // N.B.: The peer of a veth link is created along with it.
+            if result.is_ok() {
+                self.links.insert(link.name.clone(), link.clone());
+                current = self.socket.links()?;
+            }
+
+            results.push((Action::CreateLink(link.clone()), result));
+        }
+
+        let current = self.socket.links()?;
+
+        for link in links {
+            let info = match current.iter().find(|info| info.name == link.name) {
+                Some(info) => info,
+                None => continue,
+            };
+
+            if info.up == link.up && (link.mtu == 0 || info.mtu == link.mtu) {
+                continue;
+            }
+
+            let result = self.socket.set_link(info.index, link.up, link.mtu);
+
+            results.push((Action::ConfigureLink(link.clone()), result));
+        }
+
+        let assigned = self.socket.addresses()?;
+
+        for address in addresses {
+            let result = match address_info(address, &current) {
+                Ok(info) if assigned.contains(&info) => continue,
+                Ok(info) => self.socket.add_address(&info),
+                Err(err) => Err(err.into()),
+            };
+
+            if result.is_ok() && !self.addresses.contains(address) {
+                self.addresses.push(address.clone());
+            }
+
+            results.push((Action::AddAddress(address.clone()), result));
+        }
+
+        let kernel = self.socket.routes()?;
+
+        for route in routes {
+            let result = match route_info(route, &current) {
+                Ok(info) if installed(&kernel, &info) => continue,
+                Ok(info) => self.socket.add_route(&info),
+                Err(err) => Err(err.into()),
+            };
+
+            if result.is_ok() && !self.routes.contains(route) {
+                self.routes.push(route.clone());
+            }
+
+            results.push((Action::AddRoute(route.clone()), result));
+        }
+
+        Ok(results)
+    }
+
+    // Removes the routes, addresses and links created by previous calls that are no longer
+    // desired. Those that fail to be removed are retried by the next call.
+    fn remove(
+        &mut self,
+        links: &[Link],
+        addresses: &[Address],
+        routes: &[Route],
+        results: &mut Vec<(Action, io::Result<()>)>,
+    ) -> io::Result<()> {
+        let current = self.socket.links()?;
+        let kernel = self.socket.routes()?;
+
+        let mut kept = vec![];
+
+        for route in std::mem::take(&mut self.routes) {
+            if routes.contains(&route) {
+                kept.push(route);
+                continue;
+            }
+
+            // N.B.: A route whose link is gone is gone along with it.
+            let info = match route_info(&route, &current) {
+                Ok(info) if installed(&kernel, &info) => info,
+                _ => continue,
+            };
+
+            let result = self.socket.delete_route(&info);
+
+            if result.is_err() {
+                kept.push(route.clone());
+            }
+
+            results.push((Action::DeleteRoute(route), result));
+        }
+
+        self.routes = kept;
+
+        let assigned = self.socket.addresses()?;
+
+        let mut kept = vec![];
+
+        for address in std::mem::take(&mut self.addresses) {
+            if addresses.contains(&address) {
+                kept.push(address);
+                continue;
+            }
+
+            let info = match address_info(&address, &current) {
+                Ok(info) if assigned.contains(&info) => info,
+                _ => continue,
+            };
+
+            let result = self.socket.delete_address(&info);
+
+            if result.is_err() {
+                kept.push(address.clone());
+            }
+
+            results.push((Action::DeleteAddress(address), result));
+        }
+
+        self.addresses = kept;
+
+        let names: Vec<String> = self.links.keys().cloned().collect();
+
+        for name in names {
+            if links.iter().any(|link| link.name == name) {
+                continue;
+            }
+
+            let result = match index(&current, &name) {
+                Ok(index) => self.socket.delete_link(index),
+                // N.B.: The peer of a veth link is deleted along with it.
+                Err(_) => {
+                    self.links.remove(&name);
+                    continue;
+                }
+            };
+
+            if result.is_ok() {
+                self.links.remove(&name);
+            }
+
+            results.push((Action::DeleteLink(name), result));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+use crate::unix::process::environment::Environment;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::unistd::{close, dup2};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::TcpListener;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+
+/// The file descriptor of the first passed socket. The sockets follow in the order in which they
+/// are defined.
+pub const LISTEN_FDS_START: RawFd = 3;
+
+/// The number of passed sockets.
+pub const LISTEN_FDS: &str = "LISTEN_FDS";
+/// The colon separated names of the passed sockets.
+pub const LISTEN_FDNAMES: &str = "LISTEN_FDNAMES";
+/// The pid of the process the sockets are meant for.
+pub const LISTEN_PID: &str = "LISTEN_PID";
+
+/// Socket describes a listening socket that the engine binds on behalf of a service, and passes
+/// to it when it is started.
+///
+/// Exactly one of `unix` and `tcp` must be set.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Socket {
+    /// The name of the socket in `LISTEN_FDNAMES`. Defaults to the name of the service.
+    pub name: Option<String>,
+    /// The path of a Unix domain socket.
+    pub unix: Option<String>,
+    /// The address of a TCP socket (e.g. `127.0.0.1:50000`).
+    pub tcp: Option<String>,
+}
+
+enum Listener {
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+impl AsRawFd for Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Listener::Unix(listener) => listener.as_raw_fd(),
+            Listener::Tcp(listener) => listener.as_raw_fd(),
+        }
+    }
+}
+
+/// Listeners are the bound sockets of a service. They are bound once and outlive restarts of the
+/// service, so that no connection is refused while it is down.
+pub struct Listeners {
+    listeners: Vec<Listener>,
+    names: Vec<String>,
+}
+
+impl Listeners {
+    /// Binds `sockets`. Sockets without a name are named `default`.
+    pub fn bind(sockets: &[Socket], default: &str) -> io::Result<Listeners> {
+        let mut listeners = vec![];
+        let mut names = vec![];
+
+        for socket in sockets {
+            let listener = match (&socket.unix, &socket.tcp) {
+                (Some(path), None) => {
+                    // N.B.: A stale socket from a previous run would make `bind` fail.
+                    if Path::new(path).exists() {
+                        std::fs::remove_file(path)?;
+                    }
+
+                    Listener::Unix(UnixListener::bind(path)?)
+                }
+                (None, Some(address)) => Listener::Tcp(TcpListener::bind(address)?),
+                _ => {
+                    return Err(invalid(
+                        "exactly one of unix and tcp must be set".to_owned(),
+                    ))
+                }
+            };
+
+            let name = socket.name.as_deref().unwrap_or(default);
+
+            if name.is_empty() || name.contains(':') {
+                return Err(invalid(format!("invalid socket name: {:?}", name)));
+            }
+
+            listeners.push(listener);
+            names.push(name.to_owned());
+        }
+
+        Ok(Listeners { listeners, names })
+    }
+
+    pub fn len(&self) -> usize {
+        self.listeners.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    /// Blocks until a connection is pending on any of the sockets. From async code, it must be
+    /// run on a blocking thread (e.g. with `tokio::task::spawn_blocking`).
+    ///
+    /// https://man7.org/linux/man-pages/man2/poll.2.html.
+    pub fn wait(&self) -> io::Result<()> {
+        let mut fds: Vec<PollFd> = self
+            .listeners
+            .iter()
+            .map(|listener| PollFd::new(listener.as_raw_fd(), PollFlags::POLLIN))
+            .collect();
+
+        loop {
+            match poll(&mut fds, -1) {
+                Ok(_) => return Ok(()),
+                Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => continue,
+                Err(err) => return Err(errno(err)),
+            }
+        }
+    }
+
+    /// Sets the variables that describe the passed sockets.
+    pub fn export(&self, environment: &mut Environment) {
+        if self.is_empty() {
+            return;
+        }
+
+        environment.set(LISTEN_FDS, self.listeners.len().to_string());
+        environment.set(LISTEN_FDNAMES, self.names.join(":"));
+        environment.set_pid(LISTEN_PID);
+    }
+
+    /// Duplicates the sockets above the range they are passed in, so that moving them into place
+    /// in the child cannot overwrite one another.
+    pub fn prepare(&self) -> io::Result<Prepared> {
+        let mut prepared = Prepared { fds: vec![] };
+
+        let lowest = LISTEN_FDS_START + self.listeners.len() as RawFd;
+
+        for listener in &self.listeners {
+            let fd =
+                fcntl(listener.as_raw_fd(), FcntlArg::F_DUPFD_CLOEXEC(lowest)).map_err(errno)?;
+
+            prepared.fds.push(fd);
+        }
+
+        Ok(prepared)
+    }
+}
+
+/// Prepared are the duplicated sockets of a service.
+pub struct Prepared {
+    fds: Vec<RawFd>,
+}
+
+impl Prepared {
+    /// Moves the sockets into place, starting at `LISTEN_FDS_START`.
+    ///
+    /// N.B.: This is called between `fork` and `exec`, and must not allocate. `dup2` clears
+    /// `FD_CLOEXEC` on the new descriptors, so they are inherited by the service.
+    pub fn apply(&self) -> io::Result<()> {
+        for (index, fd) in self.fds.iter().enumerate() {
+            dup2(*fd, LISTEN_FDS_START + index as RawFd).map_err(errno)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Prepared {
+    fn drop(&mut self) {
+        for fd in &self.fds {
+            let _ = close(*fd);
+        }
+    }
+}
+
+/// Passed is a socket passed to the calling process by the engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Passed {
+    pub name: String,
+    pub fd: RawFd,
+}
+
+/// Returns the sockets passed to the calling process, and removes the variables that describe
+/// them so that they are not inherited by its children. The sockets are marked `FD_CLOEXEC`.
+///
+/// A service is not required to use the sockets, or to accept connections on them before it
+/// has started.
+pub fn listeners() -> io::Result<Vec<Passed>> {
+    let pid = std::env::var(LISTEN_PID).ok();
+    let fds = std::env::var(LISTEN_FDS).ok();
+    let names = std::env::var(LISTEN_FDNAMES).ok();
+
+    std::env::remove_var(LISTEN_PID);
+    std::env::remove_var(LISTEN_FDS);
+    std::env::remove_var(LISTEN_FDNAMES);
+
+    match pid.map(|pid| pid.parse::<u32>()) {
+        Some(Ok(pid)) if pid == std::process::id() => (),
+        _ => return Ok(vec![]),
+    }
+
+    let count: RawFd = match fds.map(|fds| fds.parse()) {
+        Some(Ok(count)) => count,
+        _ => return Err(invalid(format!("invalid {}", LISTEN_FDS))),
+    };
+
+    let names: Vec<String> = names
+        .map(|names| names.split(':').map(String::from).collect())
+        .unwrap_or_default();
+
+    let mut passed = vec![];
+
+    for index in 0..count {
+        let fd = LISTEN_FDS_START + index;
+
+        fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).map_err(errno)?;
+
+        passed.push(Passed {
+            name: names
+                .get(index as usize)
+                .cloned()
+                .unwrap_or_else(|| "unknown".to_owned()),
+            fd,
+        });
+    }
+
+    Ok(passed)
+}
+
+fn errno(err: nix::Error) -> io::Error {
+    match err {
+        nix::Error::Sys(errno) => io::Error::from_raw_os_error(errno as i32),
+        err => io::Error::new(io::ErrorKind::Other, err),
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
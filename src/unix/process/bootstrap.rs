@@ -0,0 +1,92 @@
+use crate::bootstrap::{
+    Bootstrap, BOOTSTRAP_FD, ENGINE_SOCKET, RUNTIME_ADDRESS, RUNTIME_SOCKET, SERVICE,
+};
+use crate::unix::process::environment::Environment;
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::unistd::{close, dup2, pipe2};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+
+/// Returns a new random token.
+pub fn token() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Sets the variables that describe `bootstrap`, except for the token, and the variable that
+/// points the service to `fd`.
+pub fn export(bootstrap: &Bootstrap, fd: RawFd, environment: &mut Environment) {
+    environment.set(BOOTSTRAP_FD, fd.to_string());
+    environment.set(ENGINE_SOCKET, &bootstrap.engine_socket);
+    environment.set(RUNTIME_SOCKET, &bootstrap.runtime_socket);
+    environment.set(SERVICE, &bootstrap.service);
+
+    match &bootstrap.runtime_address {
+        Some(address) => environment.set(RUNTIME_ADDRESS, address),
+        None => environment.remove(RUNTIME_ADDRESS),
+    }
+}
+
+/// Writes `bootstrap` to a pipe whose read end is passed to the service as `fd`.
+///
+/// N.B.: The whole bootstrap is written before the service is started, so it must fit in the
+/// pipe's buffer (64 KiB by default).
+pub fn prepare(bootstrap: &Bootstrap, fd: RawFd) -> io::Result<Prepared> {
+    let json = bootstrap
+        .to_json()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let (read, write) = pipe2(OFlag::O_CLOEXEC).map_err(errno)?;
+
+    // N.B.: Dropping the write end signals EOF to the service.
+    let result = unsafe { File::from_raw_fd(write) }.write_all(json.as_bytes());
+
+    // Keep the read end out of the way of the descriptors that are moved into place in the
+    // child.
+    let moved = fcntl(read, FcntlArg::F_DUPFD_CLOEXEC(fd + 1));
+
+    let _ = close(read);
+
+    let read = moved.map_err(errno)?;
+
+    let prepared = Prepared { read, fd };
+
+    result?;
+
+    Ok(prepared)
+}
+
+/// Prepared is a bootstrap that is ready to be passed to a service.
+pub struct Prepared {
+    read: RawFd,
+    fd: RawFd,
+}
+
+impl Prepared {
+    /// Moves the read end of the pipe into place.
+    ///
+    /// N.B.: This is called between `fork` and `exec`, and must not allocate. `dup2` clears
+    /// `FD_CLOEXEC` on the new descriptor, so it is inherited by the service.
+    pub fn apply(&self) -> io::Result<()> {
+        dup2(self.read, self.fd).map_err(errno)?;
+
+        Ok(())
+    }
+}
+
+impl Drop for Prepared {
+    fn drop(&mut self) {
+        let _ = close(self.read);
+    }
+}
+
+fn errno(err: nix::Error) -> io::Error {
+    match err {
+        nix::Error::Sys(errno) => io::Error::from_raw_os_error(errno as i32),
+        err => io::Error::new(io::ErrorKind::Other, err),
+    }
+}
@@ -0,0 +1,353 @@
+use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The environment variable that overrides `consts::CGROUP_ROOT`. Since the kernel passes unknown
+/// `name=value` parameters to init as environment variables, this can be set on the kernel
+/// command line.
+pub static CGROUP_ROOT_ENV: &str = "COSI_CGROUP_ROOT";
+
+/// Returns the cgroup under which the services' cgroups are created.
+pub fn root() -> PathBuf {
+    match std::env::var(CGROUP_ROOT_ENV) {
+        Ok(root) if !root.is_empty() => PathBuf::from(root),
+        _ => PathBuf::from(crate::consts::CGROUP_ROOT),
+    }
+}
+
+/// Resources describes the cgroup v2 limits of a service. Limits that are unset are left at the
+/// kernel's defaults.
+///
+/// https://www.kernel.org/doc/html/latest/admin-guide/cgroup-v2.html.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Resources {
+    /// The memory usage hard limit in bytes (`memory.max`).
+    pub memory_max: Option<u64>,
+    /// The relative CPU weight in the range [1, 10000] (`cpu.weight`).
+    pub cpu_weight: Option<u64>,
+    /// The maximum number of processes (`pids.max`).
+    pub pids_max: Option<u64>,
+}
+
+impl Resources {
+    fn controllers(&self) -> Vec<&'static str> {
+        let mut controllers = vec![];
+
+        if self.cpu_weight.is_some() {
+            controllers.push("cpu");
+        }
+
+        if self.memory_max.is_some() {
+            controllers.push("memory");
+        }
+
+        if self.pids_max.is_some() {
+            controllers.push("pids");
+        }
+
+        controllers
+    }
+}
+
+/// Usage is the resource usage of a cgroup, read from its stat files. Values are `None` when the
+/// corresponding file does not exist (e.g. the controller is not enabled).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Usage {
+    /// `memory.current`.
+    pub memory_current: Option<u64>,
+    /// `memory.peak`.
+    pub memory_peak: Option<u64>,
+    /// `oom_kill` in `memory.events`.
+    pub oom_kills: Option<u64>,
+    /// `usage_usec` in `cpu.stat`.
+    pub cpu_usage: Option<Duration>,
+    /// `user_usec` in `cpu.stat`.
+    pub cpu_user: Option<Duration>,
+    /// `system_usec` in `cpu.stat`.
+    pub cpu_system: Option<Duration>,
+    /// `pids.current`.
+    pub pids_current: Option<u64>,
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counters = [
+            ("memory", self.memory_current),
+            ("memory_peak", self.memory_peak),
+            ("oom_kills", self.oom_kills),
+            ("pids", self.pids_current),
+        ];
+
+        let durations = [
+            ("cpu", self.cpu_usage),
+            ("cpu_user", self.cpu_user),
+            ("cpu_system", self.cpu_system),
+        ];
+
+        let mut fields = vec![];
+
+        for (name, value) in counters.iter() {
+            if let Some(value) = value {
+                fields.push(format!("{}={}", name, value));
+            }
+        }
+
+        for (name, value) in durations.iter() {
+            if let Some(value) = value {
+                fields.push(format!("{}={:?}", name, value));
+            }
+        }
+
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
+// Enables the `controllers` that are not enabled yet in the `cgroup.subtree_control` of `cgroup`.
+fn enable(cgroup: &Path, controllers: &[&str]) -> io::Result<()> {
+    let path = cgroup.join("cgroup.subtree_control");
+
+    let enabled = match fs::read_to_string(&path) {
+        Ok(enabled) => enabled,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+
+    let enable: Vec<_> = controllers
+        .iter()
+        .filter(|controller| !enabled.split_whitespace().any(|c| c == **controller))
+        .map(|controller| format!("+{}", controller))
+        .collect();
+
+    if enable.is_empty() {
+        return Ok(());
+    }
+
+    fs::write(path, enable.join(" "))
+}
+
+/// Cgroup is the cgroup v2 directory of a service.
+#[derive(Debug, Clone)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    pub fn new<P>(root: P, name: &str) -> Cgroup
+    where
+        P: AsRef<Path>,
+    {
+        Cgroup {
+            path: root.as_ref().join(name),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Creates the cgroup, delegates the controllers needed by `resources` to it, and applies the
+    /// limits.
+    pub fn create(&self, resources: &Resources) -> io::Result<()> {
+        // Refuse to create plain directories outside of a cgroup v2 hierarchy (e.g. when
+        // `/sys/fs/cgroup` is a cgroup v1 tmpfs).
+        let mut ancestor = self.path.as_path();
+
+        while !ancestor.exists() {
+            match ancestor.parent() {
+                Some(parent) => ancestor = parent,
+                None => break,
+            }
+        }
+
+        if !ancestor.join("cgroup.controllers").exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{:?} is not in a cgroup v2 hierarchy", self.path),
+            ));
+        }
+
+        fs::create_dir_all(&self.path)?;
+
+        let controllers = resources.controllers();
+
+        if !controllers.is_empty() {
+            // N.B.: A controller must be enabled in the `cgroup.subtree_control` of every ancestor,
+            // from the top of the hierarchy down, to be available in a cgroup.
+            let top = self
+                .path
+                .ancestors()
+                .filter(|ancestor| ancestor.join("cgroup.controllers").exists())
+                .last()
+                .unwrap_or(&self.path);
+
+            let mut ancestors: Vec<_> = self
+                .path
+                .ancestors()
+                .skip(1)
+                .take_while(|ancestor| ancestor.starts_with(top))
+                .collect();
+
+            ancestors.reverse();
+
+            for ancestor in ancestors {
+                enable(ancestor, &controllers)?;
+            }
+        }
+
+        self.apply(resources)
+    }
+
+    /// Writes the limits in `resources` to the cgroup.
+    pub fn apply(&self, resources: &Resources) -> io::Result<()> {
+        if let Some(memory_max) = resources.memory_max {
+            fs::write(self.path.join("memory.max"), memory_max.to_string())?;
+        }
+
+        if let Some(cpu_weight) = resources.cpu_weight {
+            fs::write(self.path.join("cpu.weight"), cpu_weight.to_string())?;
+        }
+
+        if let Some(pids_max) = resources.pids_max {
+            fs::write(self.path.join("pids.max"), pids_max.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves `pid` into the cgroup.
+    pub fn add(&self, pid: Pid) -> io::Result<()> {
+        fs::write(self.path.join("cgroup.procs"), pid.to_string())
+    }
+
+    /// Returns the processes in the cgroup.
+    pub fn procs(&self) -> io::Result<Vec<Pid>> {
+        let procs = fs::read_to_string(self.path.join("cgroup.procs"))?;
+
+        Ok(procs
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .map(Pid::from_raw)
+            .collect())
+    }
+
+    /// Opens the cgroup so that a child can move itself into it without allocating.
+    pub fn prepare(&self) -> io::Result<Prepared> {
+        let procs = OpenOptions::new()
+            .write(true)
+            .open(self.path.join("cgroup.procs"))?;
+
+        Ok(Prepared { procs })
+    }
+
+    pub fn usage(&self) -> io::Result<Usage> {
+        let cpu = self.keyed("cpu.stat")?;
+        let memory_events = self.keyed("memory.events")?;
+
+        Ok(Usage {
+            memory_current: self.single("memory.current")?,
+            memory_peak: self.single("memory.peak")?,
+            oom_kills: memory_events.and_then(|events| value(&events, "oom_kill")),
+            cpu_usage: cpu
+                .as_ref()
+                .and_then(|cpu| value(cpu, "usage_usec"))
+                .map(Duration::from_micros),
+            cpu_user: cpu
+                .as_ref()
+                .and_then(|cpu| value(cpu, "user_usec"))
+                .map(Duration::from_micros),
+            cpu_system: cpu
+                .as_ref()
+                .and_then(|cpu| value(cpu, "system_usec"))
+                .map(Duration::from_micros),
+            pids_current: self.single("pids.current")?,
+        })
+    }
+
+    fn read(&self, file: &str) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.path.join(file)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Reads a single value file (e.g. `memory.current`).
+    fn single(&self, file: &str) -> io::Result<Option<u64>> {
+        Ok(self
+            .read(file)?
+            .and_then(|contents| contents.trim().parse().ok()))
+    }
+
+    // Reads a flat keyed file (e.g. `cpu.stat`).
+    fn keyed(&self, file: &str) -> io::Result<Option<Vec<(String, u64)>>> {
+        Ok(self.read(file)?.map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| {
+                    let mut fields = line.split_whitespace();
+
+                    match (fields.next(), fields.next().map(str::parse)) {
+                        (Some(key), Some(Ok(value))) => Some((key.to_owned(), value)),
+                        _ => None,
+                    }
+                })
+                .collect()
+        }))
+    }
+}
+
+fn value(pairs: &[(String, u64)], key: &str) -> Option<u64> {
+    pairs.iter().find(|(k, _)| k == key).map(|(_, v)| *v)
+}
+
+/// Prepared is an open `cgroup.procs` file of a cgroup.
+pub struct Prepared {
+    procs: File,
+}
+
+impl Prepared {
+    /// Moves the calling process into the cgroup.
+    ///
+    /// N.B.: This is called between `fork` and `exec`, and must not allocate.
+    pub fn apply(&self) -> io::Result<()> {
+        let mut buffer = [0u8; 20];
+        let pid = format_pid(std::process::id(), &mut buffer);
+
+        let res = unsafe {
+            libc::write(
+                self.procs.as_raw_fd(),
+                pid.as_ptr() as *const libc::c_void,
+                pid.len(),
+            )
+        };
+
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats `pid` into `buffer` without allocating.
+pub(crate) fn format_pid(mut pid: u32, buffer: &mut [u8; 20]) -> &[u8] {
+    let mut i = buffer.len();
+
+    loop {
+        i -= 1;
+        buffer[i] = b'0' + (pid % 10) as u8;
+        pid /= 10;
+
+        if pid == 0 {
+            break;
+        }
+    }
+
+    &buffer[i..]
+}
@@ -0,0 +1,142 @@
+use crate::unix::process::cgroup::format_pid;
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+
+extern "C" {
+    static mut environ: *const *const libc::c_char;
+}
+
+/// Environment is the environment of a child process.
+///
+/// It is installed between `fork` and `exec` instead of through `Command`, so that variables that
+/// are only known in the child (i.e. `LISTEN_PID`) can be set as well.
+#[derive(Debug, Default, Clone)]
+pub struct Environment {
+    // A variable without a value is set to the pid of the child.
+    variables: BTreeMap<OsString, Option<OsString>>,
+}
+
+impl Environment {
+    /// Returns an environment with the engine's variables.
+    pub fn inherit() -> Environment {
+        Environment {
+            variables: std::env::vars_os()
+                .map(|(key, value)| (key, Some(value)))
+                .collect(),
+        }
+    }
+
+    pub fn set<K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<OsString>,
+        V: Into<OsString>,
+    {
+        self.variables.insert(key.into(), Some(value.into()));
+    }
+
+    /// Sets `key` to the pid of the child.
+    pub fn set_pid<K>(&mut self, key: K)
+    where
+        K: Into<OsString>,
+    {
+        self.variables.insert(key.into(), None);
+    }
+
+    pub fn remove<K>(&mut self, key: K)
+    where
+        K: AsRef<OsStr>,
+    {
+        self.variables.remove(key.as_ref());
+    }
+
+    /// Builds the environment so that it can be installed in the child without allocating.
+    pub fn prepare(&self) -> io::Result<Prepared> {
+        let mut entries = vec![];
+        let mut pids = vec![];
+
+        for (key, value) in &self.variables {
+            let key = key.as_bytes();
+
+            if key.is_empty() || key.contains(&b'=') || key.contains(&0) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid environment variable: {:?}", key),
+                ));
+            }
+
+            let mut entry = key.to_vec();
+            entry.push(b'=');
+
+            match value {
+                Some(value) => {
+                    if value.as_bytes().contains(&0) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("invalid value of environment variable: {:?}", key),
+                        ));
+                    }
+
+                    entry.extend_from_slice(value.as_bytes());
+                    entry.push(0);
+                }
+                None => {
+                    pids.push((entries.len(), entry.len()));
+
+                    // Reserve room for the largest pid and the terminating NUL.
+                    entry.extend_from_slice(&[0; 11]);
+                }
+            }
+
+            entries.push(entry);
+        }
+
+        let mut pointers: Vec<*const libc::c_char> = entries
+            .iter()
+            .map(|entry| entry.as_ptr() as *const libc::c_char)
+            .collect();
+
+        pointers.push(std::ptr::null());
+
+        Ok(Prepared {
+            entries,
+            pointers,
+            pids,
+        })
+    }
+}
+
+/// Prepared is a built `Environment`.
+pub struct Prepared {
+    entries: Vec<Vec<u8>>,
+    pointers: Vec<*const libc::c_char>,
+    // The entries that are set to the pid of the child, and where their value starts.
+    pids: Vec<(usize, usize)>,
+}
+
+// N.B.: `pointers` only point into `entries`, which are owned by `Prepared` and never resized.
+unsafe impl Send for Prepared {}
+unsafe impl Sync for Prepared {}
+
+impl Prepared {
+    /// Installs the environment in the calling process.
+    ///
+    /// N.B.: This is called between `fork` and `exec`, and must not allocate. `Command` must not
+    /// be given an environment of its own, or it will replace this one.
+    pub fn apply(&mut self) -> io::Result<()> {
+        let mut buffer = [0u8; 20];
+        let pid = format_pid(std::process::id(), &mut buffer);
+
+        for (index, start) in &self.pids {
+            let entry = &mut self.entries[*index];
+
+            entry[*start..*start + pid.len()].copy_from_slice(pid);
+            entry[*start + pid.len()] = 0;
+        }
+
+        unsafe { environ = self.pointers.as_ptr() };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+use crate::unix::process::environment::Environment;
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fmt;
+use std::io;
+use std::process::Command;
+
+/// Exec describes the environment a service is executed in.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Exec {
+    /// The environment variables to set.
+    pub environment: BTreeMap<String, String>,
+    /// Starts the service with only `environment` instead of the engine's environment.
+    pub clear_environment: bool,
+    /// The working directory. Defaults to the engine's working directory.
+    pub working_directory: Option<String>,
+    /// The file mode creation mask (e.g. `0o022`).
+    pub umask: Option<u32>,
+    /// The OOM score adjustment in the range [-1000, 1000].
+    pub oom_score_adjust: Option<i32>,
+    pub limits: Limits,
+}
+
+impl Exec {
+    /// Returns the environment of the service.
+    pub fn environment(&self) -> Environment {
+        let mut environment = if self.clear_environment {
+            Environment::default()
+        } else {
+            Environment::inherit()
+        };
+
+        for (key, value) in &self.environment {
+            environment.set(key, value);
+        }
+
+        environment
+    }
+
+    /// Applies the settings that `Command` supports natively.
+    pub fn configure(&self, command: &mut Command) {
+        if let Some(working_directory) = &self.working_directory {
+            command.current_dir(working_directory);
+        }
+    }
+
+    /// Resolves the remaining settings into a form that can be applied in the child without
+    /// allocating.
+    pub fn prepare(&self) -> io::Result<Prepared> {
+        let oom_score_adjust = match self.oom_score_adjust {
+            Some(adjust) if (-1000..=1000).contains(&adjust) => Some(adjust.to_string()),
+            Some(adjust) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("OOM score adjustment out of range: {}", adjust),
+                ))
+            }
+            None => None,
+        };
+
+        Ok(Prepared {
+            rlimits: self.limits.rlimits(),
+            umask: self.umask.map(|umask| umask as libc::mode_t),
+            oom_score_adjust,
+            oom_score_adj_path: CString::new("/proc/self/oom_score_adj").unwrap(),
+        })
+    }
+}
+
+/// Limits describes the resource limits of a service.
+///
+/// https://man7.org/linux/man-pages/man2/setrlimit.2.html.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// `RLIMIT_NOFILE`.
+    pub nofile: Option<Limit>,
+    /// `RLIMIT_NPROC`.
+    pub nproc: Option<Limit>,
+    /// `RLIMIT_CORE`.
+    pub core: Option<Limit>,
+    /// `RLIMIT_MEMLOCK`. Loading eBPF programs requires this to be raised (e.g. `unlimited`).
+    pub memlock: Option<Limit>,
+}
+
+impl Limits {
+    fn rlimits(&self) -> Vec<(Resource, libc::rlimit)> {
+        let limits = [
+            (libc::RLIMIT_NOFILE, &self.nofile),
+            (libc::RLIMIT_NPROC, &self.nproc),
+            (libc::RLIMIT_CORE, &self.core),
+            (libc::RLIMIT_MEMLOCK, &self.memlock),
+        ];
+
+        limits
+            .iter()
+            .filter_map(|(resource, limit)| {
+                limit.map(|limit| {
+                    (
+                        *resource,
+                        libc::rlimit {
+                            rlim_cur: limit.soft().into(),
+                            rlim_max: limit.hard().into(),
+                        },
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_env = "gnu")]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type Resource = libc::c_int;
+
+/// Limit is a resource limit. It is either a single value used as both the soft and the hard
+/// limit, or a `soft` and `hard` pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Limit {
+    Value(Value),
+    Range { soft: Value, hard: Value },
+}
+
+impl Limit {
+    pub fn soft(&self) -> Value {
+        match self {
+            Limit::Value(value) => *value,
+            Limit::Range { soft, .. } => *soft,
+        }
+    }
+
+    pub fn hard(&self) -> Value {
+        match self {
+            Limit::Value(value) => *value,
+            Limit::Range { hard, .. } => *hard,
+        }
+    }
+}
+
+/// Value is a resource limit value, either a number or `unlimited`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Limited(u64),
+    Unlimited,
+}
+
+impl From<Value> for libc::rlim_t {
+    fn from(value: Value) -> libc::rlim_t {
+        match value {
+            Value::Limited(value) => value as libc::rlim_t,
+            Value::Unlimited => libc::RLIM_INFINITY,
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Limited(value) => serializer.serialize_u64(*value),
+            Value::Unlimited => serializer.serialize_str("unlimited"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a non-negative integer or \"unlimited\"")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Value::Limited(value))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Value, E>
+            where
+                E: de::Error,
+            {
+                if value < 0 {
+                    return Err(E::invalid_value(de::Unexpected::Signed(value), &self));
+                }
+
+                Ok(Value::Limited(value as u64))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Value, E>
+            where
+                E: de::Error,
+            {
+                match value {
+                    "unlimited" | "infinity" => Ok(Value::Unlimited),
+                    _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// Prepared is a resolved `Exec`.
+pub struct Prepared {
+    rlimits: Vec<(Resource, libc::rlimit)>,
+    umask: Option<libc::mode_t>,
+    oom_score_adjust: Option<String>,
+    oom_score_adj_path: CString,
+}
+
+impl Prepared {
+    /// Applies the settings to the calling process.
+    ///
+    /// N.B.: This is called between `fork` and `exec`, and must not allocate.
+    pub fn apply(&self) -> io::Result<()> {
+        for (resource, rlimit) in &self.rlimits {
+            if unsafe { libc::setrlimit(*resource, rlimit) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        if let Some(umask) = self.umask {
+            unsafe { libc::umask(umask) };
+        }
+
+        if let Some(adjust) = &self.oom_score_adjust {
+            let fd = unsafe {
+                libc::open(
+                    self.oom_score_adj_path.as_ptr(),
+                    libc::O_WRONLY | libc::O_CLOEXEC,
+                )
+            };
+
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let res =
+                unsafe { libc::write(fd, adjust.as_ptr() as *const libc::c_void, adjust.len()) };
+
+            let err = io::Error::last_os_error();
+
+            unsafe { libc::close(fd) };
+
+            if res < 0 {
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+}
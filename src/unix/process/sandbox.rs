@@ -0,0 +1,652 @@
+use nix::mount::{mount, MsFlags};
+use nix::sched::{unshare, CloneFlags};
+use nix::unistd::{chown, setgid, setgroups, setuid, Gid, Uid};
+use serde::{Deserialize, Serialize};
+use std::ffi::{CStr, CString};
+use std::{fs, io};
+
+/// Sandbox describes the security settings that are applied to a service between `fork` and
+/// `exec`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sandbox {
+    /// The user ID to run as.
+    pub uid: Option<u32>,
+    /// The group ID to run as.
+    pub gid: Option<u32>,
+    /// The supplementary groups. When `uid` or `gid` is set, the engine's supplementary groups
+    /// are always replaced by this list.
+    pub groups: Vec<u32>,
+    /// The capabilities to keep in the bounding set (e.g. `CAP_NET_ADMIN`). All other
+    /// capabilities are dropped. If unset, the bounding set is inherited from the engine.
+    pub capabilities: Option<Vec<String>>,
+    /// Sets `no_new_privs`. This is implied by `seccomp`.
+    pub no_new_privs: bool,
+    /// Runs the service in a private mount namespace.
+    pub private_mounts: bool,
+    /// Makes `/` and every mount below it read-only (Linux 5.12 and later). This implies
+    /// `private_mounts`.
+    pub read_only_root: bool,
+    /// The paths, and the mounts below them, that remain writable when `read_only_root` is set.
+    pub writable_paths: Vec<String>,
+    /// The directories to create for the service, owned by `uid` and `gid`, e.g. under `/run`.
+    /// They remain writable when `read_only_root` is set.
+    pub directories: Vec<String>,
+    /// The system calls the service is allowed to make. All others fail with `EPERM`. If unset,
+    /// no filter is installed.
+    pub seccomp: Option<Vec<String>>,
+}
+
+impl Sandbox {
+    /// Creates the directories of the sandbox, and resolves it into a form that can be applied
+    /// in the child without allocating.
+    pub fn prepare(&self) -> io::Result<Prepared> {
+        for directory in &self.directories {
+            fs::create_dir_all(directory)?;
+
+            chown(
+                directory.as_str(),
+                self.uid.map(Uid::from_raw),
+                self.gid.map(Gid::from_raw),
+            )
+            .map_err(errno)?;
+        }
+
+        let dropped = match &self.capabilities {
+            Some(names) => {
+                let keep = names
+                    .iter()
+                    .map(|name| capability(name))
+                    .collect::<io::Result<Vec<_>>>()?;
+
+                (0..=last_capability())
+                    .filter(|cap| !keep.contains(cap))
+                    .collect()
+            }
+            None => vec![],
+        };
+
+        let writable_paths = self
+            .writable_paths
+            .iter()
+            .chain(&self.directories)
+            .map(|path| CString::new(path.as_str()).map_err(invalid))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let filter = match &self.seccomp {
+            Some(names) => Some(filter(names)?),
+            None => None,
+        };
+
+        Ok(Prepared {
+            uid: self.uid.map(Uid::from_raw),
+            gid: self.gid.map(Gid::from_raw),
+            groups: self.groups.iter().map(|gid| Gid::from_raw(*gid)).collect(),
+            drop: dropped,
+            no_new_privs: self.no_new_privs || self.seccomp.is_some(),
+            private_mounts: self.private_mounts || self.read_only_root,
+            read_only_root: self.read_only_root,
+            writable_paths,
+            filter,
+        })
+    }
+}
+
+/// Prepared is a resolved `Sandbox`.
+pub struct Prepared {
+    uid: Option<Uid>,
+    gid: Option<Gid>,
+    groups: Vec<Gid>,
+    drop: Vec<libc::c_ulong>,
+    no_new_privs: bool,
+    private_mounts: bool,
+    read_only_root: bool,
+    writable_paths: Vec<CString>,
+    filter: Option<Vec<SockFilter>>,
+}
+
+impl Prepared {
+    /// Applies the sandbox to the calling process.
+    ///
+    /// N.B.: This is called between `fork` and `exec`, and must not allocate.
+    pub fn apply(&self) -> io::Result<()> {
+        if self.private_mounts {
+            unshare(CloneFlags::CLONE_NEWNS).map_err(errno)?;
+
+            // Keep mount events from propagating back to the engine's namespace.
+            mount(
+                None::<&str>,
+                "/",
+                None::<&str>,
+                MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+                None::<&str>,
+            )
+            .map_err(errno)?;
+        }
+
+        if self.read_only_root {
+            let root = CStr::from_bytes_with_nul(b"/\0").map_err(invalid)?;
+
+            // N.B.: Remounting `/` alone would leave the mounts below it, e.g. `/proc` and `/run`,
+            // writable.
+            mount_setattr(root, MOUNT_ATTR_RDONLY, 0)?;
+
+            // Bind mounting a path onto itself gives it mounts of its own, which are read-only
+            // like the ones they are copied from until made writable again.
+            for path in &self.writable_paths {
+                mount(
+                    Some(path.as_c_str()),
+                    path.as_c_str(),
+                    None::<&str>,
+                    MsFlags::MS_BIND | MsFlags::MS_REC,
+                    None::<&str>,
+                )
+                .map_err(errno)?;
+
+                mount_setattr(path, 0, MOUNT_ATTR_RDONLY)?;
+            }
+        }
+
+        for cap in &self.drop {
+            prctl(libc::PR_CAPBSET_DROP, *cap)?;
+        }
+
+        if self.no_new_privs {
+            prctl(libc::PR_SET_NO_NEW_PRIVS, 1)?;
+        }
+
+        if self.uid.is_some() || self.gid.is_some() || !self.groups.is_empty() {
+            setgroups(&self.groups).map_err(errno)?;
+        }
+
+        if let Some(gid) = self.gid {
+            setgid(gid).map_err(errno)?;
+        }
+
+        if let Some(uid) = self.uid {
+            setuid(uid).map_err(errno)?;
+        }
+
+        // N.B.: The filter is installed last so that the calls above are not subject to it.
+        if let Some(filter) = &self.filter {
+            let program = SockFprog {
+                len: filter.len() as libc::c_ushort,
+                filter: filter.as_ptr(),
+            };
+
+            let res = unsafe {
+                libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &program as *const SockFprog,
+                )
+            };
+
+            if res != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn prctl(option: libc::c_int, arg: libc::c_ulong) -> io::Result<()> {
+    match unsafe { libc::prctl(option, arg, 0, 0, 0) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn errno(err: nix::Error) -> io::Error {
+    match err {
+        nix::Error::Sys(errno) => io::Error::from_raw_os_error(errno as i32),
+        _ => io::Error::from_raw_os_error(libc::EINVAL),
+    }
+}
+
+fn invalid<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidInput, err)
+}
+
+// https://man7.org/linux/man-pages/man7/capabilities.7.html
+static CAPABILITIES: &[&str] = &[
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETPCAP",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN",
+    "CAP_NET_RAW",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_MKNOD",
+    "CAP_LEASE",
+    "CAP_AUDIT_WRITE",
+    "CAP_AUDIT_CONTROL",
+    "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MAC_ADMIN",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_AUDIT_READ",
+    "CAP_PERFMON",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+fn capability(name: &str) -> io::Result<libc::c_ulong> {
+    let name = name.to_uppercase();
+    let name = if name.starts_with("CAP_") {
+        name
+    } else {
+        format!("CAP_{}", name)
+    };
+
+    match CAPABILITIES.iter().position(|cap| *cap == name) {
+        Some(cap) => Ok(cap as libc::c_ulong),
+        None => Err(invalid(format!("unknown capability: {}", name))),
+    }
+}
+
+fn last_capability() -> libc::c_ulong {
+    match fs::read_to_string("/proc/sys/kernel/cap_last_cap") {
+        Ok(last) => match last.trim().parse() {
+            Ok(last) => last,
+            Err(_) => (CAPABILITIES.len() - 1) as libc::c_ulong,
+        },
+        Err(_) => (CAPABILITIES.len() - 1) as libc::c_ulong,
+    }
+}
+
+// https://man7.org/linux/man-pages/man2/mount_setattr.2.html
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
+// N.B.: The system call is newer than the `libc` crate, and has the same number on all
+// architectures.
+const SYS_MOUNT_SETATTR: libc::c_long = 442;
+const AT_RECURSIVE: libc::c_uint = 0x8000;
+const MOUNT_ATTR_RDONLY: u64 = 0x1;
+
+// Sets the `set` attributes, and clears the `clear` ones, of the mount at `path` and of every
+// mount below it.
+fn mount_setattr(path: &CStr, set: u64, clear: u64) -> io::Result<()> {
+    let attr = MountAttr {
+        attr_set: set,
+        attr_clr: clear,
+        propagation: 0,
+        userns_fd: 0,
+    };
+
+    let res = unsafe {
+        libc::syscall(
+            SYS_MOUNT_SETATTR,
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            AT_RECURSIVE,
+            &attr as *const MountAttr,
+            std::mem::size_of::<MountAttr>(),
+        )
+    };
+
+    match res {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+// https://www.kernel.org/doc/html/latest/userspace-api/seccomp_filter.html
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct SockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+#[repr(C)]
+struct SockFprog {
+    len: libc::c_ushort,
+    filter: *const SockFilter,
+}
+
+const BPF_LD: u16 = 0x00;
+const BPF_W: u16 = 0x00;
+const BPF_ABS: u16 = 0x20;
+const BPF_JMP: u16 = 0x05;
+const BPF_JEQ: u16 = 0x10;
+const BPF_K: u16 = 0x00;
+const BPF_RET: u16 = 0x06;
+
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+// The offsets of `nr` and `arch` in `struct seccomp_data`.
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+fn statement(code: u16, k: u32) -> SockFilter {
+    SockFilter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+    SockFilter { code, jt, jf, k }
+}
+
+/// Builds a filter that allows the named system calls and fails all others with `EPERM`.
+/// `execve` is always allowed so that the service can be started.
+fn filter(names: &[String]) -> io::Result<Vec<SockFilter>> {
+    let mut syscalls = vec![syscall("execve")?];
+
+    for name in names {
+        let nr = syscall(name)?;
+
+        if !syscalls.contains(&nr) {
+            syscalls.push(nr);
+        }
+    }
+
+    let mut filter = vec![
+        statement(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARCH),
+        jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+        statement(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+        statement(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_NR),
+    ];
+
+    for nr in syscalls {
+        filter.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1));
+        filter.push(statement(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+    }
+
+    filter.push(statement(
+        BPF_RET | BPF_K,
+        SECCOMP_RET_ERRNO | (libc::EPERM as u32),
+    ));
+
+    Ok(filter)
+}
+
+fn syscall(name: &str) -> io::Result<libc::c_long> {
+    SYSCALLS
+        .iter()
+        .chain(ARCH_SYSCALLS.iter())
+        .find(|(syscall, _)| *syscall == name)
+        .map(|(_, nr)| *nr)
+        .ok_or_else(|| invalid(format!("unknown system call: {}", name)))
+}
+
+static SYSCALLS: &[(&str, libc::c_long)] = &[
+    ("accept", libc::SYS_accept),
+    ("accept4", libc::SYS_accept4),
+    ("adjtimex", libc::SYS_adjtimex),
+    ("bind", libc::SYS_bind),
+    ("bpf", libc::SYS_bpf),
+    ("brk", libc::SYS_brk),
+    ("capget", libc::SYS_capget),
+    ("capset", libc::SYS_capset),
+    ("chdir", libc::SYS_chdir),
+    ("chroot", libc::SYS_chroot),
+    ("clock_adjtime", libc::SYS_clock_adjtime),
+    ("clock_getres", libc::SYS_clock_getres),
+    ("clock_gettime", libc::SYS_clock_gettime),
+    ("clock_nanosleep", libc::SYS_clock_nanosleep),
+    ("clock_settime", libc::SYS_clock_settime),
+    ("clone", libc::SYS_clone),
+    ("close", libc::SYS_close),
+    ("connect", libc::SYS_connect),
+    ("copy_file_range", libc::SYS_copy_file_range),
+    ("delete_module", libc::SYS_delete_module),
+    ("dup", libc::SYS_dup),
+    ("dup3", libc::SYS_dup3),
+    ("epoll_create1", libc::SYS_epoll_create1),
+    ("epoll_ctl", libc::SYS_epoll_ctl),
+    ("epoll_pwait", libc::SYS_epoll_pwait),
+    ("eventfd2", libc::SYS_eventfd2),
+    ("execve", libc::SYS_execve),
+    ("exit", libc::SYS_exit),
+    ("exit_group", libc::SYS_exit_group),
+    ("faccessat", libc::SYS_faccessat),
+    ("fallocate", libc::SYS_fallocate),
+    ("fchdir", libc::SYS_fchdir),
+    ("fchmod", libc::SYS_fchmod),
+    ("fchmodat", libc::SYS_fchmodat),
+    ("fchown", libc::SYS_fchown),
+    ("fchownat", libc::SYS_fchownat),
+    ("fcntl", libc::SYS_fcntl),
+    ("fdatasync", libc::SYS_fdatasync),
+    ("fgetxattr", libc::SYS_fgetxattr),
+    ("finit_module", libc::SYS_finit_module),
+    ("flistxattr", libc::SYS_flistxattr),
+    ("flock", libc::SYS_flock),
+    ("fremovexattr", libc::SYS_fremovexattr),
+    ("fsetxattr", libc::SYS_fsetxattr),
+    ("fstat", libc::SYS_fstat),
+    ("fstatfs", libc::SYS_fstatfs),
+    ("fsync", libc::SYS_fsync),
+    ("ftruncate", libc::SYS_ftruncate),
+    ("futex", libc::SYS_futex),
+    ("get_robust_list", libc::SYS_get_robust_list),
+    ("getcwd", libc::SYS_getcwd),
+    ("getdents64", libc::SYS_getdents64),
+    ("getegid", libc::SYS_getegid),
+    ("geteuid", libc::SYS_geteuid),
+    ("getgid", libc::SYS_getgid),
+    ("getgroups", libc::SYS_getgroups),
+    ("getpeername", libc::SYS_getpeername),
+    ("getpgid", libc::SYS_getpgid),
+    ("getpid", libc::SYS_getpid),
+    ("getppid", libc::SYS_getppid),
+    ("getpriority", libc::SYS_getpriority),
+    ("getrandom", libc::SYS_getrandom),
+    ("getresgid", libc::SYS_getresgid),
+    ("getresuid", libc::SYS_getresuid),
+    ("getrusage", libc::SYS_getrusage),
+    ("getsid", libc::SYS_getsid),
+    ("getsockname", libc::SYS_getsockname),
+    ("getsockopt", libc::SYS_getsockopt),
+    ("gettid", libc::SYS_gettid),
+    ("gettimeofday", libc::SYS_gettimeofday),
+    ("getuid", libc::SYS_getuid),
+    ("getxattr", libc::SYS_getxattr),
+    ("init_module", libc::SYS_init_module),
+    ("inotify_add_watch", libc::SYS_inotify_add_watch),
+    ("inotify_init1", libc::SYS_inotify_init1),
+    ("inotify_rm_watch", libc::SYS_inotify_rm_watch),
+    ("ioctl", libc::SYS_ioctl),
+    ("kill", libc::SYS_kill),
+    ("lgetxattr", libc::SYS_lgetxattr),
+    ("linkat", libc::SYS_linkat),
+    ("listen", libc::SYS_listen),
+    ("listxattr", libc::SYS_listxattr),
+    ("lseek", libc::SYS_lseek),
+    ("lsetxattr", libc::SYS_lsetxattr),
+    ("madvise", libc::SYS_madvise),
+    ("membarrier", libc::SYS_membarrier),
+    ("memfd_create", libc::SYS_memfd_create),
+    ("mincore", libc::SYS_mincore),
+    ("mkdirat", libc::SYS_mkdirat),
+    ("mknodat", libc::SYS_mknodat),
+    ("mlock", libc::SYS_mlock),
+    ("mlockall", libc::SYS_mlockall),
+    ("mmap", libc::SYS_mmap),
+    ("mount", libc::SYS_mount),
+    ("mprotect", libc::SYS_mprotect),
+    ("mremap", libc::SYS_mremap),
+    ("msync", libc::SYS_msync),
+    ("munlock", libc::SYS_munlock),
+    ("munlockall", libc::SYS_munlockall),
+    ("munmap", libc::SYS_munmap),
+    ("nanosleep", libc::SYS_nanosleep),
+    ("newfstatat", libc::SYS_newfstatat),
+    ("openat", libc::SYS_openat),
+    ("perf_event_open", libc::SYS_perf_event_open),
+    ("personality", libc::SYS_personality),
+    ("pipe2", libc::SYS_pipe2),
+    ("pivot_root", libc::SYS_pivot_root),
+    ("ppoll", libc::SYS_ppoll),
+    ("prctl", libc::SYS_prctl),
+    ("pread64", libc::SYS_pread64),
+    ("prlimit64", libc::SYS_prlimit64),
+    ("pselect6", libc::SYS_pselect6),
+    ("pwrite64", libc::SYS_pwrite64),
+    ("read", libc::SYS_read),
+    ("readlinkat", libc::SYS_readlinkat),
+    ("readv", libc::SYS_readv),
+    ("reboot", libc::SYS_reboot),
+    ("recvfrom", libc::SYS_recvfrom),
+    ("recvmsg", libc::SYS_recvmsg),
+    ("removexattr", libc::SYS_removexattr),
+    ("renameat2", libc::SYS_renameat2),
+    ("rt_sigaction", libc::SYS_rt_sigaction),
+    ("rt_sigprocmask", libc::SYS_rt_sigprocmask),
+    ("rt_sigreturn", libc::SYS_rt_sigreturn),
+    ("rt_sigsuspend", libc::SYS_rt_sigsuspend),
+    ("rt_sigtimedwait", libc::SYS_rt_sigtimedwait),
+    ("sched_get_priority_max", libc::SYS_sched_get_priority_max),
+    ("sched_get_priority_min", libc::SYS_sched_get_priority_min),
+    ("sched_getaffinity", libc::SYS_sched_getaffinity),
+    ("sched_getparam", libc::SYS_sched_getparam),
+    ("sched_getscheduler", libc::SYS_sched_getscheduler),
+    ("sched_setaffinity", libc::SYS_sched_setaffinity),
+    ("sched_setparam", libc::SYS_sched_setparam),
+    ("sched_setscheduler", libc::SYS_sched_setscheduler),
+    ("sched_yield", libc::SYS_sched_yield),
+    ("seccomp", libc::SYS_seccomp),
+    ("sendmsg", libc::SYS_sendmsg),
+    ("sendto", libc::SYS_sendto),
+    ("set_robust_list", libc::SYS_set_robust_list),
+    ("set_tid_address", libc::SYS_set_tid_address),
+    ("setdomainname", libc::SYS_setdomainname),
+    ("setgid", libc::SYS_setgid),
+    ("setgroups", libc::SYS_setgroups),
+    ("sethostname", libc::SYS_sethostname),
+    ("setns", libc::SYS_setns),
+    ("setpgid", libc::SYS_setpgid),
+    ("setpriority", libc::SYS_setpriority),
+    ("setregid", libc::SYS_setregid),
+    ("setresgid", libc::SYS_setresgid),
+    ("setresuid", libc::SYS_setresuid),
+    ("setreuid", libc::SYS_setreuid),
+    ("setsid", libc::SYS_setsid),
+    ("setsockopt", libc::SYS_setsockopt),
+    ("settimeofday", libc::SYS_settimeofday),
+    ("setuid", libc::SYS_setuid),
+    ("setxattr", libc::SYS_setxattr),
+    ("shutdown", libc::SYS_shutdown),
+    ("sigaltstack", libc::SYS_sigaltstack),
+    ("signalfd4", libc::SYS_signalfd4),
+    ("socket", libc::SYS_socket),
+    ("socketpair", libc::SYS_socketpair),
+    ("splice", libc::SYS_splice),
+    ("statfs", libc::SYS_statfs),
+    ("statx", libc::SYS_statx),
+    ("symlinkat", libc::SYS_symlinkat),
+    ("sync", libc::SYS_sync),
+    ("syncfs", libc::SYS_syncfs),
+    ("sysinfo", libc::SYS_sysinfo),
+    ("tee", libc::SYS_tee),
+    ("tgkill", libc::SYS_tgkill),
+    ("timerfd_create", libc::SYS_timerfd_create),
+    ("timerfd_gettime", libc::SYS_timerfd_gettime),
+    ("timerfd_settime", libc::SYS_timerfd_settime),
+    ("tkill", libc::SYS_tkill),
+    ("truncate", libc::SYS_truncate),
+    ("umask", libc::SYS_umask),
+    ("umount2", libc::SYS_umount2),
+    ("uname", libc::SYS_uname),
+    ("unlinkat", libc::SYS_unlinkat),
+    ("unshare", libc::SYS_unshare),
+    ("utimensat", libc::SYS_utimensat),
+    ("wait4", libc::SYS_wait4),
+    ("waitid", libc::SYS_waitid),
+    ("write", libc::SYS_write),
+    ("writev", libc::SYS_writev),
+];
+
+#[cfg(target_arch = "x86_64")]
+static ARCH_SYSCALLS: &[(&str, libc::c_long)] = &[
+    ("access", libc::SYS_access),
+    ("alarm", libc::SYS_alarm),
+    ("arch_prctl", libc::SYS_arch_prctl),
+    ("chmod", libc::SYS_chmod),
+    ("chown", libc::SYS_chown),
+    ("creat", libc::SYS_creat),
+    ("dup2", libc::SYS_dup2),
+    ("epoll_create", libc::SYS_epoll_create),
+    ("epoll_wait", libc::SYS_epoll_wait),
+    ("eventfd", libc::SYS_eventfd),
+    ("fadvise64", libc::SYS_fadvise64),
+    ("fork", libc::SYS_fork),
+    ("futimesat", libc::SYS_futimesat),
+    ("getdents", libc::SYS_getdents),
+    ("getpgrp", libc::SYS_getpgrp),
+    ("getrlimit", libc::SYS_getrlimit),
+    ("inotify_init", libc::SYS_inotify_init),
+    ("lchown", libc::SYS_lchown),
+    ("link", libc::SYS_link),
+    ("lstat", libc::SYS_lstat),
+    ("mkdir", libc::SYS_mkdir),
+    ("open", libc::SYS_open),
+    ("pause", libc::SYS_pause),
+    ("pipe", libc::SYS_pipe),
+    ("poll", libc::SYS_poll),
+    ("readlink", libc::SYS_readlink),
+    ("rename", libc::SYS_rename),
+    ("renameat", libc::SYS_renameat),
+    ("rmdir", libc::SYS_rmdir),
+    ("select", libc::SYS_select),
+    ("sendfile", libc::SYS_sendfile),
+    ("setrlimit", libc::SYS_setrlimit),
+    ("signalfd", libc::SYS_signalfd),
+    ("stat", libc::SYS_stat),
+    ("symlink", libc::SYS_symlink),
+    ("sync_file_range", libc::SYS_sync_file_range),
+    ("time", libc::SYS_time),
+    ("unlink", libc::SYS_unlink),
+    ("utime", libc::SYS_utime),
+    ("utimes", libc::SYS_utimes),
+    ("vfork", libc::SYS_vfork),
+];
+
+#[cfg(target_arch = "aarch64")]
+static ARCH_SYSCALLS: &[(&str, libc::c_long)] = &[];
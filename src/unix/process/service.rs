@@ -0,0 +1,70 @@
+use crate::unix::process::{activation::Socket, cgroup::Resources, exec::Exec, sandbox::Sandbox};
+use serde::{Deserialize, Serialize};
+use std::{env, fs, path::Path};
+
+/// Service describes how a supervised process is run.
+///
+/// A definition is read from a YAML file next to the executable, with a `.yaml` extension (e.g.
+/// `/system/plugins/resolver-linux-x86_64.yaml`). Every field is optional.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Service {
+    /// The name of the service. Defaults to the executable's file name without the
+    /// `-<os>-<arch>` suffix.
+    pub name: String,
+    #[serde(skip)]
+    pub executable: String,
+    pub args: Vec<String>,
+    pub exec: Exec,
+    pub sandbox: Sandbox,
+    pub resources: Resources,
+    /// The sockets to bind and pass to the service.
+    pub sockets: Vec<Socket>,
+    /// Starts the service when the first connection is pending on one of its sockets, instead of
+    /// immediately.
+    pub on_demand: bool,
+}
+
+impl Service {
+    pub fn new(executable: String) -> Service {
+        Service {
+            name: name(&executable),
+            executable,
+            ..Default::default()
+        }
+    }
+
+    /// Loads the definition of the service for `executable`, falling back to the defaults if
+    /// there is none.
+    pub fn load(executable: String) -> Result<Service, Box<dyn std::error::Error>> {
+        let definition = format!("{}.yaml", executable);
+
+        if !Path::new(&definition).exists() {
+            return Ok(Service::new(executable));
+        }
+
+        let contents = fs::read_to_string(&definition)?;
+
+        let mut service: Service = serde_yaml::from_str(&contents)?;
+
+        if service.name.is_empty() {
+            service.name = name(&executable);
+        }
+
+        service.executable = executable;
+
+        Ok(service)
+    }
+}
+
+fn name(executable: &str) -> String {
+    let suffix = format!("-{}-{}", env::consts::OS, env::consts::ARCH);
+
+    match Path::new(executable).file_name() {
+        Some(file_name) => file_name
+            .to_string_lossy()
+            .trim_end_matches(suffix.as_str())
+            .to_owned(),
+        None => executable.to_owned(),
+    }
+}
@@ -0,0 +1,316 @@
+use crate::unix::process::cgroup::{Cgroup, Usage};
+use nix::errno::Errno;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::{fs, io};
+
+/// Makes the calling process the reaper of its orphaned descendants.
+///
+/// https://man7.org/linux/man-pages/man2/prctl.2.html.
+pub fn subreaper() -> io::Result<()> {
+    match unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Origin is the service that a process belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub service: String,
+    /// Whether the process is the main process of the service, as opposed to one of its
+    /// descendants.
+    pub main: bool,
+}
+
+#[derive(Debug)]
+struct Entry {
+    origin: Origin,
+    // Used to detect that a pid was reused.
+    start_time: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    processes: HashMap<Pid, Entry>,
+    cgroups: HashMap<String, Cgroup>,
+}
+
+/// Tracker attributes processes to the services that started them, so that a service's whole
+/// process tree can be found even after its processes have been orphaned.
+///
+/// A process is attributed to a service if it is in the service's cgroup, or if its parent is
+/// attributed to the service. The latter only works if `refresh` runs before the parent exits, so
+/// cgroup membership is preferred.
+#[derive(Debug, Default, Clone)]
+pub struct Tracker {
+    state: Arc<Mutex<State>>,
+}
+
+impl Tracker {
+    /// Records `pid` as the main process of `service`.
+    pub fn track(&self, service: &str, pid: Pid) {
+        let mut state = self.state.lock().unwrap();
+
+        state.processes.insert(
+            pid,
+            Entry {
+                origin: Origin {
+                    service: service.to_owned(),
+                    main: true,
+                },
+                start_time: None,
+            },
+        );
+    }
+
+    /// Records `cgroup` as the cgroup of `service`.
+    pub fn track_cgroup(&self, service: &str, cgroup: Cgroup) {
+        let mut state = self.state.lock().unwrap();
+
+        state.cgroups.insert(service.to_owned(), cgroup);
+    }
+
+    /// Scans `/proc` and attributes new processes to services. Processes that no longer exist are
+    /// forgotten.
+    pub fn refresh(&self) -> io::Result<()> {
+        let processes = scan()?;
+        let mountpoint = mountpoint();
+
+        let mut state = self.state.lock().unwrap();
+
+        state.processes.retain(|pid, entry| {
+            match processes.iter().find(|process| process.pid == *pid) {
+                Some(process) => match entry.start_time {
+                    Some(start_time) => start_time == process.start_time,
+                    None => {
+                        entry.start_time = Some(process.start_time);
+                        true
+                    }
+                },
+                None => false,
+            }
+        });
+
+        // N.B.: A parent may be listed after its children, so repeat until nothing changes.
+        loop {
+            let mut changed = false;
+
+            for process in &processes {
+                if state.processes.contains_key(&process.pid) {
+                    continue;
+                }
+
+                let service = match (&mountpoint, &process.cgroup) {
+                    (Some(mountpoint), Some(path)) => state
+                        .cgroups
+                        .iter()
+                        .find(|(_, cgroup)| contains(cgroup, mountpoint, path))
+                        .map(|(service, _)| service.clone()),
+                    _ => None,
+                };
+
+                let service = service.or_else(|| {
+                    state
+                        .processes
+                        .get(&process.ppid)
+                        .map(|entry| entry.origin.service.clone())
+                });
+
+                if let Some(service) = service {
+                    state.processes.insert(
+                        process.pid,
+                        Entry {
+                            origin: Origin {
+                                service,
+                                main: false,
+                            },
+                            start_time: Some(process.start_time),
+                        },
+                    );
+
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns the processes of `service`.
+    pub fn members(&self, service: &str) -> Vec<Pid> {
+        let state = self.state.lock().unwrap();
+
+        let mut members: Vec<Pid> = state
+            .processes
+            .iter()
+            .filter(|(_, entry)| entry.origin.service == service)
+            .map(|(pid, _)| *pid)
+            .collect();
+
+        if let Some(cgroup) = state.cgroups.get(service) {
+            if let Ok(procs) = cgroup.procs() {
+                for pid in procs {
+                    if !members.contains(&pid) {
+                        members.push(pid);
+                    }
+                }
+            }
+        }
+
+        members.sort();
+
+        members
+    }
+
+    /// Sends `signal` to every process of `service` and returns the processes that were
+    /// signaled.
+    pub fn kill(&self, service: &str, signal: Signal) -> io::Result<Vec<Pid>> {
+        self.refresh()?;
+
+        let mut signaled = vec![];
+
+        for pid in self.members(service) {
+            match kill(pid, signal) {
+                Ok(()) => signaled.push(pid),
+                // The process exited in the meantime.
+                Err(nix::Error::Sys(Errno::ESRCH)) => (),
+                Err(nix::Error::Sys(errno)) => {
+                    return Err(io::Error::from_raw_os_error(errno as i32))
+                }
+                Err(err) => return Err(io::Error::new(io::ErrorKind::Other, err)),
+            }
+        }
+
+        Ok(signaled)
+    }
+
+    /// Returns the origin of `pid`, if known, without forgetting it.
+    pub fn origin(&self, pid: Pid) -> Option<Origin> {
+        let state = self.state.lock().unwrap();
+
+        state.processes.get(&pid).map(|entry| entry.origin.clone())
+    }
+
+    /// Forgets `pid` (e.g. once it was reaped) and returns its origin, if known.
+    pub fn forget(&self, pid: Pid) -> Option<Origin> {
+        let mut state = self.state.lock().unwrap();
+
+        state.processes.remove(&pid).map(|entry| entry.origin)
+    }
+
+    /// Returns the services that run in cgroups of their own, in order.
+    pub fn accounted(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+
+        let mut services: Vec<String> = state.cgroups.keys().cloned().collect();
+
+        services.sort();
+
+        services
+    }
+
+    /// Returns the resource usage of `service`, or `None` if it does not run in a cgroup of its
+    /// own.
+    pub fn usage(&self, service: &str) -> io::Result<Option<Usage>> {
+        let state = self.state.lock().unwrap();
+
+        match state.cgroups.get(service) {
+            Some(cgroup) => cgroup.usage().map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+// Returns true if `path`, as listed in `/proc/<pid>/cgroup`, is `cgroup` or one of its descendants.
+fn contains(cgroup: &Cgroup, mountpoint: &Path, path: &str) -> bool {
+    match cgroup.path().strip_prefix(mountpoint) {
+        Ok(relative) => Path::new(path).starts_with(Path::new("/").join(relative)),
+        Err(_) => false,
+    }
+}
+
+// Returns where the cgroup2 filesystem is mounted, since the paths in `/proc/<pid>/cgroup` are
+// relative to it.
+//
+// https://man7.org/linux/man-pages/man5/proc.5.html.
+fn mountpoint() -> Option<PathBuf> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").ok()?;
+
+    mountinfo.lines().find_map(|line| {
+        let mut halves = line.splitn(2, " - ");
+
+        let mountpoint = halves.next()?.split_whitespace().nth(4)?;
+        let fstype = halves.next()?.split_whitespace().next()?;
+
+        match fstype {
+            "cgroup2" => Some(PathBuf::from(mountpoint)),
+            _ => None,
+        }
+    })
+}
+
+struct Process {
+    pid: Pid,
+    ppid: Pid,
+    start_time: u64,
+    cgroup: Option<String>,
+}
+
+fn scan() -> io::Result<Vec<Process>> {
+    let mut processes = vec![];
+
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+
+        let pid: i32 = match entry.file_name().to_string_lossy().parse() {
+            Ok(pid) => pid,
+            Err(_) => continue,
+        };
+
+        // N.B.: The process may exit while it is being read.
+        let stat = match fs::read_to_string(entry.path().join("stat")) {
+            Ok(stat) => stat,
+            Err(_) => continue,
+        };
+
+        // The command name may contain spaces and parentheses, the fields that follow it start
+        // with `state`.
+        let fields: Vec<&str> = match stat.rfind(')') {
+            Some(end) => stat[end + 1..].split_whitespace().collect(),
+            None => continue,
+        };
+
+        let (ppid, start_time) = match (fields.get(1), fields.get(19)) {
+            (Some(ppid), Some(start_time)) => match (ppid.parse(), start_time.parse()) {
+                (Ok(ppid), Ok(start_time)) => (Pid::from_raw(ppid), start_time),
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        // The unified hierarchy is listed as `0::<path>`.
+        let cgroup = fs::read_to_string(entry.path().join("cgroup"))
+            .ok()
+            .and_then(|cgroups| {
+                cgroups
+                    .lines()
+                    .find(|line| line.starts_with("0::"))
+                    .map(|line| line[3..].to_owned())
+            });
+
+        processes.push(Process {
+            pid: Pid::from_raw(pid),
+            ppid,
+            start_time,
+            cgroup,
+        });
+    }
+
+    Ok(processes)
+}
@@ -0,0 +1,201 @@
+//! Rendering `Resolver` resources as `/etc/resolv.conf`.
+//!
+//! https://man7.org/linux/man-pages/man5/resolv.conf.5.html.
+
+use crate::resource::TypedResource;
+use crate::spec::engine::{Resolver, ResolverOptions};
+use std::fmt::{self, Write};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+
+/// The environment variable that overrides `consts::RESOLV_CONF`, e.g. to render to a directory
+/// that `/etc/resolv.conf` links to, so that the resolver need not write to `/etc`.
+pub static RESOLV_CONF_ENV: &str = "COSI_RESOLV_CONF";
+
+/// Returns the path `resolv.conf` is rendered to.
+pub fn path() -> PathBuf {
+    match std::env::var(RESOLV_CONF_ENV) {
+        Ok(path) if !path.is_empty() => PathBuf::from(path),
+        _ => PathBuf::from(crate::consts::RESOLV_CONF),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ResolverError {
+    /// A nameserver is not an IP address.
+    InvalidNameserver(String),
+    /// A sortlist entry is not an IPv4 address, optionally followed by a netmask.
+    InvalidSortlist(String),
+}
+
+impl fmt::Display for ResolverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolverError::InvalidNameserver(nameserver) => {
+                write!(f, "invalid nameserver {:?}", nameserver)
+            }
+            ResolverError::InvalidSortlist(entry) => write!(f, "invalid sortlist {:?}", entry),
+        }
+    }
+}
+
+impl std::error::Error for ResolverError {}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    if !values.iter().any(|v| v == value) {
+        values.push(value.to_owned());
+    }
+}
+
+fn merge_options(merged: &mut ResolverOptions, options: &ResolverOptions) {
+    merged.debug |= options.debug;
+    merged.rotate |= options.rotate;
+    merged.no_check_names |= options.no_check_names;
+    merged.inet6 |= options.inet6;
+    merged.ip6_bytestring |= options.ip6_bytestring;
+    merged.ip6_dotint |= options.ip6_dotint;
+    merged.edns0 |= options.edns0;
+    merged.single_request |= options.single_request;
+    merged.single_request_reopen |= options.single_request_reopen;
+    merged.no_tld_query |= options.no_tld_query;
+    merged.use_vc |= options.use_vc;
+    merged.no_reload |= options.no_reload;
+    merged.trust_ad |= options.trust_ad;
+
+    if options.ndots != 0 {
+        merged.ndots = options.ndots;
+    }
+
+    if options.timeout != 0 {
+        merged.timeout = options.timeout;
+    }
+
+    if options.attempts != 0 {
+        merged.attempts = options.attempts;
+    }
+}
+
+/// Merges `resolvers` into one, in the order of their IDs. Nameservers, search domains and
+/// sortlist entries are concatenated without duplicates, boolean options are enabled if any
+/// resolver enables them, and numeric options are taken from the last resolver that sets them.
+pub fn merge(resolvers: &[TypedResource<Resolver>]) -> Resolver {
+    let mut resolvers: Vec<&TypedResource<Resolver>> = resolvers.iter().collect();
+
+    resolvers.sort_by(|a, b| a.metadata.id.cmp(&b.metadata.id));
+
+    let mut nameservers = vec![];
+    let mut search = vec![];
+    let mut sortlist = vec![];
+    let mut options = ResolverOptions::default();
+
+    for resolver in resolvers.iter().map(|resolver| &resolver.spec) {
+        for nameserver in &resolver.nameserver {
+            push_unique(&mut nameservers, nameserver.trim());
+        }
+
+        for domain in resolver.search.split_whitespace() {
+            push_unique(&mut search, domain);
+        }
+
+        for entry in &resolver.sortlist {
+            push_unique(&mut sortlist, entry.trim());
+        }
+
+        for resolver_options in &resolver.options {
+            merge_options(&mut options, resolver_options);
+        }
+    }
+
+    Resolver {
+        nameserver: nameservers,
+        search: search.join(" "),
+        sortlist,
+        options: match options == ResolverOptions::default() {
+            true => vec![],
+            false => vec![options],
+        },
+    }
+}
+
+/// Validates the nameservers and sortlist of `resolver`.
+pub fn validate(resolver: &Resolver) -> Result<(), ResolverError> {
+    for nameserver in &resolver.nameserver {
+        // N.B.: A link-local IPv6 nameserver may carry a zone (e.g. `fe80::1%eth0`).
+        let address = nameserver.split('%').next().unwrap_or_default();
+
+        if address.parse::<IpAddr>().is_err() {
+            return Err(ResolverError::InvalidNameserver(nameserver.clone()));
+        }
+    }
+
+    for entry in &resolver.sortlist {
+        let mut parts = entry.splitn(2, '/');
+
+        let valid = parts.all(|part| part.parse::<Ipv4Addr>().is_ok());
+
+        if !valid {
+            return Err(ResolverError::InvalidSortlist(entry.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders `resolver` in the format of `resolv.conf`.
+pub fn render(resolver: &Resolver) -> String {
+    let mut conf = String::new();
+
+    for nameserver in &resolver.nameserver {
+        let _ = writeln!(conf, "nameserver {}", nameserver);
+    }
+
+    if !resolver.search.trim().is_empty() {
+        let _ = writeln!(conf, "search {}", resolver.search.trim());
+    }
+
+    if !resolver.sortlist.is_empty() {
+        let _ = writeln!(conf, "sortlist {}", resolver.sortlist.join(" "));
+    }
+
+    for options in &resolver.options {
+        let flags = vec![
+            (options.debug, "debug"),
+            (options.rotate, "rotate"),
+            (options.no_check_names, "no-check-names"),
+            (options.inet6, "inet6"),
+            (options.ip6_bytestring, "ip6-bytestring"),
+            (options.ip6_dotint, "ip6-dotint"),
+            (options.edns0, "edns0"),
+            (options.single_request, "single-request"),
+            (options.single_request_reopen, "single-request-reopen"),
+            (options.no_tld_query, "no-tld-query"),
+            (options.use_vc, "use-vc"),
+            (options.no_reload, "no-reload"),
+            (options.trust_ad, "trust-ad"),
+        ];
+
+        let values = vec![
+            (options.ndots, "ndots"),
+            (options.timeout, "timeout"),
+            (options.attempts, "attempts"),
+        ];
+
+        let rendered: Vec<String> = values
+            .into_iter()
+            .filter(|(value, _)| *value != 0)
+            .map(|(value, name)| format!("{}:{}", name, value))
+            .chain(
+                flags
+                    .into_iter()
+                    .filter(|(enabled, _)| *enabled)
+                    .map(|(_, name)| name.to_owned()),
+            )
+            .collect();
+
+        if !rendered.is_empty() {
+            let _ = writeln!(conf, "options {}", rendered.join(" "));
+        }
+    }
+
+    conf
+}
@@ -0,0 +1,353 @@
+//! Polling time servers over SNTP, and disciplining the clock with the offsets they report.
+//!
+//! https://datatracker.ietf.org/doc/html/rfc4330.
+//! https://man7.org/linux/man-pages/man2/adjtimex.2.html.
+
+use std::fmt;
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The port of time servers whose address does not name one.
+pub const PORT: u16 = 123;
+
+/// The offset beyond which the clock is stepped rather than slewed, in nanoseconds, as with
+/// ntpd.
+pub const STEP_THRESHOLD: i64 = 128_000_000;
+
+// The length of a packet without extension fields or a MAC.
+const PACKET_LEN: usize = 48;
+
+// The seconds between the NTP epoch (1900) and the Unix epoch (1970).
+const EPOCH_OFFSET: i64 = 2_208_988_800;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+// LI 0 (no warning), VN 4, mode 3 (client).
+const REQUEST_HEADER: u8 = 0x23;
+
+const MODE_SERVER: u8 = 4;
+
+const LEAP_UNSYNCHRONIZED: u8 = 3;
+
+// The stratum of a server that is not synchronized itself. Stratum 0 is a kiss-o'-death packet.
+const MAX_STRATUM: u8 = 15;
+
+// N.B.: musl does not wrap `adjtimex`, nor does libc define its flags for it, so they are
+// defined here as in `include/uapi/linux/timex.h`.
+const ADJ_OFFSET: libc::c_uint = 0x0001;
+const ADJ_MAXERROR: libc::c_uint = 0x0004;
+const ADJ_ESTERROR: libc::c_uint = 0x0008;
+const ADJ_STATUS: libc::c_uint = 0x0010;
+const ADJ_SETOFFSET: libc::c_uint = 0x0100;
+const ADJ_NANO: libc::c_uint = 0x2000;
+
+const STA_PLL: libc::c_int = 0x0001;
+
+// The argument of `adjtimex`, as `struct timex` in `include/uapi/linux/timex.h`.
+#[repr(C)]
+struct Timex {
+    modes: libc::c_uint,
+    offset: libc::c_long,
+    freq: libc::c_long,
+    maxerror: libc::c_long,
+    esterror: libc::c_long,
+    status: libc::c_int,
+    constant: libc::c_long,
+    precision: libc::c_long,
+    tolerance: libc::c_long,
+    time: libc::timeval,
+    tick: libc::c_long,
+    ppsfreq: libc::c_long,
+    jitter: libc::c_long,
+    shift: libc::c_int,
+    stabil: libc::c_long,
+    jitcnt: libc::c_long,
+    calcnt: libc::c_long,
+    errcnt: libc::c_long,
+    stbcnt: libc::c_long,
+    tai: libc::c_int,
+    padding: [libc::c_int; 11],
+}
+
+#[derive(Debug)]
+pub enum SntpError {
+    InvalidAddress(String),
+    /// The server did not answer in time.
+    Timeout(String),
+    /// The server asked not to be polled (a kiss-o'-death packet), with the code it gave.
+    KissOfDeath {
+        server: String,
+        code: String,
+    },
+    /// The server is not synchronized itself.
+    Unsynchronized(String),
+    /// The server answered with a packet that is not a valid response to the request.
+    InvalidResponse(String),
+    IoError(io::Error),
+}
+
+impl fmt::Display for SntpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SntpError::InvalidAddress(address) => {
+                write!(f, "invalid time server address {:?}", address)
+            }
+            SntpError::Timeout(server) => write!(f, "time server {} did not answer", server),
+            SntpError::KissOfDeath { server, code } => {
+                write!(f, "time server {} refused to be polled ({})", server, code)
+            }
+            SntpError::Unsynchronized(server) => {
+                write!(f, "time server {} is not synchronized", server)
+            }
+            SntpError::InvalidResponse(server) => {
+                write!(f, "invalid response from time server {}", server)
+            }
+            SntpError::IoError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SntpError {}
+
+impl From<io::Error> for SntpError {
+    fn from(error: io::Error) -> SntpError {
+        SntpError::IoError(error)
+    }
+}
+
+/// A poll of a time server. Times are in nanoseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// The offset of the server's clock from the local one.
+    pub offset: i64,
+    /// The round-trip delay to the server.
+    pub delay: i64,
+    pub stratum: u8,
+    /// The round-trip delay and dispersion from the server to its reference clock.
+    pub root_delay: i64,
+    pub root_dispersion: i64,
+}
+
+impl Sample {
+    /// Returns the maximum error of the local clock once it is synchronized with the sample.
+    pub fn distance(&self) -> i64 {
+        self.root_delay / 2 + self.root_dispersion + self.delay / 2
+    }
+}
+
+/// How the clock was adjusted.
+#[derive(Debug, PartialEq)]
+pub enum Adjustment {
+    /// The clock is sped up or slowed down until it catches up with the offset.
+    Slewed,
+    /// The clock jumped by the offset.
+    Stepped,
+}
+
+/// Resolves the address of a time server, which is a host name or IP address with an optional
+/// port.
+pub fn resolve(address: &str) -> Result<SocketAddr, SntpError> {
+    if let Ok(ip) = address.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, PORT));
+    }
+
+    if address.is_empty() || address.starts_with(':') {
+        return Err(SntpError::InvalidAddress(address.to_owned()));
+    }
+
+    let mut addresses = if address.contains(':') {
+        address.to_socket_addrs()?
+    } else {
+        (address, PORT).to_socket_addrs()?
+    };
+
+    addresses
+        .next()
+        .ok_or_else(|| SntpError::InvalidAddress(address.to_owned()))
+}
+
+/// Polls the time server at `address`, waiting at most `timeout` for it to answer.
+pub fn query(address: &str, timeout: Duration) -> Result<Sample, SntpError> {
+    let server = resolve(address)?;
+
+    let local = match server {
+        SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+
+    let socket = UdpSocket::bind(local)?;
+
+    // N.B.: A connected socket only receives packets from the server.
+    socket.connect(server)?;
+
+    let mut request = [0; PACKET_LEN];
+
+    request[0] = REQUEST_HEADER;
+
+    // The server echoes the transmit timestamp of the request as the origin timestamp of its
+    // response, which tells stale or forged responses apart.
+    let sent = now();
+    let origin = encode(sent);
+
+    request[40..48].copy_from_slice(&origin);
+
+    socket.send(&request)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut response = [0; PACKET_LEN];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining == Duration::from_secs(0) {
+            return Err(SntpError::Timeout(address.to_owned()));
+        }
+
+        socket.set_read_timeout(Some(remaining))?;
+
+        let len = match socket.recv(&mut response) {
+            Ok(len) => len,
+            Err(err)
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                return Err(SntpError::Timeout(address.to_owned()))
+            }
+            Err(err) => return Err(SntpError::from(err)),
+        };
+
+        let received = now();
+
+        if len < PACKET_LEN || response[24..32] != origin {
+            continue;
+        }
+
+        return sample(address, &response, sent, received);
+    }
+}
+
+// Computes a sample from the `response` to a request sent at `sent` and received at `received`.
+fn sample(address: &str, response: &[u8], sent: i64, received: i64) -> Result<Sample, SntpError> {
+    let leap = response[0] >> 6;
+    let mode = response[0] & 0x7;
+    let stratum = response[1];
+
+    if mode != MODE_SERVER || response[40..48] == [0; 8] {
+        return Err(SntpError::InvalidResponse(address.to_owned()));
+    }
+
+    if stratum == 0 {
+        let code = response[12..16]
+            .iter()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| *c as char)
+            .collect();
+
+        return Err(SntpError::KissOfDeath {
+            server: address.to_owned(),
+            code,
+        });
+    }
+
+    if stratum > MAX_STRATUM || leap == LEAP_UNSYNCHRONIZED {
+        return Err(SntpError::Unsynchronized(address.to_owned()));
+    }
+
+    let t2 = decode(&response[32..40]);
+    let t3 = decode(&response[40..48]);
+
+    Ok(Sample {
+        offset: ((t2 - sent) + (t3 - received)) / 2,
+        delay: ((received - sent) - (t3 - t2)).max(0),
+        stratum,
+        root_delay: short(&response[4..8]),
+        root_dispersion: short(&response[8..12]),
+    })
+}
+
+/// Adjusts the clock by `sample`'s offset, and marks it as synchronized with the maximum error
+/// of the sample.
+pub fn adjust(sample: &Sample) -> io::Result<Adjustment> {
+    // N.B.: The structure has no default, since it holds a `timeval`.
+    let mut timex: Timex = unsafe { mem::zeroed() };
+
+    // N.B.: Setting the status also clears `STA_UNSYNC`, and the offset of the kernel's phase
+    // locked loop, if the clock is stepped.
+    timex.modes = ADJ_OFFSET | ADJ_STATUS | ADJ_NANO | ADJ_MAXERROR | ADJ_ESTERROR;
+    timex.status = STA_PLL;
+    timex.maxerror = (sample.distance() / 1000) as libc::c_long;
+    timex.esterror = (sample.delay / 2 / 1000) as libc::c_long;
+
+    let adjustment = if sample.offset.abs() > STEP_THRESHOLD {
+        // N.B.: With `ADJ_NANO`, `tv_usec` holds nanoseconds, and must not be negative.
+        timex.modes |= ADJ_SETOFFSET;
+        timex.time.tv_sec = sample.offset.div_euclid(NANOS_PER_SEC) as libc::time_t;
+        timex.time.tv_usec = sample.offset.rem_euclid(NANOS_PER_SEC) as libc::suseconds_t;
+
+        Adjustment::Stepped
+    } else {
+        timex.offset = sample.offset as libc::c_long;
+
+        Adjustment::Slewed
+    };
+
+    match unsafe { libc::syscall(libc::SYS_adjtimex, &mut timex as *mut Timex) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(adjustment),
+    }
+}
+
+/// Returns the time since the Unix epoch, in nanoseconds.
+pub fn now() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_nanos() as i64,
+        Err(err) => -(err.duration().as_nanos() as i64),
+    }
+}
+
+/// Encodes a time since the Unix epoch, in nanoseconds, as an NTP timestamp.
+pub fn encode(time: i64) -> [u8; 8] {
+    let seconds = (time.div_euclid(NANOS_PER_SEC) + EPOCH_OFFSET) as u32;
+    let fraction = ((time.rem_euclid(NANOS_PER_SEC) << 32) / NANOS_PER_SEC) as u32;
+
+    let mut timestamp = [0; 8];
+
+    timestamp[..4].copy_from_slice(&seconds.to_be_bytes());
+    timestamp[4..].copy_from_slice(&fraction.to_be_bytes());
+
+    timestamp
+}
+
+/// Decodes an NTP timestamp as a time since the Unix epoch, in nanoseconds.
+pub fn decode(timestamp: &[u8]) -> i64 {
+    let mut seconds = i64::from(u32::from_be_bytes([
+        timestamp[0],
+        timestamp[1],
+        timestamp[2],
+        timestamp[3],
+    ]));
+
+    let fraction = i64::from(u32::from_be_bytes([
+        timestamp[4],
+        timestamp[5],
+        timestamp[6],
+        timestamp[7],
+    ]));
+
+    // N.B.: Timestamps wrap around in 2036. Those with the most significant bit unset are taken
+    // to be in the next era, as RFC 4330 suggests.
+    if seconds < 1 << 31 {
+        seconds += 1 << 32;
+    }
+
+    (seconds - EPOCH_OFFSET) * NANOS_PER_SEC + ((fraction * NANOS_PER_SEC) >> 32)
+}
+
+// Decodes a 16.16 fixed point number of seconds, in nanoseconds.
+fn short(value: &[u8]) -> i64 {
+    let value = i64::from(u32::from_be_bytes([value[0], value[1], value[2], value[3]]));
+
+    (value * NANOS_PER_SEC) >> 16
+}
@@ -0,0 +1,128 @@
+//! Reading and writing kernel parameters through `/proc/sys`.
+//!
+//! https://man7.org/linux/man-pages/man5/proc.5.html (`/proc/sys`).
+//! https://man7.org/linux/man-pages/man8/sysctl.8.html.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// The environment variable that overrides `consts::PROC_SYS`, e.g. to use a temporary directory
+/// in tests.
+pub static PROC_SYS_ENV: &str = "COSI_PROC_SYS";
+
+/// Returns the directory kernel parameters are read from and written to.
+pub fn root() -> PathBuf {
+    match std::env::var(PROC_SYS_ENV) {
+        Ok(root) if !root.is_empty() => PathBuf::from(root),
+        _ => PathBuf::from(crate::consts::PROC_SYS),
+    }
+}
+
+#[derive(Debug)]
+pub enum SysctlError {
+    /// The key does not name a file under the root (e.g. `net.ipv4..forward`).
+    InvalidKey(String),
+    /// The kernel has no such parameter.
+    UnknownKey(String),
+    /// The value read back differs from the one written.
+    Mismatch {
+        key: String,
+        expected: String,
+        found: String,
+    },
+    IoError(io::Error),
+}
+
+impl fmt::Display for SysctlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SysctlError::InvalidKey(key) => write!(f, "invalid kernel parameter {:?}", key),
+            SysctlError::UnknownKey(key) => write!(f, "unknown kernel parameter {}", key),
+            SysctlError::Mismatch {
+                key,
+                expected,
+                found,
+            } => write!(
+                f,
+                "kernel parameter {} is {:?} after writing {:?}",
+                key, found, expected
+            ),
+            SysctlError::IoError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SysctlError {}
+
+impl From<io::Error> for SysctlError {
+    fn from(error: io::Error) -> SysctlError {
+        SysctlError::IoError(error)
+    }
+}
+
+/// Returns the path of `key` under `root`. Keys are separated by dots (`net.ipv4.ip_forward`)
+/// or, as with sysctl(8), by slashes when a component contains a dot
+/// (`net/ipv4/conf/eth0.1/forwarding`).
+pub fn path(root: &Path, key: &str) -> Result<PathBuf, SysctlError> {
+    let relative = match key.contains('/') {
+        true => PathBuf::from(key),
+        false => PathBuf::from(key.replace('.', "/")),
+    };
+
+    let valid = !key.is_empty()
+        && !key.split(&['.', '/'][..]).any(str::is_empty)
+        && relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+
+    match valid {
+        true => Ok(root.join(relative)),
+        false => Err(SysctlError::InvalidKey(key.to_owned())),
+    }
+}
+
+fn unknown(key: &str) -> impl Fn(io::Error) -> SysctlError + '_ {
+    move |err| match err.kind() {
+        io::ErrorKind::NotFound => SysctlError::UnknownKey(key.to_owned()),
+        _ => SysctlError::IoError(err),
+    }
+}
+
+// N.B.: Multi-valued parameters (e.g. `net.ipv4.ip_local_port_range`) are read back separated by
+// tabs, so values are compared by their words.
+fn normalize(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Reads the value of `key`.
+pub fn read(root: &Path, key: &str) -> Result<String, SysctlError> {
+    let value = fs::read_to_string(path(root, key)?).map_err(unknown(key))?;
+
+    Ok(normalize(&value))
+}
+
+/// Writes `value` to `key`, and returns the value read back after verifying that it matches.
+pub fn write(root: &Path, key: &str, value: &str) -> Result<String, SysctlError> {
+    let path = path(root, key)?;
+
+    // N.B.: The file is not created, so that unknown keys are reported as such.
+    OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(value.as_bytes()))
+        .map_err(unknown(key))?;
+
+    let found = read(root, key)?;
+
+    match found == normalize(value) {
+        true => Ok(found),
+        false => Err(SysctlError::Mismatch {
+            key: key.to_owned(),
+            expected: value.to_owned(),
+            found,
+        }),
+    }
+}
@@ -1,9 +1,15 @@
+use cosi::bootstrap::{Tokens, AUTHORIZATION};
+use nix::sched::{unshare, CloneFlags};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{fork, getgid, getuid, ForkResult};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
-use std::fs::create_dir_all;
+use std::fs::{self, create_dir_all};
 use std::io;
 use std::{env::temp_dir, path::PathBuf};
+use tonic::Request;
 
+#[allow(dead_code)]
 pub fn setup() -> Result<PathBuf, io::Error> {
     let mut dir = temp_dir();
 
@@ -20,3 +26,62 @@ pub fn setup() -> Result<PathBuf, io::Error> {
 
     Ok(dir)
 }
+
+/// Runs `f` in a child process, in a new user namespace where the caller is root and in the other
+/// namespaces of `flags`, and asserts that it succeeded. Its error is printed to stderr.
+#[allow(dead_code)]
+pub fn in_namespace<F>(flags: CloneFlags, f: F)
+where
+    F: FnOnce() -> Result<(), String>,
+{
+    let (uid, gid) = (getuid(), getgid());
+
+    match unsafe { fork() }.unwrap() {
+        ForkResult::Child => {
+            let result = unshare(CloneFlags::CLONE_NEWUSER | flags)
+                .map_err(|err| err.to_string())
+                .and_then(|_| {
+                    fs::write("/proc/self/setgroups", "deny")
+                        .and_then(|_| fs::write("/proc/self/uid_map", format!("0 {} 1", uid)))
+                        .and_then(|_| fs::write("/proc/self/gid_map", format!("0 {} 1", gid)))
+                        .map_err(|err| err.to_string())
+                })
+                .and_then(|_| f());
+
+            if let Err(err) = &result {
+                eprintln!("{}", err);
+            }
+
+            unsafe { libc::_exit(result.is_err() as i32) }
+        }
+        ForkResult::Parent { child } => {
+            assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Exited(child, 0));
+        }
+    }
+}
+
+/// Returns the tokens of `services`, each of which is issued its own name as its token.
+#[allow(dead_code)]
+pub fn tokens(services: &[&str]) -> Tokens {
+    let tokens = Tokens::default();
+
+    for service in services {
+        tokens.issue(service, service);
+    }
+
+    tokens
+}
+
+/// Returns `message` as a request of `service`, with the token `tokens` issues it, or as a request
+/// of a user if `service` is empty.
+#[allow(dead_code)]
+pub fn request<T>(service: &str, message: T) -> Request<T> {
+    let mut request = Request::new(message);
+
+    if !service.is_empty() {
+        let value = format!("Bearer {}", service).parse().unwrap();
+        request.metadata_mut().insert(AUTHORIZATION, value);
+    }
+
+    request
+}
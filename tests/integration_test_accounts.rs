@@ -0,0 +1,196 @@
+mod common;
+
+use common::setup;
+use cosi::spec::engine::{Group, User};
+use cosi::unix::accounts::{self, AccountError, Accounts, Action, Managed};
+use nix::unistd::{getgid, getuid};
+use std::fs::{self, Permissions};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+
+static PASSWD: &str = "\
+root:x:0:0:root:/root:/bin/bash
+daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin
+";
+
+static GROUP: &str = "\
+root:x:0:
+daemon:x:1:
+";
+
+static SHADOW: &str = "\
+root:*:18000:0:99999:7:::
+daemon:*:18000:0:99999:7:::
+";
+
+fn prepare(root: &Path) {
+    fs::write(root.join("passwd"), PASSWD).unwrap();
+    fs::write(root.join("group"), GROUP).unwrap();
+    fs::write(root.join("shadow"), SHADOW).unwrap();
+    fs::set_permissions(root.join("shadow"), Permissions::from_mode(0o640)).unwrap();
+}
+
+fn user(name: &str, uid: u32, group: &str, groups: &[&str]) -> User {
+    User {
+        name: name.to_owned(),
+        uid,
+        group: group.to_owned(),
+        groups: groups.iter().map(|g| g.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+fn group(name: &str, gid: u32) -> Group {
+    Group {
+        name: name.to_owned(),
+        gid,
+    }
+}
+
+fn actions(results: Vec<(Action, Result<(), AccountError>)>) -> Vec<Action> {
+    results
+        .into_iter()
+        .map(|(action, result)| result.map(|_| action))
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn accounts_converge() {
+    let root = setup().unwrap();
+
+    prepare(&root);
+
+    let home = root.join("home/sandbox");
+
+    let users = vec![
+        user("containerd", 0, "", &["docker"]),
+        User {
+            home: home.display().to_string(),
+            create_home: true,
+            ..user("sandbox", getuid().as_raw(), "sandbox", &[])
+        },
+        // Users that already exist are left alone.
+        user("daemon", 0, "", &["docker"]),
+    ];
+
+    let groups = vec![group("docker", 0), group("sandbox", getgid().as_raw())];
+
+    let mut managed = Managed::default();
+
+    let results = accounts::converge(&root, &users, &groups, &mut managed).unwrap();
+
+    assert_eq!(
+        actions(results),
+        vec![
+            Action::AddGroup(String::from("docker")),
+            Action::AddGroup(String::from("sandbox")),
+            Action::AddUser(String::from("containerd")),
+            Action::AddUser(String::from("sandbox")),
+            Action::CreateHome(String::from("sandbox")),
+        ]
+    );
+
+    let accounts = Accounts::read(&root).unwrap();
+
+    // IDs are allocated from the top of the system range, and a group created along with a user
+    // gets the same ID.
+    let (uid, gid) = accounts.user("containerd").unwrap();
+
+    assert_eq!(accounts.group("docker"), Some(999));
+    assert!((100..999).contains(&uid));
+    assert_eq!(gid, uid);
+    assert_eq!(accounts.group("containerd"), Some(gid));
+    assert_eq!(accounts.members("docker"), vec!["containerd"]);
+    assert_eq!(accounts.user("daemon"), Some((1, 1)));
+
+    let shadow = fs::read_to_string(root.join("shadow")).unwrap();
+
+    assert!(shadow.starts_with(SHADOW));
+    assert!(shadow.contains("\ncontainerd:!:"));
+    assert_eq!(
+        fs::metadata(root.join("shadow")).unwrap().mode() & 0o7777,
+        0o640
+    );
+
+    let (uid, gid) = accounts.user("sandbox").unwrap();
+    let metadata = fs::metadata(&home).unwrap();
+
+    assert_eq!((metadata.uid(), metadata.gid()), (uid, gid));
+    assert_eq!(metadata.mode() & 0o7777, 0o700);
+
+    assert_eq!(
+        managed.groups.iter().collect::<Vec<_>>(),
+        vec!["containerd", "docker", "sandbox"]
+    );
+
+    // Converged users and groups are left alone.
+    let results = accounts::converge(&root, &users, &groups, &mut managed).unwrap();
+
+    assert!(results.is_empty());
+
+    // Only the users and groups that were created are removed, along with their memberships.
+    let results = accounts::converge(&root, &users[2..], &[], &mut managed).unwrap();
+
+    assert_eq!(
+        actions(results),
+        vec![
+            Action::RemoveUser(String::from("containerd")),
+            Action::RemoveUser(String::from("sandbox")),
+            Action::RemoveGroup(String::from("containerd")),
+            Action::RemoveGroup(String::from("docker")),
+            Action::RemoveGroup(String::from("sandbox")),
+        ]
+    );
+
+    assert_eq!(fs::read_to_string(root.join("passwd")).unwrap(), PASSWD);
+    assert_eq!(fs::read_to_string(root.join("group")).unwrap(), GROUP);
+    assert_eq!(fs::read_to_string(root.join("shadow")).unwrap(), SHADOW);
+    assert!(home.exists());
+    assert_eq!(managed, Managed::default());
+}
+
+#[test]
+fn accounts_errors() {
+    let root = setup().unwrap();
+
+    prepare(&root);
+
+    let users = vec![
+        user("Service", 0, "", &[]),
+        user("service", 0, "missing", &[]),
+        user("service", 1, "", &[]),
+        user("service", 0, "", &["missing"]),
+        // A line break in a field would add an entry of its own.
+        User {
+            comment: String::from("x\nevil::0:0::/:/bin/sh"),
+            ..user("service", 0, "", &[])
+        },
+        User {
+            shell: String::from("/bin/sh:"),
+            ..user("service", 0, "", &[])
+        },
+    ];
+
+    for user in users {
+        let results = accounts::converge(&root, &[user], &[], &mut Managed::default()).unwrap();
+
+        match &results[..] {
+            [(Action::AddUser(_), Err(AccountError::InvalidName(name)))] => {
+                assert_eq!(name, "Service")
+            }
+            [(Action::AddUser(_), Err(AccountError::UnknownGroup(name)))] => {
+                assert_eq!(name, "missing")
+            }
+            [(Action::AddUser(_), Err(AccountError::IdInUse(id)))] => assert_eq!(*id, 1),
+            [(Action::AddUser(_), Err(AccountError::InvalidField(field)))] => {
+                assert!(field.contains(':'))
+            }
+            results => panic!("unexpected results: {:?}", results),
+        }
+    }
+
+    // Nothing is written for users that fail to be added.
+    assert_eq!(fs::read_to_string(root.join("passwd")).unwrap(), PASSWD);
+    assert_eq!(fs::read_to_string(root.join("group")).unwrap(), GROUP);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::setup;
+use cosi::unix::process::activation::Listeners;
+use cosi::unix::process::environment::Environment;
+use cosi::unix::process::service::Service;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+#[test]
+fn activation() {
+    let dir = setup().unwrap();
+
+    let definition = format!(
+        r#"
+sockets:
+  - unix: {}/activation.sock
+  - name: web
+    tcp: 127.0.0.1:0
+on_demand: true
+"#,
+        dir.display()
+    );
+
+    let service: Service = serde_yaml::from_str(&definition).unwrap();
+
+    assert!(service.on_demand);
+
+    let listeners = Listeners::bind(&service.sockets, "activation").unwrap();
+
+    let path = dir.join("activation.sock");
+
+    // A pending connection wakes up the engine.
+    let _stream = UnixStream::connect(&path).unwrap();
+
+    listeners.wait().unwrap();
+
+    let mut environment = Environment::default();
+    listeners.export(&mut environment);
+
+    let mut environment = environment.prepare().unwrap();
+    let activation = listeners.prepare().unwrap();
+
+    let mut command = Command::new("/bin/sh");
+
+    command.args(&[
+        "-c",
+        r#"echo $LISTEN_FDS $LISTEN_FDNAMES; [ "$LISTEN_PID" = "$$" ] && echo pid; [ -S /proc/self/fd/3 ] && [ -S /proc/self/fd/4 ] && echo sockets; exit 0"#,
+    ]);
+
+    unsafe {
+        command.pre_exec(move || {
+            environment.apply()?;
+            activation.apply()
+        });
+    }
+
+    let output = command.output().unwrap();
+
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    assert_eq!(lines, vec!["2 activation:web", "pid", "sockets"]);
+}
+
+#[test]
+fn activation_rejects_invalid_sockets() {
+    let dir = setup().unwrap();
+
+    let service: Service = serde_yaml::from_str("sockets: [{name: web}]").unwrap();
+    assert!(Listeners::bind(&service.sockets, "activation").is_err());
+
+    let definition = format!("sockets: [{{name: 'a:b', unix: {}/a.sock}}]", dir.display());
+    let service: Service = serde_yaml::from_str(&definition).unwrap();
+    assert!(Listeners::bind(&service.sockets, "activation").is_err());
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::setup;
+use cosi::bootstrap::{Bootstrap, BOOTSTRAP_FILE};
+use cosi::unix::process::bootstrap;
+use cosi::unix::process::environment::Environment;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+fn sample() -> Bootstrap {
+    Bootstrap {
+        engine_socket: "/system/engine.sock".to_owned(),
+        runtime_socket: "/system/runtime.sock".to_owned(),
+        runtime_address: Some("127.0.0.1:50000".to_owned()),
+        service: "test".to_owned(),
+        token: bootstrap::token().unwrap(),
+    }
+}
+
+#[test]
+fn bootstrap_fd() {
+    let expected = sample();
+
+    assert_eq!(expected.token.len(), 32);
+
+    let mut environment = Environment::default();
+    bootstrap::export(&expected, 3, &mut environment);
+
+    let mut environment = environment.prepare().unwrap();
+    let handoff = bootstrap::prepare(&expected, 3).unwrap();
+
+    let mut command = Command::new("/bin/sh");
+
+    command.args(&[
+        "-c",
+        r#"echo $COSI_SERVICE ${COSI_TOKEN:-unset}; cat <&$COSI_BOOTSTRAP_FD"#,
+    ]);
+
+    unsafe {
+        command.pre_exec(move || {
+            environment.apply()?;
+            handoff.apply()
+        });
+    }
+
+    let output = command.output().unwrap();
+
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+
+    assert_eq!(lines.next(), Some("test unset"));
+    assert_eq!(Bootstrap::parse(lines.next().unwrap()).unwrap(), expected);
+}
+
+#[test]
+fn bootstrap_file() {
+    let dir = setup().unwrap();
+
+    let expected = sample();
+
+    let path = dir.join("bootstrap.json");
+
+    std::fs::write(&path, expected.to_json().unwrap()).unwrap();
+
+    std::env::set_var(BOOTSTRAP_FILE, &path);
+
+    assert_eq!(Bootstrap::load().unwrap(), expected);
+
+    std::env::remove_var(BOOTSTRAP_FILE);
+
+    assert!(Bootstrap::parse("{}").is_err());
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use common::setup;
+use cosi::unix::process::cgroup::{Cgroup, Resources};
+use cosi::unix::process::tree::Tracker;
+use nix::unistd::Pid;
+use std::fs;
+use std::time::Duration;
+
+#[test]
+fn cgroup() {
+    // N.B.: A fake cgroupfs, the files are written as regular files.
+    let root = setup().unwrap();
+
+    fs::write(root.join("cgroup.controllers"), "cpu memory pids").unwrap();
+
+    let resources = Resources {
+        memory_max: Some(64 * 1024 * 1024),
+        cpu_weight: Some(50),
+        pids_max: Some(32),
+    };
+
+    // The services' cgroups are created under a root of their own, which the engine creates as
+    // well.
+    let cgroup = Cgroup::new(root.join("cosi"), "test");
+
+    cgroup.create(&resources).unwrap();
+
+    let read = |file: &str| fs::read_to_string(cgroup.path().join(file)).unwrap();
+
+    // The controllers are enabled in every ancestor, from the top of the hierarchy down.
+    for ancestor in &[root.clone(), root.join("cosi")] {
+        assert_eq!(
+            fs::read_to_string(ancestor.join("cgroup.subtree_control")).unwrap(),
+            "+cpu +memory +pids"
+        );
+    }
+
+    // Controllers that are already enabled are left alone.
+    fs::write(root.join("cosi/cgroup.controllers"), "cpu memory pids").unwrap();
+    fs::write(root.join("cgroup.subtree_control"), "cpu memory").unwrap();
+
+    Cgroup::new(root.join("cosi"), "other")
+        .create(&resources)
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(root.join("cgroup.subtree_control")).unwrap(),
+        "+pids"
+    );
+    assert_eq!(read("memory.max"), "67108864");
+    assert_eq!(read("cpu.weight"), "50");
+    assert_eq!(read("pids.max"), "32");
+
+    cgroup.add(Pid::from_raw(42)).unwrap();
+
+    assert_eq!(cgroup.procs().unwrap(), vec![Pid::from_raw(42)]);
+
+    cgroup.prepare().unwrap().apply().unwrap();
+
+    assert_eq!(read("cgroup.procs"), std::process::id().to_string());
+
+    fs::write(cgroup.path().join("memory.current"), "1024\n").unwrap();
+    fs::write(
+        cgroup.path().join("memory.events"),
+        "low 0\nhigh 0\nmax 3\noom 1\noom_kill 1\n",
+    )
+    .unwrap();
+    fs::write(
+        cgroup.path().join("cpu.stat"),
+        "usage_usec 3000\nuser_usec 2000\nsystem_usec 1000\n",
+    )
+    .unwrap();
+    fs::write(cgroup.path().join("pids.current"), "2\n").unwrap();
+
+    let usage = cgroup.usage().unwrap();
+
+    assert_eq!(usage.memory_current, Some(1024));
+    assert_eq!(usage.memory_peak, None);
+    assert_eq!(usage.oom_kills, Some(1));
+    assert_eq!(usage.cpu_usage, Some(Duration::from_millis(3)));
+    assert_eq!(usage.cpu_user, Some(Duration::from_millis(2)));
+    assert_eq!(usage.cpu_system, Some(Duration::from_millis(1)));
+    assert_eq!(usage.pids_current, Some(2));
+
+    // The usage of a service is read through the tracker of the engine.
+    let tracker = Tracker::default();
+
+    tracker.track_cgroup("test", cgroup);
+
+    assert_eq!(tracker.accounted(), vec![String::from("test")]);
+    assert_eq!(tracker.usage("test").unwrap(), Some(usage.clone()));
+    assert_eq!(tracker.usage("other").unwrap(), None);
+    assert_eq!(
+        usage.to_string(),
+        "memory=1024 oom_kills=1 pids=2 cpu=3ms cpu_user=2ms cpu_system=1ms"
+    );
+}
+
+#[test]
+fn cgroup_outside_of_hierarchy() {
+    let root = setup().unwrap();
+
+    let cgroup = Cgroup::new(&root, "test");
+
+    assert!(cgroup.create(&Resources::default()).is_err());
+    assert!(!cgroup.path().exists());
+}
@@ -0,0 +1,273 @@
+mod common;
+
+use common::setup;
+use cosi::bootstrap::{Bootstrap, Tokens};
+use cosi::controller::{Context, Controller, Error, Runner};
+use cosi::spec::resource::{Metadata, Resource};
+use cosi::spec::runtime::controller_adapter_server::{ControllerAdapter, ControllerAdapterServer};
+use cosi::spec::runtime::controller_runtime_server::{ControllerRuntime, ControllerRuntimeServer};
+use cosi::spec::runtime::*;
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tonic::{transport::Server, Request, Response, Status};
+
+// A runtime that sends two reconcile events and lists a single resource.
+#[derive(Default, Clone)]
+struct FakeRuntime {
+    started: Arc<AtomicUsize>,
+}
+
+#[tonic::async_trait]
+impl ControllerRuntime for FakeRuntime {
+    async fn register_controller(
+        &self,
+        request: Request<RegisterControllerRequest>,
+    ) -> Result<Response<RegisterControllerResponse>, Status> {
+        Ok(Response::new(RegisterControllerResponse {
+            controller_token: format!("token-{}", request.into_inner().controller_name),
+        }))
+    }
+
+    async fn start(&self, _: Request<StartRequest>) -> Result<Response<StartResponse>, Status> {
+        self.started.fetch_add(1, Ordering::SeqCst);
+
+        Ok(Response::new(StartResponse {}))
+    }
+
+    async fn stop(&self, _: Request<StopRequest>) -> Result<Response<StopResponse>, Status> {
+        Ok(Response::new(StopResponse {}))
+    }
+}
+
+type Stream_<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + Sync + 'static>>;
+
+#[tonic::async_trait]
+impl ControllerAdapter for FakeRuntime {
+    type ReconcileEventsStream = Stream_<ReconcileEventsResponse>;
+
+    async fn reconcile_events(
+        &self,
+        request: Request<ReconcileEventsRequest>,
+    ) -> Result<Response<Self::ReconcileEventsStream>, Status> {
+        assert_eq!(request.into_inner().controller_token, "token-test");
+
+        let events = vec![
+            Ok(ReconcileEventsResponse {}),
+            Ok(ReconcileEventsResponse {}),
+        ];
+
+        let stream = futures::stream::iter(events).chain(futures::stream::pending());
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn queue_reconcile(
+        &self,
+        _: Request<QueueReconcileRequest>,
+    ) -> Result<Response<QueueReconcileResponse>, Status> {
+        Err(Status::unimplemented("queue_reconcile"))
+    }
+
+    async fn update_inputs(
+        &self,
+        _: Request<UpdateInputsRequest>,
+    ) -> Result<Response<UpdateInputsResponse>, Status> {
+        Err(Status::unimplemented("update_inputs"))
+    }
+
+    async fn get(
+        &self,
+        _: Request<RuntimeGetRequest>,
+    ) -> Result<Response<RuntimeGetResponse>, Status> {
+        Err(Status::not_found("get"))
+    }
+
+    type ListStream = Stream_<RuntimeListResponse>;
+
+    async fn list(
+        &self,
+        _: Request<RuntimeListRequest>,
+    ) -> Result<Response<Self::ListStream>, Status> {
+        let resource = Resource {
+            metadata: Some(Metadata {
+                namespace: String::from("system"),
+                r#type: String::from("Test"),
+                id: String::from("test"),
+                ..Default::default()
+            }),
+            spec: None,
+            status: None,
+        };
+
+        let responses = vec![Ok(RuntimeListResponse {
+            resource: Some(resource),
+        })];
+
+        Ok(Response::new(Box::pin(futures::stream::iter(responses))))
+    }
+
+    async fn watch_for(
+        &self,
+        _: Request<RuntimeWatchForRequest>,
+    ) -> Result<Response<RuntimeWatchForResponse>, Status> {
+        Err(Status::unimplemented("watch_for"))
+    }
+
+    async fn create(
+        &self,
+        _: Request<RuntimeCreateRequest>,
+    ) -> Result<Response<RuntimeCreateResponse>, Status> {
+        Err(Status::unimplemented("create"))
+    }
+
+    async fn update(
+        &self,
+        _: Request<RuntimeUpdateRequest>,
+    ) -> Result<Response<RuntimeUpdateResponse>, Status> {
+        Err(Status::unimplemented("update"))
+    }
+
+    async fn update_status(
+        &self,
+        _: Request<RuntimeUpdateStatusRequest>,
+    ) -> Result<Response<RuntimeUpdateStatusResponse>, Status> {
+        Err(Status::unimplemented("update_status"))
+    }
+
+    async fn teardown(
+        &self,
+        _: Request<RuntimeTeardownRequest>,
+    ) -> Result<Response<RuntimeTeardownResponse>, Status> {
+        Err(Status::unimplemented("teardown"))
+    }
+
+    async fn destroy(
+        &self,
+        _: Request<RuntimeDestroyRequest>,
+    ) -> Result<Response<RuntimeDestroyResponse>, Status> {
+        Err(Status::unimplemented("destroy"))
+    }
+
+    async fn add_finalizer(
+        &self,
+        _: Request<RuntimeAddFinalizerRequest>,
+    ) -> Result<Response<RuntimeAddFinalizerResponse>, Status> {
+        Err(Status::unimplemented("add_finalizer"))
+    }
+
+    async fn remove_finalizer(
+        &self,
+        _: Request<RuntimeRemoveFinalizerRequest>,
+    ) -> Result<Response<RuntimeRemoveFinalizerResponse>, Status> {
+        Err(Status::unimplemented("remove_finalizer"))
+    }
+}
+
+// A controller that fails its first reconcile, and reports every successful one.
+struct TestController {
+    attempts: usize,
+    done: Option<oneshot::Sender<()>>,
+    reconciled: Arc<AtomicUsize>,
+}
+
+#[tonic::async_trait]
+impl Controller for TestController {
+    fn name(&self) -> String {
+        String::from("test")
+    }
+
+    fn inputs(&self) -> Vec<ControllerInput> {
+        vec![]
+    }
+
+    fn outputs(&self) -> Vec<ControllerOutput> {
+        vec![]
+    }
+
+    async fn reconcile(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        self.attempts += 1;
+
+        if self.attempts == 1 {
+            return Err(Error::from("first attempt"));
+        }
+
+        assert_eq!(ctx.token, "token-test");
+        assert_eq!(ctx.list("cosi.dev", "system", "Test").await?.len(), 1);
+        assert_eq!(
+            ctx.get("cosi.dev", "system", "Test", "missing").await?,
+            None
+        );
+
+        if self.reconciled.fetch_add(1, Ordering::SeqCst) + 1 == 2 {
+            if let Some(done) = self.done.take() {
+                let _ = done.send(());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn controller() {
+    let dir = setup().unwrap();
+
+    let engine_socket = dir.join("engine.sock").to_str().unwrap().to_owned();
+    let runtime_socket = dir.join("runtime.sock").to_str().unwrap().to_owned();
+
+    let e = engine_socket.clone();
+
+    let tokens = Tokens::default();
+    tokens.issue("test", "token");
+
+    tokio::spawn(async {
+        cosi::machinery::engine::v1alpha1::EngineService::new(tokens)
+            .serve(e)
+            .await
+    });
+
+    let runtime = FakeRuntime::default();
+    let started = runtime.started.clone();
+
+    let incoming = cosi::unix::UnixIncoming::bind(runtime_socket.clone()).unwrap();
+
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(ControllerRuntimeServer::new(runtime.clone()))
+            .add_service(ControllerAdapterServer::new(runtime))
+            .serve_with_incoming(incoming)
+            .await
+    });
+
+    let mut runner = Runner::new(Bootstrap {
+        engine_socket,
+        runtime_socket,
+        runtime_address: None,
+        service: String::from("test"),
+        token: String::from("token"),
+    });
+
+    runner.backoff = Duration::from_millis(10);
+
+    let (tx, rx) = oneshot::channel();
+    let reconciled = Arc::new(AtomicUsize::new(0));
+
+    let controller = TestController {
+        attempts: 0,
+        done: Some(tx),
+        reconciled: reconciled.clone(),
+    };
+
+    let shutdown = async {
+        let _ = rx.await;
+    };
+
+    runner.run_until(controller, shutdown).await.unwrap();
+
+    assert_eq!(reconciled.load(Ordering::SeqCst), 2);
+    assert_eq!(started.load(Ordering::SeqCst), 1);
+}
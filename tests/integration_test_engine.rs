@@ -1,7 +1,9 @@
 mod common;
 
 use common::setup;
+use cosi::bootstrap::{Bootstrap, Tokens};
 use cosi::machinery::plugin;
+use cosi::spec::engine::Schema;
 use cosi::spec::resource::{Metadata, Resource, Spec};
 use cosi::spec::runtime::{
     ControllerOutput, ControllerOutputKind, RegisterControllerRequest, RuntimeCreateRequest,
@@ -9,6 +11,7 @@ use cosi::spec::runtime::{
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
 use tokio::runtime::Runtime;
+use tonic::Code;
 
 #[test]
 fn engine() {
@@ -29,10 +32,13 @@ fn engine() {
 
         // Start the engine.
 
+        let tokens = Tokens::default();
+        tokens.issue("test", "test");
+
         tokio::spawn(async {
             println!("Engine: {}", e1);
 
-            let service = cosi::machinery::engine::v1alpha1::EngineService::default();
+            let service = cosi::machinery::engine::v1alpha1::EngineService::new(tokens);
             service.serve(e1).await;
 
             panic!("expected engine server to not fail")
@@ -56,7 +62,15 @@ fn engine() {
         tokio::spawn(async {
             std::thread::sleep(std::time::Duration::from_millis(500));
 
-            let result = plugin::register(engine_socket, String::from("test")).await;
+            let bootstrap = Bootstrap {
+                engine_socket,
+                runtime_socket: String::new(),
+                runtime_address: None,
+                service: String::from("test"),
+                token: String::from("test"),
+            };
+
+            let result = plugin::register(&bootstrap, String::from("test")).await;
 
             assert!(result.is_ok())
         });
@@ -75,6 +89,7 @@ fn engine() {
                 outputs: vec![ControllerOutput {
                     r#type: String::from("TestStatus"),
                     kind: ControllerOutputKind::Shared as i32,
+                    api: String::from("cosi.dev"),
                 }],
                 inputs: vec![],
             };
@@ -106,11 +121,14 @@ fn engine() {
                         phase: String::from("running"),
                         finalizers: vec![],
                         r#type: String::from("Test"),
+                        api: String::from("cosi.dev"),
+                        ..Default::default()
                     }),
                     spec: Some(Spec {
                         proto_spec: vec![],
                         yaml_spec: String::from(""),
                     }),
+                    status: None,
                 }),
             };
 
@@ -125,3 +143,62 @@ fn engine() {
 
     rx.recv().unwrap();
 }
+
+#[tokio::test]
+async fn engine_token() {
+    let dir = setup().unwrap();
+
+    let socket = dir.join("engine.sock").to_str().unwrap().to_owned();
+
+    let tokens = Tokens::default();
+    tokens.issue("test", "token");
+
+    let s = socket.clone();
+    let t = tokens.clone();
+
+    tokio::spawn(async {
+        cosi::machinery::engine::v1alpha1::EngineService::new(t)
+            .serve(s)
+            .await
+    });
+
+    tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+
+    let mut bootstrap = Bootstrap {
+        engine_socket: socket.clone(),
+        runtime_socket: String::new(),
+        runtime_address: None,
+        service: String::from("test"),
+        token: String::from("wrong"),
+    };
+
+    let status = plugin::register(&bootstrap, String::from("test"))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    // Requests without a token are rejected as well.
+    let mut engine = cosi::machinery::engine::client::connect(socket)
+        .await
+        .unwrap();
+
+    let status = engine.register_schema(Schema::default()).await.unwrap_err();
+
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    bootstrap.token = String::from("token");
+
+    plugin::register(&bootstrap, String::from("test"))
+        .await
+        .unwrap();
+
+    // A restarted service is issued a new token, and the previous one is no longer accepted.
+    tokens.issue("test", "restarted");
+
+    let status = plugin::register(&bootstrap, String::from("other"))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
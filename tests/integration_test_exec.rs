@@ -0,0 +1,79 @@
+mod common;
+
+use common::setup;
+use cosi::unix::process::service::Service;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+#[test]
+fn exec() {
+    let dir = setup().unwrap();
+
+    let definition = format!(
+        r#"
+exec:
+  environment:
+    GREETING: hello
+  clear_environment: true
+  working_directory: {}
+  umask: 0o027
+  limits:
+    nofile: 512
+    core:
+      soft: 0
+      hard: unlimited
+"#,
+        dir.display()
+    );
+
+    let service: Service = serde_yaml::from_str(&definition).unwrap();
+
+    let mut environment = service.exec.environment().prepare().unwrap();
+    let exec = service.exec.prepare().unwrap();
+
+    let mut command = Command::new("/bin/sh");
+
+    command.args(&[
+        "-c",
+        "echo $GREETING; echo ${HOME:-unset}; pwd; umask; ulimit -n; ulimit -Sc; ulimit -Hc",
+    ]);
+
+    service.exec.configure(&mut command);
+
+    unsafe {
+        command.pre_exec(move || {
+            environment.apply()?;
+            exec.apply()
+        });
+    }
+
+    let output = command.output().unwrap();
+
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    assert_eq!(
+        lines,
+        vec![
+            "hello",
+            "unset",
+            dir.to_str().unwrap(),
+            "0027",
+            "512",
+            "0",
+            "unlimited"
+        ]
+    );
+}
+
+#[test]
+fn exec_rejects_invalid_limits() {
+    assert!(serde_yaml::from_str::<Service>("exec: {limits: {nofile: -1}}").is_err());
+    assert!(serde_yaml::from_str::<Service>("exec: {limits: {nofile: lots}}").is_err());
+
+    let service: Service = serde_yaml::from_str("exec: {oom_score_adjust: 2000}").unwrap();
+
+    assert!(service.exec.prepare().is_err());
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use common::setup;
+use cosi::spec::engine::{File, FileOriginal};
+use cosi::unix::file::{self, FileError};
+use nix::unistd::{getgid, getuid};
+use std::fs::{self, Permissions};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+
+fn spec(path: &Path, content: &str, mode: u32, append: bool) -> File {
+    File {
+        path: path.display().to_string(),
+        content: content.to_owned(),
+        base64_content: String::new(),
+        mode,
+        uid: getuid().as_raw(),
+        gid: getgid().as_raw(),
+        append,
+    }
+}
+
+#[test]
+fn file_contents() {
+    for path in &["etc/hosts", "/etc/../etc/hosts", "/etc/./hosts", "/"] {
+        let spec = File {
+            path: path.to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            file::validate(&spec),
+            Err(FileError::InvalidPath(path.to_string()))
+        );
+    }
+
+    let original = FileOriginal {
+        existed: true,
+        base64_content: base64::encode("127.0.0.1 localhost\n"),
+        ..Default::default()
+    };
+
+    let mut spec = File {
+        path: String::from("/etc/hosts"),
+        base64_content: base64::encode("10.0.0.1 gateway\n"),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        file::contents(&spec, &original).unwrap(),
+        b"10.0.0.1 gateway\n"
+    );
+
+    spec.append = true;
+
+    assert_eq!(
+        file::contents(&spec, &original).unwrap(),
+        b"127.0.0.1 localhost\n10.0.0.1 gateway\n"
+    );
+
+    // Without an original file, there is nothing to append to.
+    assert_eq!(
+        file::contents(&spec, &FileOriginal::default()).unwrap(),
+        b"10.0.0.1 gateway\n"
+    );
+
+    spec.content = String::from("10.0.0.1 gateway\n");
+
+    assert_eq!(
+        file::contents(&spec, &original),
+        Err(FileError::AmbiguousContent(String::from("/etc/hosts")))
+    );
+
+    spec.content = String::new();
+    spec.base64_content = String::from("not base64!");
+
+    assert_eq!(
+        file::contents(&spec, &original),
+        Err(FileError::InvalidBase64(String::from("/etc/hosts")))
+    );
+}
+
+#[test]
+fn file_apply() {
+    let root = setup().unwrap();
+
+    // A file that did not exist is created along with its directories, and removed on restore.
+    let path = root.join("containerd/config.toml");
+    let desired = spec(&path, "version = 2\n", 0o600, false);
+    let original = file::original(&path).unwrap();
+
+    assert!(!original.existed);
+    assert!(file::apply(&desired, &original).unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "version = 2\n");
+    assert_eq!(fs::metadata(&path).unwrap().mode() & 0o7777, 0o600);
+
+    // A file that is in sync is left alone, and drift is corrected.
+    assert!(!file::apply(&desired, &original).unwrap());
+
+    fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+
+    assert!(!file::in_sync(&desired, &original).unwrap());
+    assert!(file::apply(&desired, &original).unwrap());
+    assert!(file::in_sync(&desired, &original).unwrap());
+
+    file::restore(&path, &original).unwrap();
+
+    assert!(!path.exists());
+
+    // A file that existed is appended to, and restored with its contents and mode.
+    let path = root.join("hosts");
+
+    fs::write(&path, "127.0.0.1 localhost\n").unwrap();
+    fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+
+    let desired = spec(&path, "10.0.0.1 gateway\n", 0, true);
+    let original = file::original(&path).unwrap();
+
+    assert!(file::apply(&desired, &original).unwrap());
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "127.0.0.1 localhost\n10.0.0.1 gateway\n"
+    );
+    assert_eq!(fs::metadata(&path).unwrap().mode() & 0o7777, 0o644);
+
+    // The contents are appended to the original contents, not to those already appended.
+    assert!(!file::apply(&desired, &original).unwrap());
+
+    file::restore(&path, &original).unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "127.0.0.1 localhost\n");
+    assert_eq!(fs::metadata(&path).unwrap().mode() & 0o7777, 0o640);
+}
+
+#[test]
+fn file_symlink() {
+    let root = setup().unwrap();
+
+    // A link is replaced by the file, and restored as a link to the file it pointed to, which is
+    // left alone.
+    let target = root.join("stub-resolv.conf");
+    let path = root.join("resolv.conf");
+
+    fs::write(&target, "nameserver 127.0.0.53\n").unwrap();
+    std::os::unix::fs::symlink("stub-resolv.conf", &path).unwrap();
+
+    let desired = spec(&path, "nameserver 10.0.0.1\n", 0, true);
+    let original = file::original(&path).unwrap();
+
+    assert!(original.existed);
+    assert_eq!(original.link, "stub-resolv.conf");
+
+    assert!(file::apply(&desired, &original).unwrap());
+    assert!(!fs::symlink_metadata(&path)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "nameserver 127.0.0.53\nnameserver 10.0.0.1\n"
+    );
+    assert_eq!(
+        fs::read_to_string(&target).unwrap(),
+        "nameserver 127.0.0.53\n"
+    );
+
+    file::restore(&path, &original).unwrap();
+
+    assert_eq!(fs::read_link(&path).unwrap(), Path::new("stub-resolv.conf"));
+
+    // A dangling link is recorded and restored as well.
+    fs::remove_file(&target).unwrap();
+
+    let original = file::original(&path).unwrap();
+
+    assert!(original.existed);
+    assert_eq!(original.base64_content, "");
+
+    assert!(file::apply(&spec(&path, "nameserver 10.0.0.1\n", 0, false), &original).unwrap());
+
+    file::restore(&path, &original).unwrap();
+
+    assert_eq!(fs::read_link(&path).unwrap(), Path::new("stub-resolv.conf"));
+}
@@ -0,0 +1,226 @@
+use cosi::bootstrap::Tokens;
+use cosi::machinery::state::{Registry, StateService, FINALIZER_FOREGROUND, PHASE_TEARING_DOWN};
+use cosi::resource::ResourceKind;
+use cosi::spec::engine::Mount;
+use cosi::spec::resource::{
+    state_server::State, CreateRequest, DestroyOptions, DestroyRequest, EventType, GetRequest,
+    Metadata, OwnerReference, Propagation, Resource, Spec, UpdateRequest, WatchOptions,
+    WatchRequest,
+};
+use futures::StreamExt;
+use tonic::{Code, Request};
+
+fn reference(id: &str) -> OwnerReference {
+    OwnerReference {
+        namespace: String::from("system"),
+        r#type: String::from("Mount"),
+        id: id.to_owned(),
+        api: String::new(),
+    }
+}
+
+fn mount(id: &str, owners: &[&str], finalizers: &[&str]) -> Resource {
+    Resource {
+        metadata: Some(Metadata {
+            namespace: String::from("system"),
+            r#type: String::from("Mount"),
+            id: id.to_owned(),
+            owner_references: owners.iter().map(|owner| reference(owner)).collect(),
+            finalizers: finalizers.iter().map(|f| f.to_string()).collect(),
+            ..Default::default()
+        }),
+        spec: Some(Spec::default()),
+        status: None,
+    }
+}
+
+async fn create(state: &StateService, resource: Resource) -> Result<(), tonic::Status> {
+    state
+        .create(Request::new(CreateRequest {
+            resource: Some(resource),
+            options: None,
+        }))
+        .await
+        .map(|_| ())
+}
+
+async fn get(state: &StateService, id: &str) -> Option<Metadata> {
+    let request = GetRequest {
+        namespace: String::from("system"),
+        r#type: String::from("Mount"),
+        id: id.to_owned(),
+        options: None,
+        api: String::new(),
+    };
+
+    state
+        .get(Request::new(request))
+        .await
+        .ok()
+        .and_then(|response| response.into_inner().resource)
+        .and_then(|resource| resource.metadata)
+}
+
+async fn destroy(state: &StateService, id: &str, propagation: Propagation) {
+    let request = DestroyRequest {
+        namespace: String::from("system"),
+        r#type: String::from("Mount"),
+        id: id.to_owned(),
+        options: Some(DestroyOptions {
+            owner: String::new(),
+            propagation: propagation as i32,
+        }),
+        api: String::new(),
+    };
+
+    state.destroy(Request::new(request)).await.unwrap();
+}
+
+// Removes the finalizers of a resource, as the controller that added them would.
+async fn finalize(state: &StateService, id: &str) {
+    let metadata = get(state, id).await.unwrap();
+
+    let mut resource = mount(id, &[], &[]);
+
+    resource.metadata = Some(Metadata {
+        finalizers: vec![],
+        ..metadata.clone()
+    });
+
+    state
+        .update(Request::new(UpdateRequest {
+            current_version: metadata.version,
+            new_resource: Some(resource),
+            options: None,
+        }))
+        .await
+        .unwrap();
+}
+
+fn service() -> StateService {
+    let registry = Registry::default();
+
+    registry.register(&Mount::schema()).unwrap();
+
+    StateService::new(registry, Tokens::default())
+}
+
+#[tokio::test]
+async fn gc_background() {
+    let state = service();
+
+    let status = create(&state, mount("orphan", &["missing"], &[]))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    create(&state, mount("root", &[], &[])).await.unwrap();
+    create(&state, mount("child", &["root"], &[]))
+        .await
+        .unwrap();
+    create(&state, mount("grandchild", &["child"], &[]))
+        .await
+        .unwrap();
+    create(&state, mount("finalized", &["root"], &["test"]))
+        .await
+        .unwrap();
+    create(&state, mount("shared", &["root", "other"], &[]))
+        .await
+        .unwrap_err();
+    create(&state, mount("other", &[], &[])).await.unwrap();
+    create(&state, mount("shared", &["root", "other"], &[]))
+        .await
+        .unwrap();
+
+    destroy(&state, "root", Propagation::Background).await;
+
+    for id in &["root", "child", "grandchild"] {
+        assert_eq!(get(&state, id).await, None, "{}", id);
+    }
+
+    // A dependent with another owner is kept.
+    assert_eq!(get(&state, "shared").await.unwrap().phase, "running");
+
+    // A dependent with finalizers is torn down, and destroyed once they are removed.
+    assert_eq!(
+        get(&state, "finalized").await.unwrap().phase,
+        PHASE_TEARING_DOWN
+    );
+
+    finalize(&state, "finalized").await;
+
+    assert_eq!(get(&state, "finalized").await, None);
+
+    destroy(&state, "other", Propagation::Background).await;
+
+    assert_eq!(get(&state, "shared").await, None);
+}
+
+#[tokio::test]
+async fn gc_foreground() {
+    let state = service();
+
+    let watch = WatchRequest {
+        namespace: String::from("system"),
+        r#type: String::from("Mount"),
+        id: None,
+        options: Some(WatchOptions::default()),
+        api: String::new(),
+    };
+
+    let events = state
+        .watch(Request::new(watch))
+        .await
+        .unwrap()
+        .into_inner()
+        .map(|response| {
+            let event = response.unwrap().event.unwrap();
+            let id = event.resource.unwrap().metadata.unwrap().id;
+
+            (id, EventType::from_i32(event.event_type).unwrap())
+        });
+
+    create(&state, mount("root", &[], &[])).await.unwrap();
+    create(&state, mount("child", &["root"], &["test"]))
+        .await
+        .unwrap();
+    create(&state, mount("grandchild", &["child"], &[]))
+        .await
+        .unwrap();
+
+    destroy(&state, "root", Propagation::Foreground).await;
+
+    // The owner waits for its dependents, which wait for theirs and for their finalizers.
+    let root = get(&state, "root").await.unwrap();
+
+    assert_eq!(root.phase, PHASE_TEARING_DOWN);
+    assert_eq!(root.finalizers, vec![FINALIZER_FOREGROUND]);
+
+    let child = get(&state, "child").await.unwrap();
+
+    assert_eq!(child.phase, PHASE_TEARING_DOWN);
+    assert_eq!(child.finalizers, vec!["test"]);
+
+    assert_eq!(get(&state, "grandchild").await, None);
+
+    let status = create(&state, mount("late", &["root"], &[]))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    finalize(&state, "child").await;
+
+    assert_eq!(get(&state, "child").await, None);
+    assert_eq!(get(&state, "root").await, None);
+
+    let destroyed: Vec<String> = events
+        .filter(|(_, event_type)| futures::future::ready(*event_type == EventType::Destroyed))
+        .map(|(id, _)| id)
+        .take(3)
+        .collect()
+        .await;
+
+    assert_eq!(destroyed, vec!["grandchild", "child", "root"]);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::{in_namespace, setup};
+use cosi::spec::engine::Hostname;
+use cosi::unix::hostname::{self, HostnameError};
+use nix::sched::CloneFlags;
+use std::fs;
+use std::path::Path;
+
+fn spec(hostname: &str, domainname: &str) -> Hostname {
+    Hostname {
+        hostname: hostname.to_owned(),
+        domainname: domainname.to_owned(),
+    }
+}
+
+#[test]
+fn hostname_resolve() {
+    assert_eq!(
+        hostname::resolve(&spec("node1", "")).unwrap(),
+        (String::from("node1"), String::new())
+    );
+    assert_eq!(
+        hostname::resolve(&spec("node1.cosi.dev", "")).unwrap(),
+        (String::from("node1"), String::from("cosi.dev"))
+    );
+    assert_eq!(
+        hostname::resolve(&spec("node1.cosi.dev", "nis.cosi.dev")).unwrap(),
+        (String::from("node1"), String::from("nis.cosi.dev"))
+    );
+
+    for name in &["", "-node1", "node_1", "node1..cosi.dev"] {
+        assert_eq!(
+            hostname::resolve(&spec(name, "")),
+            Err(HostnameError::InvalidHostname(name.to_string()))
+        );
+    }
+
+    assert_eq!(
+        hostname::resolve(&spec("node1", "cosi.dev.")),
+        Err(HostnameError::InvalidDomainname(String::from("cosi.dev.")))
+    );
+
+    // Other entries are kept, and a previous entry of the hostname is replaced.
+    let hosts = "127.0.0.1\tlocalhost\n127.0.1.1\told\n10.0.0.1 gateway\n";
+
+    assert_eq!(
+        hostname::render_hosts(hosts, "node1", "cosi.dev"),
+        "127.0.0.1\tlocalhost\n10.0.0.1 gateway\n127.0.1.1\tnode1.cosi.dev node1\n"
+    );
+    assert_eq!(
+        hostname::render_hosts("", "node1", ""),
+        "127.0.0.1\tlocalhost\n::1\tlocalhost ip6-localhost ip6-loopback\n127.0.1.1\tnode1\n"
+    );
+}
+
+fn set(root: &Path) -> Result<(), String> {
+    let (hostname, domainname) =
+        hostname::resolve(&spec("node1.cosi.dev", "")).map_err(|err| err.to_string())?;
+
+    hostname::set(&hostname, &domainname).map_err(|err| err.to_string())?;
+
+    let current = hostname::current().map_err(|err| err.to_string())?;
+
+    if current != (hostname.clone(), domainname.clone()) {
+        return Err(format!("unexpected names: {:?}", current));
+    }
+
+    // An empty domain name leaves the current one alone.
+    hostname::set("node2", "").map_err(|err| err.to_string())?;
+
+    let current = hostname::current().map_err(|err| err.to_string())?;
+
+    if current != (String::from("node2"), domainname.clone()) {
+        return Err(format!("unexpected names: {:?}", current));
+    }
+
+    let written = hostname::sync(root, "node2", &domainname).map_err(|err| err.to_string())?;
+
+    if written != vec![root.join("hostname"), root.join("hosts")] {
+        return Err(format!("unexpected files: {:?}", written));
+    }
+
+    let contents = fs::read_to_string(root.join("hostname")).map_err(|err| err.to_string())?;
+
+    if contents != "node2\n" {
+        return Err(format!("unexpected hostname: {:?}", contents));
+    }
+
+    // Files that are in sync are left alone.
+    let written = hostname::sync(root, "node2", &domainname).map_err(|err| err.to_string())?;
+
+    if !written.is_empty() {
+        return Err(format!("unexpected files: {:?}", written));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn hostname_set() {
+    let root = setup().unwrap();
+
+    in_namespace(CloneFlags::CLONE_NEWUTS, || set(&root));
+}
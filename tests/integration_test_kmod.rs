@@ -0,0 +1,195 @@
+mod common;
+
+use common::setup;
+use cosi::spec::engine::KernelModule;
+use cosi::unix::kmod::{self, Action, Index, KmodError, Loader, Modules};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+static MODULES_DEP: &str = "\
+kernel/fs/fuse/fuse.ko:
+kernel/net/netfilter/nf_conntrack.ko: kernel/net/ipv6/netfilter/nf_defrag_ipv6.ko kernel/net/ipv4/netfilter/nf_defrag_ipv4.ko kernel/lib/libcrc32c.ko
+kernel/net/ipv6/netfilter/nf_defrag_ipv6.ko: kernel/lib/libcrc32c.ko
+kernel/net/ipv4/netfilter/nf_defrag_ipv4.ko:
+kernel/lib/libcrc32c.ko:
+kernel/drivers/block/loop.ko.xz:
+";
+
+// FakeLoader records the calls made to it instead of loading modules.
+#[derive(Default)]
+struct FakeLoader {
+    loaded: BTreeSet<String>,
+    calls: Vec<String>,
+    busy: BTreeSet<String>,
+}
+
+impl Loader for FakeLoader {
+    fn loaded(&self) -> io::Result<BTreeSet<String>> {
+        Ok(self.loaded.clone())
+    }
+
+    fn load(&mut self, path: &Path, parameters: &str) -> io::Result<()> {
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let name = name.split(".ko").next().unwrap().to_owned();
+
+        self.calls
+            .push(format!("load {} {}", name, parameters).trim().to_owned());
+        self.loaded.insert(name);
+
+        Ok(())
+    }
+
+    fn unload(&mut self, name: &str) -> io::Result<()> {
+        self.calls.push(format!("unload {}", name));
+
+        match self.busy.contains(name) {
+            true => Err(io::Error::from_raw_os_error(libc::EWOULDBLOCK)),
+            false => {
+                self.loaded.remove(name);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn module(name: &str, parameters: &[&str], blacklist: bool) -> KernelModule {
+    KernelModule {
+        name: name.to_owned(),
+        parameters: parameters.iter().map(|p| p.to_string()).collect(),
+        blacklist,
+    }
+}
+
+fn calls(modules: &mut Modules<FakeLoader>) -> Vec<String> {
+    std::mem::take(&mut modules.loader_mut().calls)
+}
+
+#[test]
+fn kmod_index() {
+    let index = Index::parse(MODULES_DEP, "kernel/drivers/char/mem.ko\n");
+
+    assert_eq!(
+        index.resolve("nf-conntrack").unwrap(),
+        vec![
+            "libcrc32c",
+            "nf_defrag_ipv4",
+            "nf_defrag_ipv6",
+            "nf_conntrack"
+        ]
+    );
+    assert_eq!(index.resolve("mem").unwrap(), vec!["mem"]);
+    assert_eq!(
+        index.path("loop"),
+        Some(Path::new("kernel/drivers/block/loop.ko.xz"))
+    );
+    assert!(matches!(
+        index.resolve("missing"),
+        Err(KmodError::UnknownModule(_))
+    ));
+
+    let modules = vec!["libcrc32c", "nf_conntrack", "nf_defrag_ipv6"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    assert_eq!(
+        index.unload_order(&modules),
+        vec!["nf_conntrack", "nf_defrag_ipv6", "libcrc32c"]
+    );
+
+    assert_eq!(
+        kmod::parameters(&[String::from("max_loop=8"), String::from("debug")]).unwrap(),
+        "max_loop=8 debug"
+    );
+    assert!(kmod::parameters(&[String::from("=8")]).is_err());
+
+    // Values with whitespace are quoted, so that they are not split into other parameters.
+    assert_eq!(
+        kmod::parameters(&[String::from("options=a b"), String::from("x=1\tdebug")]).unwrap(),
+        "options=\"a b\" x=\"1\tdebug\""
+    );
+    assert!(matches!(
+        kmod::parameters(&[String::from("options=\"a\" debug")]),
+        Err(KmodError::InvalidParameter(_))
+    ));
+    assert!(kmod::parameters(&[String::from("max loop=8")]).is_err());
+}
+
+#[test]
+fn kmod_converge() {
+    let root = setup().unwrap();
+
+    fs::write(root.join("modules.dep"), MODULES_DEP).unwrap();
+
+    let mut loader = FakeLoader::default();
+
+    // Modules loaded by someone else are left alone.
+    loader.loaded.insert(String::from("libcrc32c"));
+
+    let mut modules = Modules::new(root, loader);
+
+    let desired = vec![
+        module("nf_conntrack", &["hashsize=4096"], false),
+        module("loop", &["max_loop=8"], false),
+        module("fuse", &[], true),
+        module("missing", &[], false),
+    ];
+
+    let results = modules.converge(&desired).unwrap();
+
+    assert!(matches!(
+        &results[0],
+        (Action::Load(module), Err(KmodError::UnknownModule(_))) if module == "missing"
+    ));
+    assert_eq!(
+        calls(&mut modules),
+        vec![
+            "load nf_defrag_ipv4",
+            "load nf_defrag_ipv6",
+            "load nf_conntrack hashsize=4096",
+            "load loop max_loop=8",
+        ]
+    );
+
+    // Converged modules are left alone.
+    modules.converge(&desired).unwrap();
+
+    assert!(calls(&mut modules).is_empty());
+
+    // Modules that are no longer needed are unloaded before their dependencies, and a module
+    // that is blacklisted is unloaded and not loaded as a dependency.
+    modules.loader_mut().loaded.insert(String::from("fuse"));
+    modules.loader_mut().busy.insert(String::from("loop"));
+
+    let desired = vec![
+        module("nf_defrag_ipv6", &[], false),
+        module("nf_conntrack", &[], false),
+        module("nf_defrag_ipv4", &[], true),
+        module("fuse", &[], true),
+    ];
+
+    let results = modules.converge(&desired).unwrap();
+
+    assert!(matches!(
+        &results[0],
+        (Action::Load(module), Err(KmodError::Blacklisted(blacklisted)))
+            if module == "nf_conntrack" && blacklisted == "nf_defrag_ipv4"
+    ));
+    assert_eq!(
+        calls(&mut modules),
+        vec![
+            "unload fuse",
+            "unload loop",
+            "unload nf_conntrack",
+            "unload nf_defrag_ipv4",
+        ]
+    );
+
+    // A module that is busy is not retried.
+    modules.converge(&desired).unwrap();
+
+    assert!(calls(&mut modules).is_empty());
+    assert!(modules.loader().loaded.contains("loop"));
+}
@@ -0,0 +1,289 @@
+mod common;
+
+use common::{in_namespace, setup};
+use cosi::spec::engine::Mount;
+use cosi::unix::mount::{self, Action, MountInfo};
+use nix::mount::MsFlags;
+use nix::sched::CloneFlags;
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+#[test]
+fn mount_mountinfo() {
+    let mounts = mount::parse(
+        "22 1 0:21 / / rw,relatime shared:1 - ext4 /dev/sda1 rw\n\
+         36 22 0:32 / /mnt/with\\040space rw,nosuid - tmpfs tmpfs rw,size=1024k\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        mounts[1],
+        MountInfo {
+            id: 36,
+            parent: 22,
+            device: String::from("0:32"),
+            root: String::from("/"),
+            mount_point: String::from("/mnt/with space"),
+            options: vec![String::from("rw"), String::from("nosuid")],
+            fields: vec![],
+            fs_type: String::from("tmpfs"),
+            source: String::from("tmpfs"),
+            super_options: vec![String::from("rw"), String::from("size=1024k")],
+        }
+    );
+
+    assert_eq!(mounts[0].fields, vec!["shared:1"]);
+    assert_eq!(mount::find(&mounts, "/mnt/with space/").unwrap().id, 36);
+    assert!(mount::parse("22 1 0:21 / /").is_err());
+}
+
+#[test]
+fn mount_options() {
+    let options = mount::options(&[
+        String::from("ro"),
+        String::from("nosuid"),
+        String::from("size=64m"),
+        String::from("noexec"),
+        String::from("exec"),
+        String::from("mode=755"),
+    ]);
+
+    assert_eq!(options.flags, MsFlags::MS_RDONLY | MsFlags::MS_NOSUID);
+    assert_eq!(options.propagation, MsFlags::empty());
+    assert_eq!(options.data, "size=64m,mode=755");
+
+    let options = mount::options(&[
+        String::from("rbind"),
+        String::from("ro"),
+        String::from("rslave"),
+    ]);
+
+    assert_eq!(
+        options.flags,
+        MsFlags::MS_BIND | MsFlags::MS_REC | MsFlags::MS_RDONLY
+    );
+    assert_eq!(options.propagation, MsFlags::MS_SLAVE | MsFlags::MS_REC);
+    assert!(options.is_bind());
+}
+
+fn tmpfs(target: &str, options: &[&str]) -> Mount {
+    Mount {
+        source: String::from("tmpfs"),
+        target: target.to_owned(),
+        r#type: String::from("tmpfs"),
+        options: options.iter().map(|o| o.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+fn targets(mounts: &[&Mount]) -> Vec<String> {
+    mounts.iter().map(|m| m.target.clone()).collect()
+}
+
+#[test]
+fn mount_order() {
+    let log = Mount {
+        depends_on: vec![String::from("/data")],
+        ..tmpfs("/var/log", &[])
+    };
+
+    let desired = vec![
+        log.clone(),
+        tmpfs("/var/", &[]),
+        tmpfs("/data", &[]),
+        tmpfs("/variable", &[]),
+    ];
+
+    assert_eq!(
+        targets(&mount::order(&desired).unwrap()),
+        vec!["/data", "/var/", "/var/log", "/variable"]
+    );
+
+    let cycle = vec![
+        log,
+        Mount {
+            depends_on: vec![String::from("/var/log")],
+            ..tmpfs("/data", &[])
+        },
+    ];
+
+    assert!(mount::order(&cycle).is_err());
+    assert!(mount::order(&[tmpfs("/var", &[]), tmpfs("/var/", &[])]).is_err());
+}
+
+#[test]
+fn mount_plan() {
+    let mounted =
+        mount::parse("22 1 0:21 / / rw - ext4 /dev/sda1 rw\n23 22 0:22 / /tmp rw - tmpfs tmpfs rw")
+            .unwrap();
+
+    let managed: BTreeMap<String, Mount> = vec![tmpfs("/tmp", &[]), tmpfs("/run", &[])]
+        .into_iter()
+        .map(|m| (m.target.clone(), m))
+        .collect();
+
+    let desired = vec![
+        tmpfs("/var/lib", &[]),
+        tmpfs("/tmp/", &[]),
+        tmpfs("/var", &[]),
+    ];
+
+    assert_eq!(
+        mount::plan(&desired, &mounted, &managed).unwrap(),
+        vec![
+            Action::Mount(tmpfs("/var", &[])),
+            Action::Mount(tmpfs("/var/lib", &[]))
+        ]
+    );
+
+    assert_eq!(
+        mount::plan(&[], &mounted, &managed).unwrap(),
+        vec![Action::Unmount(String::from("/tmp"))]
+    );
+}
+
+// Converges on tmpfs and bind mounts in a private user and mount namespace.
+fn converge(dir: &Path) -> Result<(), String> {
+    let root = dir.to_str().unwrap();
+
+    mount::mount(&tmpfs(root, &[])).map_err(|err| err.to_string())?;
+
+    let data = format!("{}/data", root);
+    let log = format!("{}/data/var/log", root);
+    let config = format!("{}/config", root);
+
+    fs::write(format!("{}/source", root), "bound").map_err(|err| err.to_string())?;
+
+    let mut managed = BTreeMap::new();
+
+    let desired = vec![
+        Mount {
+            mode: 0o700,
+            ..tmpfs(&log, &["nosuid", "size=1m"])
+        },
+        Mount {
+            source: format!("{}/source", root),
+            r#type: String::new(),
+            depends_on: vec![log.clone()],
+            ..tmpfs(&config, &["bind", "ro", "private"])
+        },
+        tmpfs(&data, &[]),
+    ];
+
+    // The targets are created as needed, and mounted after their dependencies.
+    let results = mount::converge(&desired, &mut managed).map_err(|err| err.to_string())?;
+
+    let actions: Vec<Action> = results
+        .into_iter()
+        .map(|(action, result)| result.map(|_| action))
+        .collect::<Result<_, _>>()
+        .map_err(|err| err.to_string())?;
+
+    if actions
+        != vec![
+            Action::Mount(desired[2].clone()),
+            Action::Mount(desired[0].clone()),
+            Action::Mount(desired[1].clone()),
+        ]
+    {
+        return Err(format!("unexpected actions: {:?}", actions));
+    }
+
+    let mounted = mount::mountinfo().map_err(|err| err.to_string())?;
+
+    match mount::find(&mounted, &log) {
+        Some(info) if info.fs_type == "tmpfs" && info.options.contains(&"nosuid".to_owned()) => {}
+        info => return Err(format!("unexpected mount: {:?}", info)),
+    }
+
+    match mount::find(&mounted, &config) {
+        Some(info) if info.options.contains(&"ro".to_owned()) => {}
+        info => return Err(format!("unexpected mount: {:?}", info)),
+    }
+
+    // The directories are created on the data mount, with the mode of the target.
+    let mode = fs::metadata(format!("{}/var", data))
+        .map_err(|err| err.to_string())?
+        .permissions()
+        .mode();
+
+    if mode & 0o777 != 0o700 {
+        return Err(format!("unexpected mode: {:o}", mode));
+    }
+
+    if fs::read_to_string(&config).map_err(|err| err.to_string())? != "bound" {
+        return Err(String::from("unexpected bind mount"));
+    }
+
+    // Converged mounts are left alone.
+    let results = mount::converge(&desired, &mut managed).map_err(|err| err.to_string())?;
+
+    if !results.is_empty() {
+        return Err(format!("unexpected results: {:?}", results));
+    }
+
+    // Mounts are unmounted in reverse order, even when they are busy.
+    let busy = fs::File::open(&log).map_err(|err| err.to_string())?;
+
+    let results = mount::converge(&[], &mut managed).map_err(|err| err.to_string())?;
+
+    drop(busy);
+
+    let actions: Vec<Action> = results.into_iter().map(|(action, _)| action).collect();
+
+    if actions
+        != vec![
+            Action::Unmount(config.clone()),
+            Action::Unmount(log.clone()),
+            Action::Unmount(data.clone()),
+        ]
+    {
+        return Err(format!("unexpected actions: {:?}", actions));
+    }
+
+    let mounted = mount::mountinfo().map_err(|err| err.to_string())?;
+
+    for target in &[&data, &log, &config] {
+        if mount::find(&mounted, target).is_some() {
+            return Err(format!("{} is still mounted", target));
+        }
+    }
+
+    if !managed.is_empty() {
+        return Err(format!("unexpected managed mounts: {:?}", managed));
+    }
+
+    // A mount waits for dependencies that are not mounted.
+    let desired = vec![Mount {
+        depends_on: vec![String::from("/missing")],
+        ..tmpfs(&data, &[])
+    }];
+
+    let results = mount::converge(&desired, &mut managed).map_err(|err| err.to_string())?;
+
+    if results.len() != 1 || results[0].1.is_ok() {
+        return Err(format!("unexpected results: {:?}", results));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn mount_converge() {
+    let dir = setup().unwrap();
+
+    in_namespace(CloneFlags::CLONE_NEWNS, || {
+        nix::mount::mount(
+            None::<&str>,
+            "/",
+            None::<&str>,
+            MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+            None::<&str>,
+        )
+        .map_err(|err| err.to_string())?;
+
+        converge(&dir)
+    });
+}
@@ -0,0 +1,265 @@
+mod common;
+
+use common::{request, tokens};
+use cosi::machinery::state::{
+    Registry, StateService, PHASE_ACTIVE, PHASE_TEARING_DOWN, PHASE_TERMINATING,
+};
+use cosi::resource::ResourceKind;
+use cosi::spec::engine::Mount;
+use cosi::spec::resource::{
+    namespaces_server::Namespaces, state_server::State, CreateNamespaceRequest, CreateOptions,
+    CreateRequest, DestroyNamespaceRequest, GetRequest, ListNamespacesRequest, Metadata, Namespace,
+    Resource, Spec, UpdateRequest,
+};
+use tonic::{Code, Request};
+
+fn mount(namespace: &str, id: &str, finalizers: &[&str]) -> Resource {
+    Resource {
+        metadata: Some(Metadata {
+            namespace: namespace.to_owned(),
+            r#type: String::from("Mount"),
+            id: id.to_owned(),
+            finalizers: finalizers.iter().map(|f| f.to_string()).collect(),
+            ..Default::default()
+        }),
+        spec: Some(Spec::default()),
+        status: None,
+    }
+}
+
+async fn create(
+    state: &StateService,
+    resource: Resource,
+    service: &str,
+    owner: &str,
+) -> Result<(), Code> {
+    let message = CreateRequest {
+        resource: Some(resource),
+        options: Some(CreateOptions {
+            owner: owner.to_owned(),
+        }),
+    };
+
+    state
+        .create(request(service, message))
+        .await
+        .map(|_| ())
+        .map_err(|status| status.code())
+}
+
+async fn create_namespace(
+    state: &StateService,
+    service: &str,
+    namespace: Namespace,
+) -> Result<(), Code> {
+    let message = CreateNamespaceRequest {
+        namespace: Some(namespace),
+    };
+
+    state
+        .create_namespace(request(service, message))
+        .await
+        .map(|_| ())
+        .map_err(|status| status.code())
+}
+
+async fn destroy_namespace(state: &StateService, service: &str, name: &str) -> Result<(), Code> {
+    let message = DestroyNamespaceRequest {
+        name: name.to_owned(),
+    };
+
+    state
+        .destroy_namespace(request(service, message))
+        .await
+        .map(|_| ())
+        .map_err(|status| status.code())
+}
+
+async fn namespaces(state: &StateService) -> Vec<(String, String)> {
+    state
+        .list_namespaces(Request::new(ListNamespacesRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .namespaces
+        .into_iter()
+        .map(|namespace| (namespace.name, namespace.phase))
+        .collect()
+}
+
+async fn get(state: &StateService, namespace: &str, id: &str) -> Option<Metadata> {
+    let request = GetRequest {
+        namespace: namespace.to_owned(),
+        r#type: String::from("Mount"),
+        id: id.to_owned(),
+        options: None,
+        api: String::new(),
+    };
+
+    state
+        .get(Request::new(request))
+        .await
+        .ok()
+        .and_then(|response| response.into_inner().resource)
+        .and_then(|resource| resource.metadata)
+}
+
+fn namespace(name: &str) -> Namespace {
+    Namespace {
+        name: name.to_owned(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn namespace_policy() {
+    let registry = Registry::default();
+
+    registry.register(&Mount::schema()).unwrap();
+
+    let state = StateService::new(registry, tokens(&["disk", "other", "generator"]));
+
+    assert_eq!(
+        namespaces(&state).await,
+        vec![(String::from("system"), String::from(PHASE_ACTIVE))]
+    );
+
+    assert_eq!(
+        create_namespace(&state, "generator", namespace("Not_Valid")).await,
+        Err(Code::InvalidArgument)
+    );
+    assert_eq!(
+        create_namespace(&state, "generator", namespace("system")).await,
+        Err(Code::AlreadyExists)
+    );
+
+    assert_eq!(
+        create(&state, mount("missing", "tmp", &[]), "", "").await,
+        Err(Code::FailedPrecondition)
+    );
+
+    // A read-only namespace is only written by its writers.
+    let hardware = Namespace {
+        read_only: true,
+        writers: vec![String::from("disk")],
+        ..namespace("hardware")
+    };
+
+    create_namespace(&state, "generator", hardware)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        create(&state, mount("hardware", "sda", &[]), "", "").await,
+        Err(Code::PermissionDenied)
+    );
+    assert_eq!(
+        create(&state, mount("hardware", "sda", &[]), "other", "").await,
+        Err(Code::PermissionDenied)
+    );
+
+    // The writer is the service that makes the request, which cannot claim to be another.
+    assert_eq!(
+        create(&state, mount("hardware", "sda", &[]), "", "disk").await,
+        Err(Code::PermissionDenied)
+    );
+    assert_eq!(
+        create(&state, mount("hardware", "sda", &[]), "other", "disk").await,
+        Err(Code::PermissionDenied)
+    );
+    assert_eq!(
+        create(&state, mount("hardware", "sda", &[]), "disk", "").await,
+        Ok(())
+    );
+    assert_eq!(get(&state, "hardware", "sda").await.unwrap().owner, "disk");
+
+    // Only services may create and destroy namespaces.
+    assert_eq!(
+        create_namespace(&state, "", namespace("users")).await,
+        Err(Code::Unauthenticated)
+    );
+    assert_eq!(
+        destroy_namespace(&state, "", "hardware").await,
+        Err(Code::Unauthenticated)
+    );
+
+    assert_eq!(
+        destroy_namespace(&state, "generator", "system").await,
+        Err(Code::FailedPrecondition)
+    );
+    assert_eq!(
+        destroy_namespace(&state, "generator", "missing").await,
+        Err(Code::NotFound)
+    );
+}
+
+#[tokio::test]
+async fn namespace_cascade() {
+    let registry = Registry::default();
+
+    registry.register(&Mount::schema()).unwrap();
+
+    let state = StateService::new(registry, tokens(&["disk", "other", "generator"]));
+
+    create_namespace(&state, "generator", namespace("apps"))
+        .await
+        .unwrap();
+
+    create(&state, mount("apps", "data", &[]), "", "")
+        .await
+        .unwrap();
+    create(&state, mount("apps", "cache", &["test"]), "", "")
+        .await
+        .unwrap();
+    create(&state, mount("system", "tmp", &[]), "", "")
+        .await
+        .unwrap();
+
+    destroy_namespace(&state, "generator", "apps")
+        .await
+        .unwrap();
+
+    // The namespace terminates once the finalizers of its resources are removed.
+    assert_eq!(get(&state, "apps", "data").await, None);
+    assert_eq!(
+        get(&state, "apps", "cache").await.unwrap().phase,
+        PHASE_TEARING_DOWN
+    );
+    assert!(get(&state, "system", "tmp").await.is_some());
+
+    assert_eq!(
+        namespaces(&state).await,
+        vec![
+            (String::from("apps"), String::from(PHASE_TERMINATING)),
+            (String::from("system"), String::from(PHASE_ACTIVE)),
+        ]
+    );
+
+    assert_eq!(
+        create(&state, mount("apps", "late", &[]), "", "").await,
+        Err(Code::FailedPrecondition)
+    );
+
+    let metadata = get(&state, "apps", "cache").await.unwrap();
+
+    state
+        .update(Request::new(UpdateRequest {
+            current_version: metadata.version.clone(),
+            new_resource: Some(Resource {
+                metadata: Some(Metadata {
+                    finalizers: vec![],
+                    ..metadata
+                }),
+                ..mount("apps", "cache", &[])
+            }),
+            options: None,
+        }))
+        .await
+        .unwrap();
+
+    assert_eq!(get(&state, "apps", "cache").await, None);
+    assert_eq!(
+        namespaces(&state).await,
+        vec![(String::from("system"), String::from(PHASE_ACTIVE))]
+    );
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use common::in_namespace;
+use cosi::spec::engine::{Address, Link, Route};
+use cosi::unix::netlink::LinkInfo;
+use cosi::unix::network::{self, Action, Network, NetworkError};
+use nix::sched::CloneFlags;
+use std::net::{IpAddr, Ipv4Addr};
+
+fn link(name: &str, kind: &str, peer: &str, mtu: u32) -> Link {
+    Link {
+        name: name.to_owned(),
+        up: true,
+        mtu,
+        kind: kind.to_owned(),
+        peer: peer.to_owned(),
+    }
+}
+
+fn route(destination: &str, gateway: &str, link: &str) -> Route {
+    Route {
+        destination: destination.to_owned(),
+        gateway: gateway.to_owned(),
+        link: link.to_owned(),
+        metric: 0,
+    }
+}
+
+#[test]
+fn network_cidr() {
+    let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+    assert_eq!(network::cidr("10.1.0.1/24").unwrap(), (ip("10.1.0.1"), 24));
+    assert_eq!(network::cidr("fd00::1").unwrap(), (ip("fd00::1"), 128));
+    assert_eq!(
+        network::cidr("10.1.0.1/33"),
+        Err(NetworkError::InvalidAddress(String::from("10.1.0.1/33")))
+    );
+    assert!(network::cidr("10.1.0/24").is_err());
+
+    let links = vec![LinkInfo {
+        index: 2,
+        name: String::from("eth0"),
+        up: true,
+        mtu: 1500,
+        address: String::new(),
+        operational_state: String::new(),
+        kind: String::new(),
+    }];
+
+    let info = network::route_info(&route("", "10.1.0.254", ""), &links).unwrap();
+
+    assert_eq!(info.destination, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    assert_eq!(info.prefix, 0);
+    assert_eq!(info.gateway, Some(ip("10.1.0.254")));
+    assert_eq!(info.index, None);
+
+    assert_eq!(
+        network::route_info(&route("10.2.0.0/16", "", "eth0"), &links)
+            .unwrap()
+            .index,
+        Some(2)
+    );
+    assert_eq!(
+        network::route_info(&route("10.2.0.0/16", "", "eth1"), &links).unwrap_err(),
+        NetworkError::UnknownLink(String::from("eth1"))
+    );
+}
+
+fn converge() -> Result<(), String> {
+    let mut network = Network::new().map_err(|err| err.to_string())?;
+
+    let links = vec![
+        link("veth0", "veth", "veth1", 1400),
+        link("veth1", "", "", 0),
+    ];
+    let addresses = vec![Address {
+        link: String::from("veth0"),
+        address: String::from("10.1.0.1/24"),
+    }];
+    let routes = vec![route("10.2.0.0/16", "10.1.0.2", "")];
+
+    // The peer of a veth link is created along with it, and is only configured.
+    let results = network
+        .converge(&links, &addresses, &routes)
+        .map_err(|err| err.to_string())?;
+
+    let actions: Vec<Action> = results
+        .into_iter()
+        .map(|(action, result)| result.map(|_| action))
+        .collect::<Result<_, _>>()
+        .map_err(|err| err.to_string())?;
+
+    if actions
+        != vec![
+            Action::CreateLink(links[0].clone()),
+            Action::ConfigureLink(links[0].clone()),
+            Action::ConfigureLink(links[1].clone()),
+            Action::AddAddress(addresses[0].clone()),
+            Action::AddRoute(routes[0].clone()),
+        ]
+    {
+        return Err(format!("unexpected actions: {:?}", actions));
+    }
+
+    let socket = network.socket();
+
+    let current = socket.links().map_err(|err| err.to_string())?;
+
+    match current.iter().find(|info| info.name == "veth0") {
+        Some(info) if info.up && info.mtu == 1400 && info.kind == "veth" => {}
+        info => return Err(format!("unexpected link: {:?}", info)),
+    }
+
+    let info = network::address_info(&addresses[0], &current).map_err(|err| err.to_string())?;
+
+    if !socket
+        .addresses()
+        .map_err(|err| err.to_string())?
+        .contains(&info)
+    {
+        return Err(format!("address not assigned: {:?}", info));
+    }
+
+    let info = network::route_info(&routes[0], &current).map_err(|err| err.to_string())?;
+
+    if !network::installed(&socket.routes().map_err(|err| err.to_string())?, &info) {
+        return Err(format!("route not installed: {:?}", info));
+    }
+
+    // Converged links, addresses and routes are left alone.
+    let results = network
+        .converge(&links, &addresses, &routes)
+        .map_err(|err| err.to_string())?;
+
+    if !results.is_empty() {
+        return Err(format!("unexpected results: {:?}", results));
+    }
+
+    if !network.created_link("veth0")
+        || network.created_link("veth1")
+        || !network.created_address(&addresses[0])
+        || !network.created_route(&routes[0])
+    {
+        return Err(String::from(
+            "unexpected created links, addresses or routes",
+        ));
+    }
+
+    // After a restart, what was created before is adopted, and is still removed.
+    let mut network = Network::new().map_err(|err| err.to_string())?;
+
+    network.adopt(&links[..1], &addresses, &routes);
+
+    // Routes, addresses and links that are no longer desired are removed in that order.
+    let results = network
+        .converge(&[], &[], &[])
+        .map_err(|err| err.to_string())?;
+
+    let actions: Vec<Action> = results.into_iter().map(|(action, _)| action).collect();
+
+    if actions
+        != vec![
+            Action::DeleteRoute(routes[0].clone()),
+            Action::DeleteAddress(addresses[0].clone()),
+            Action::DeleteLink(String::from("veth0")),
+        ]
+    {
+        return Err(format!("unexpected actions: {:?}", actions));
+    }
+
+    let current = network.socket().links().map_err(|err| err.to_string())?;
+
+    if current.iter().any(|info| info.name.starts_with("veth")) {
+        return Err(format!("unexpected links: {:?}", current));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn network_converge() {
+    in_namespace(CloneFlags::CLONE_NEWNET, converge);
+}
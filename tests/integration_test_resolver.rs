@@ -0,0 +1,125 @@
+mod common;
+
+use common::setup;
+use cosi::resource::TypedResource;
+use cosi::spec::engine::{Resolver, ResolverOptions};
+use cosi::unix::resolver::{self, ResolverError};
+use std::fs;
+
+fn resolver(nameservers: &[&str], search: &str, options: ResolverOptions) -> Resolver {
+    Resolver {
+        nameserver: nameservers.iter().map(|n| n.to_string()).collect(),
+        search: search.to_owned(),
+        sortlist: vec![],
+        options: vec![options],
+    }
+}
+
+#[test]
+fn resolver_render() {
+    // Resolvers are merged in the order of their IDs, regardless of the order they are listed in.
+    let resolvers = vec![
+        TypedResource::new(
+            "b-dhcp",
+            resolver(
+                &["10.0.0.1", "2001:db8::1"],
+                "example.com",
+                ResolverOptions {
+                    ndots: 2,
+                    timeout: 3,
+                    rotate: true,
+                    ..Default::default()
+                },
+            ),
+        ),
+        TypedResource::new(
+            "a-static",
+            resolver(
+                &["1.1.1.1", "10.0.0.1"],
+                "corp.example.com example.com",
+                ResolverOptions {
+                    ndots: 5,
+                    edns0: true,
+                    trust_ad: true,
+                    ..Default::default()
+                },
+            ),
+        ),
+    ];
+
+    let merged = resolver::merge(&resolvers);
+
+    assert_eq!(
+        merged.nameserver,
+        vec!["1.1.1.1", "10.0.0.1", "2001:db8::1"]
+    );
+    assert_eq!(merged.search, "corp.example.com example.com");
+
+    resolver::validate(&merged).unwrap();
+
+    assert_eq!(
+        resolver::render(&merged),
+        "nameserver 1.1.1.1\n\
+         nameserver 10.0.0.1\n\
+         nameserver 2001:db8::1\n\
+         search corp.example.com example.com\n\
+         options ndots:2 timeout:3 rotate edns0 trust-ad\n"
+    );
+
+    let mut reversed = resolvers;
+    reversed.reverse();
+
+    assert_eq!(resolver::merge(&reversed), merged);
+}
+
+#[test]
+fn resolver_validate() {
+    let mut conf = resolver(&["fe80::1%eth0", "192.0.2.1"], "", Default::default());
+
+    conf.sortlist = vec![String::from("130.155.160.0/255.255.240.0")];
+
+    resolver::validate(&conf).unwrap();
+
+    // Options that are all unset are not rendered.
+    assert_eq!(
+        resolver::render(&conf),
+        "nameserver fe80::1%eth0\n\
+         nameserver 192.0.2.1\n\
+         sortlist 130.155.160.0/255.255.240.0\n"
+    );
+
+    conf.sortlist = vec![String::from("2001:db8::/32")];
+
+    assert_eq!(
+        resolver::validate(&conf),
+        Err(ResolverError::InvalidSortlist(String::from(
+            "2001:db8::/32"
+        )))
+    );
+
+    let conf = resolver(&["ns1.example.com"], "", Default::default());
+
+    assert_eq!(
+        resolver::validate(&conf),
+        Err(ResolverError::InvalidNameserver(String::from(
+            "ns1.example.com"
+        )))
+    );
+}
+
+#[test]
+fn resolver_write() {
+    let path = setup().unwrap().join("resolv.conf");
+
+    std::env::set_var(resolver::RESOLV_CONF_ENV, &path);
+
+    assert_eq!(resolver::path(), path);
+
+    cosi::unix::write_atomically(resolver::path(), b"nameserver 1.1.1.1\n").unwrap();
+    cosi::unix::write_atomically(resolver::path(), b"nameserver 8.8.8.8\n").unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "nameserver 8.8.8.8\n");
+
+    // No temporary files are left behind.
+    assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+}
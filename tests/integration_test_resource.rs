@@ -0,0 +1,113 @@
+use cosi::resource::{ResourceError, ResourceKind, TypedResource};
+use cosi::spec::engine::{KernelParameter, Mount, Resolver};
+use cosi::spec::resource::{Metadata, Resource, Spec};
+use prost::Message;
+
+fn wire(r#type: &str, spec: Spec) -> Resource {
+    Resource {
+        metadata: Some(Metadata {
+            namespace: String::from("system"),
+            r#type: r#type.to_owned(),
+            id: String::from("test"),
+            ..Default::default()
+        }),
+        spec: Some(spec),
+        status: None,
+    }
+}
+
+#[test]
+fn typed_resource_round_trip() {
+    let mount = Mount {
+        source: String::from("tmpfs"),
+        target: String::from("/tmp"),
+        r#type: String::from("tmpfs"),
+        options: vec![String::from("nosuid")],
+        ..Default::default()
+    };
+
+    let resource = TypedResource::new("tmp", mount);
+
+    assert_eq!(resource.metadata.namespace, Mount::NAMESPACE);
+    assert_eq!(resource.metadata.r#type, "Mount");
+
+    let encoded = resource.encode().unwrap();
+
+    assert_eq!(
+        encoded.metadata.as_ref().unwrap().api_version,
+        Mount::VERSION
+    );
+
+    let decoded = TypedResource::<Mount>::decode(encoded).unwrap();
+
+    assert_eq!(decoded, resource);
+}
+
+#[test]
+fn typed_resource_decode() {
+    // Fields that are left out take their default value, as in `examples/resolver.yaml`.
+    let resource = wire(
+        "Resolver",
+        Spec {
+            proto_spec: vec![],
+            yaml_spec: String::from(r#"{"search": "local"}"#),
+        },
+    );
+
+    let resolver = TypedResource::<Resolver>::decode(resource).unwrap();
+
+    assert_eq!(resolver.metadata.api_version, "v1alpha1");
+    assert_eq!(resolver.spec.search, "local");
+    assert!(resolver.spec.nameserver.is_empty());
+
+    let parameter = KernelParameter {
+        key: String::from("net.ipv4.ip_forward"),
+        value: String::from("1"),
+    };
+
+    let mut proto_spec = vec![];
+    parameter.encode(&mut proto_spec).unwrap();
+
+    let resource = wire(
+        "KernelParameter",
+        Spec {
+            proto_spec,
+            yaml_spec: String::new(),
+        },
+    );
+
+    assert_eq!(
+        TypedResource::<KernelParameter>::decode(resource)
+            .unwrap()
+            .spec,
+        parameter
+    );
+
+    let resource = wire("Mount", Spec::default());
+
+    match TypedResource::<Resolver>::decode(resource) {
+        Err(ResourceError::TypeMismatch { expected, found }) => {
+            assert_eq!(expected, "cosi.dev/Resolver");
+            assert_eq!(found, "cosi.dev/Mount");
+        }
+        result => panic!("expected a type mismatch, got {:?}", result),
+    }
+
+    // A type of the same name in another API group is another type.
+    let mut resource = wire("Mount", Spec::default());
+    resource.metadata.as_mut().unwrap().api = String::from("example.com");
+
+    assert!(TypedResource::<Mount>::decode(resource).is_err());
+
+    // Specs in other versions are not decoded as this one.
+    let mut resource = wire("Mount", Spec::default());
+    resource.metadata.as_mut().unwrap().api_version = String::from("v1beta1");
+
+    match TypedResource::<Mount>::decode(resource) {
+        Err(ResourceError::VersionMismatch { expected, found }) => {
+            assert_eq!(expected, "v1alpha1");
+            assert_eq!(found, "v1beta1");
+        }
+        result => panic!("expected a version mismatch, got {:?}", result),
+    }
+}
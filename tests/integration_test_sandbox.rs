@@ -0,0 +1,130 @@
+mod common;
+
+use common::{in_namespace, setup};
+use cosi::unix::process::sandbox::Sandbox;
+use nix::mount::{mount, MsFlags};
+use nix::sched::CloneFlags;
+use std::fs;
+use std::io;
+
+// Returns the value of `field` in `/proc/self/status`.
+fn status(field: &str) -> Result<String, String> {
+    let status = fs::read_to_string("/proc/self/status").map_err(|err| err.to_string())?;
+
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(&format!("{}:", field)))
+        .map(|value| value.trim().to_owned())
+        .ok_or_else(|| format!("no {} in /proc/self/status", field))
+}
+
+#[test]
+fn sandbox_seccomp() {
+    let sandbox = Sandbox {
+        seccomp: Some(vec![String::from("exit_group"), String::from("getpid")]),
+        ..Default::default()
+    }
+    .prepare()
+    .unwrap();
+
+    in_namespace(CloneFlags::empty(), || {
+        sandbox.apply().map_err(|err| err.to_string())?;
+
+        // N.B.: Nothing can be printed once the filter is installed, so the result is only
+        // reported through the exit code.
+        let allowed = unsafe { libc::syscall(libc::SYS_getpid) } > 0;
+        let denied = unsafe { libc::syscall(libc::SYS_getppid) } == -1
+            && io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+
+        unsafe { libc::_exit(!(allowed && denied) as i32) }
+    });
+}
+
+#[test]
+fn sandbox_read_only_root() {
+    let dir = setup().unwrap();
+    let writable = dir.join("writable");
+    let submount = dir.join("submount");
+    let writable_submount = writable.join("submount");
+    let directory = dir.join("run").join("service");
+
+    for path in &[&writable, &submount, &writable_submount] {
+        fs::create_dir(path).unwrap();
+    }
+
+    let sandbox = Sandbox {
+        read_only_root: true,
+        writable_paths: vec![writable.display().to_string()],
+        directories: vec![directory.display().to_string()],
+        ..Default::default()
+    }
+    .prepare()
+    .unwrap();
+
+    assert!(directory.is_dir());
+
+    in_namespace(CloneFlags::CLONE_NEWNS, || {
+        // N.B.: The temporary directory may or may not be on the root mount, so mounts of its own
+        // are made below it.
+        for path in &[&submount, &writable_submount] {
+            mount(
+                Some("tmpfs"),
+                path.as_path(),
+                Some("tmpfs"),
+                MsFlags::empty(),
+                None::<&str>,
+            )
+            .map_err(|err| err.to_string())?;
+        }
+
+        sandbox.apply().map_err(|err| err.to_string())?;
+
+        for path in &[&writable, &writable_submount, &directory] {
+            fs::write(path.join("file"), "written").map_err(|err| err.to_string())?;
+        }
+
+        for path in &[&dir, &submount] {
+            match fs::write(path.join("file"), "written") {
+                Err(err) if err.raw_os_error() == Some(libc::EROFS) => {}
+                result => return Err(format!("unexpected result: {:?}", result)),
+            }
+        }
+
+        Ok(())
+    });
+
+    // N.B.: The sandbox only applies to the child, in a mount namespace of its own.
+    assert_eq!(
+        fs::read_to_string(writable.join("file")).unwrap(),
+        "written"
+    );
+    assert!(!dir.join("file").exists());
+}
+
+#[test]
+fn sandbox_capabilities() {
+    let sandbox = Sandbox {
+        capabilities: Some(vec![String::from("CAP_NET_ADMIN")]),
+        no_new_privs: true,
+        ..Default::default()
+    }
+    .prepare()
+    .unwrap();
+
+    in_namespace(CloneFlags::empty(), || {
+        sandbox.apply().map_err(|err| err.to_string())?;
+
+        // Only `CAP_NET_ADMIN` (12) is left in the bounding set.
+        let bounding =
+            u64::from_str_radix(&status("CapBnd")?, 16).map_err(|err| err.to_string())?;
+
+        if bounding != 1 << 12 {
+            return Err(format!("unexpected bounding set: {:x}", bounding));
+        }
+
+        match status("NoNewPrivs")?.as_str() {
+            "1" => Ok(()),
+            value => Err(format!("unexpected no_new_privs: {}", value)),
+        }
+    });
+}
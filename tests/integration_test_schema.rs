@@ -0,0 +1,287 @@
+mod common;
+
+use common::setup;
+use cosi::bootstrap::{Bootstrap, Tokens};
+use cosi::machinery::plugin;
+use cosi::resource::ResourceKind;
+use cosi::schema::Validator;
+use cosi::spec::engine::{Mount, Schema};
+use cosi::spec::resource::{
+    CreateRequest, GetRequest, ListRequest, Metadata, Resource, Spec, UpdateRequest,
+};
+use cosi::spec::FILE_DESCRIPTOR_SET;
+use serde_json::json;
+use tonic::Code;
+
+#[test]
+fn schema_json() {
+    let validator = Validator::from_json_schema(
+        r##"{
+            "type": "object",
+            "required": ["name", "port"],
+            "additionalProperties": false,
+            "properties": {
+                "name": {"type": "string"},
+                "port": {"type": "integer", "minimum": 1, "maximum": 65535},
+                "protocol": {"enum": ["tcp", "udp"]},
+                "tags": {"type": "array", "items": {"$ref": "#/definitions/tag"}}
+            },
+            "definitions": {
+                "tag": {"type": "string"}
+            }
+        }"##,
+    )
+    .unwrap();
+
+    assert!(validator
+        .validate("spec", &json!({"name": "dns", "port": 53, "tags": ["a"]}))
+        .is_ok());
+
+    let errors = validator
+        .validate(
+            "spec",
+            &json!({"port": 70000, "protocol": "sctp", "tags": [1], "extra": true}),
+        )
+        .unwrap_err();
+
+    let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+
+    assert_eq!(
+        errors,
+        vec![
+            "spec.name: required field is missing",
+            "spec.extra: unknown field",
+            "spec.port: 70000 is out of range",
+            "spec.protocol: \"sctp\" is not one of the allowed values",
+            "spec.tags[0]: expected string, found integer",
+        ]
+    );
+
+    assert!(Validator::from_json_schema(r#"{"pattern": "^a"}"#).is_err());
+    assert!(Validator::from_json_schema(r##"{"$ref": "#/definitions/missing"}"##).is_err());
+}
+
+#[test]
+fn schema_descriptor() {
+    let validator = Validator::from_descriptor_set(FILE_DESCRIPTOR_SET, "engine.Mount").unwrap();
+
+    assert!(validator
+        .validate(
+            "spec",
+            &json!({"source": "tmpfs", "target": "/tmp", "options": []})
+        )
+        .is_ok());
+
+    let errors = validator
+        .validate("spec", &json!({"target": 1, "options": "ro", "flags": 0}))
+        .unwrap_err();
+
+    let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+
+    assert_eq!(
+        errors,
+        vec![
+            "spec.flags: unknown field",
+            "spec.options: expected array, found string",
+            "spec.target: expected string, found integer",
+        ]
+    );
+
+    assert!(Validator::from_descriptor_set(FILE_DESCRIPTOR_SET, "engine.Missing").is_err());
+}
+
+fn mount(yaml_spec: &str) -> Resource {
+    Resource {
+        metadata: Some(Metadata {
+            namespace: String::from("system"),
+            r#type: String::from("Mount"),
+            id: String::from("tmp"),
+            ..Default::default()
+        }),
+        spec: Some(Spec {
+            proto_spec: vec![],
+            yaml_spec: yaml_spec.to_owned(),
+        }),
+        status: None,
+    }
+}
+
+#[tokio::test]
+async fn schema_state() {
+    let dir = setup().unwrap();
+
+    let socket = dir.join("engine.sock").to_str().unwrap().to_owned();
+
+    let s = socket.clone();
+
+    let tokens = Tokens::default();
+    tokens.issue("test", "token");
+
+    tokio::spawn(async {
+        cosi::machinery::engine::v1alpha1::EngineService::new(tokens)
+            .serve(s)
+            .await
+    });
+
+    tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+
+    let bootstrap = Bootstrap {
+        engine_socket: socket.clone(),
+        runtime_socket: String::new(),
+        runtime_address: None,
+        service: String::from("test"),
+        token: String::from("token"),
+    };
+
+    let mut state = cosi::machinery::runtime::client::connect_state(socket)
+        .await
+        .unwrap();
+
+    // Types without a schema are rejected.
+    let status = state
+        .create(CreateRequest {
+            resource: Some(mount("{}")),
+            options: None,
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(status.message(), "unknown resource type: cosi.dev/Mount");
+
+    let invalid = Schema {
+        r#type: String::from("Mount"),
+        ..Default::default()
+    };
+
+    let status = plugin::register_schema(&bootstrap, invalid)
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // A service cannot register a type on behalf of another.
+    let spoofed = Schema {
+        owner: String::from("other"),
+        ..Mount::schema()
+    };
+
+    let status = plugin::register_schema(&bootstrap, spoofed)
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    plugin::register_schema(&bootstrap, Mount::schema())
+        .await
+        .unwrap();
+
+    let status = state
+        .create(CreateRequest {
+            resource: Some(mount("source: tmpfs\ntarget: [/tmp]\n")),
+            options: None,
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(
+        status.message(),
+        "spec.target: expected string, found array"
+    );
+
+    state
+        .create(CreateRequest {
+            resource: Some(mount("source: tmpfs\ntarget: /tmp\ntype: tmpfs\n")),
+            options: None,
+        })
+        .await
+        .unwrap();
+
+    let status = state
+        .update(UpdateRequest {
+            current_version: String::from("1"),
+            new_resource: Some(mount(r#"{"type": 1}"#)),
+            options: None,
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        status.message(),
+        "spec.type: expected string, found integer"
+    );
+
+    state
+        .update(UpdateRequest {
+            current_version: String::from("1"),
+            new_resource: Some(mount(r#"{"target": "/var/tmp"}"#)),
+            options: None,
+        })
+        .await
+        .unwrap();
+
+    let resource = state
+        .get(GetRequest {
+            namespace: String::from("system"),
+            r#type: String::from("Mount"),
+            id: String::from("tmp"),
+            options: None,
+            api: String::new(),
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .resource
+        .unwrap();
+
+    let metadata = resource.metadata.unwrap();
+
+    assert_eq!(metadata.version, "2");
+    assert_eq!(metadata.api, "cosi.dev");
+
+    // A third-party type of the same name coexists with the built-in one.
+    plugin::register_schema(
+        &bootstrap,
+        Schema {
+            api: String::from("example.com"),
+            r#type: String::from("Mount"),
+            json_schema: String::from(r#"{"properties": {"path": {"type": "string"}}}"#),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let mut resource = mount("path: /mnt");
+    resource.metadata.as_mut().unwrap().api = String::from("example.com");
+
+    state
+        .create(CreateRequest {
+            resource: Some(resource),
+            options: None,
+        })
+        .await
+        .unwrap();
+
+    for (api, version) in &[("", "2"), ("example.com", "1")] {
+        let mut stream = state
+            .list(ListRequest {
+                namespace: String::from("system"),
+                r#type: String::from("Mount"),
+                options: None,
+                api: api.to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut versions = vec![];
+
+        while let Some(response) = stream.message().await.unwrap() {
+            versions.push(response.resource.unwrap().metadata.unwrap().version);
+        }
+
+        assert_eq!(versions, vec![version.to_string()]);
+    }
+}
@@ -0,0 +1,201 @@
+use cosi::bootstrap::Tokens;
+use cosi::machinery::state::{Registry, StateService};
+use cosi::resource::ResourceKind;
+use cosi::selector::{Selector, SelectorError};
+use cosi::spec::engine::Mount;
+use cosi::spec::resource::{
+    state_server::State, CreateRequest, EventType, ListOptions, ListRequest, Metadata, Resource,
+    Spec, UpdateRequest, WatchOptions, WatchRequest,
+};
+use futures::StreamExt;
+use std::collections::HashMap;
+use tonic::{Code, Request};
+
+fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn selector() {
+    let boot = labels(&[("tier", "boot"), ("cosi.dev/zone", "a")]);
+    let late = labels(&[("tier", "late"), ("deprecated", "")]);
+    let none = labels(&[]);
+
+    let cases = vec![
+        ("", vec![true, true, true]),
+        ("tier=boot", vec![true, false, false]),
+        ("tier == boot", vec![true, false, false]),
+        ("tier!=boot", vec![false, true, true]),
+        ("tier in (boot, late)", vec![true, true, false]),
+        ("tier notin (boot)", vec![false, true, true]),
+        ("deprecated", vec![false, true, false]),
+        ("!deprecated", vec![true, false, true]),
+        ("tier, !deprecated", vec![true, false, false]),
+        ("cosi.dev/zone in (a,b),tier=boot", vec![true, false, false]),
+    ];
+
+    for (selector, expected) in cases {
+        let parsed = Selector::parse(selector).unwrap();
+
+        let matches: Vec<bool> = [&boot, &late, &none]
+            .iter()
+            .map(|labels| parsed.matches(labels))
+            .collect();
+
+        assert_eq!(matches, expected, "{}", selector);
+
+        // The canonical form parses to the same selector.
+        assert_eq!(Selector::parse(&parsed.to_string()).unwrap(), parsed);
+    }
+
+    for selector in &[
+        "tier in boot",
+        "tier in (boot",
+        "=boot",
+        "tier boot",
+        "tier=boot,",
+        // Multi-byte characters where `in` and `notin` would end.
+        "tier xñ",
+        "tier xxxxñ",
+        "tier ñ",
+    ] {
+        match Selector::parse(selector) {
+            Err(SelectorError::Syntax(_, _)) => {}
+            result => panic!("{}: expected a syntax error, got {:?}", selector, result),
+        }
+    }
+
+    assert_eq!(
+        Selector::parse("Tier/x=boot"),
+        Err(SelectorError::InvalidKey(String::from("Tier/x")))
+    );
+    assert_eq!(
+        Selector::parse("tier=-boot"),
+        Err(SelectorError::InvalidValue(String::from("-boot")))
+    );
+}
+
+fn mount(id: &str, pairs: &[(&str, &str)]) -> Resource {
+    Resource {
+        metadata: Some(Metadata {
+            namespace: String::from("system"),
+            r#type: String::from("Mount"),
+            id: id.to_owned(),
+            labels: labels(pairs),
+            annotations: labels(&[("cosi.dev/source", "/etc/cosi/mounts.yaml")]),
+            ..Default::default()
+        }),
+        spec: Some(Spec::default()),
+        status: None,
+    }
+}
+
+async fn list(state: &StateService, selector: &str) -> Result<Vec<String>, tonic::Status> {
+    let request = ListRequest {
+        namespace: String::from("system"),
+        r#type: String::from("Mount"),
+        options: Some(ListOptions {
+            label_selector: selector.to_owned(),
+        }),
+        api: String::new(),
+    };
+
+    let stream = state.list(Request::new(request)).await?.into_inner();
+
+    Ok(stream
+        .map(|response| response.unwrap().resource.unwrap().metadata.unwrap().id)
+        .collect()
+        .await)
+}
+
+#[tokio::test]
+async fn selector_state() {
+    let registry = Registry::default();
+
+    registry.register(&Mount::schema()).unwrap();
+
+    let state = StateService::new(registry, Tokens::default());
+
+    let status = state
+        .create(Request::new(CreateRequest {
+            resource: Some(mount("invalid", &[("tier", "not valid")])),
+            options: None,
+        }))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let watch = WatchRequest {
+        namespace: String::from("system"),
+        r#type: String::from("Mount"),
+        id: None,
+        options: Some(WatchOptions {
+            bootstrap_contents: true,
+            label_selector: String::from("tier=boot"),
+        }),
+        api: String::new(),
+    };
+
+    let mut events = state
+        .watch(Request::new(watch))
+        .await
+        .unwrap()
+        .into_inner()
+        .map(|response| {
+            let event = response.unwrap().event.unwrap();
+            let id = event.resource.unwrap().metadata.unwrap().id;
+
+            (id, EventType::from_i32(event.event_type).unwrap())
+        });
+
+    for (id, pairs) in &[
+        ("proc", vec![("tier", "boot")]),
+        ("var", vec![("tier", "late")]),
+        ("tmp", vec![]),
+    ] {
+        state
+            .create(Request::new(CreateRequest {
+                resource: Some(mount(id, pairs)),
+                options: None,
+            }))
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(list(&state, "").await.unwrap(), vec!["proc", "tmp", "var"]);
+    assert_eq!(list(&state, "tier=boot").await.unwrap(), vec!["proc"]);
+    assert_eq!(list(&state, "tier").await.unwrap(), vec!["proc", "var"]);
+    assert_eq!(list(&state, "!tier").await.unwrap(), vec!["tmp"]);
+    assert_eq!(
+        list(&state, "tier in (boot, late)").await.unwrap(),
+        vec!["proc", "var"]
+    );
+    assert_eq!(
+        list(&state, "tier in boot").await.unwrap_err().code(),
+        Code::InvalidArgument
+    );
+
+    // A resource enters and leaves the selection of a watcher as its labels change.
+    for (id, pairs) in &[("var", vec![("tier", "boot")]), ("proc", vec![])] {
+        state
+            .update(Request::new(UpdateRequest {
+                current_version: String::from("1"),
+                new_resource: Some(mount(id, pairs)),
+                options: None,
+            }))
+            .await
+            .unwrap();
+    }
+
+    let expected = vec![
+        (String::from("proc"), EventType::Created),
+        (String::from("var"), EventType::Created),
+        (String::from("proc"), EventType::Destroyed),
+    ];
+
+    assert_eq!(events.by_ref().take(3).collect::<Vec<_>>().await, expected);
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use common::{request, tokens};
+use cosi::machinery::state::{Registry, StateService};
+use cosi::resource::{ResourceKind, TypedResource};
+use cosi::spec::engine::{Mount, Schema};
+use cosi::spec::resource::{
+    state_server::State, CreateRequest, GetRequest, Metadata, Resource, ResourceStatus, Spec,
+    UpdateRequest, UpdateStatusOptions, UpdateStatusRequest,
+};
+use serde::Deserialize;
+use tonic::{Code, Request};
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct MountStatus {
+    mounted: bool,
+}
+
+fn mount(yaml_spec: &str) -> Resource {
+    Resource {
+        metadata: Some(Metadata {
+            namespace: String::from("system"),
+            r#type: String::from("Mount"),
+            id: String::from("tmp"),
+            ..Default::default()
+        }),
+        spec: Some(Spec {
+            proto_spec: vec![],
+            yaml_spec: yaml_spec.to_owned(),
+        }),
+        status: None,
+    }
+}
+
+fn status(yaml_status: &str) -> Option<ResourceStatus> {
+    Some(ResourceStatus {
+        proto_status: vec![],
+        yaml_status: yaml_status.to_owned(),
+    })
+}
+
+async fn get(state: &StateService) -> Resource {
+    let request = GetRequest {
+        namespace: String::from("system"),
+        r#type: String::from("Mount"),
+        id: String::from("tmp"),
+        options: None,
+        api: String::new(),
+    };
+
+    state
+        .get(Request::new(request))
+        .await
+        .unwrap()
+        .into_inner()
+        .resource
+        .unwrap()
+}
+
+async fn update_status(
+    state: &StateService,
+    service: &str,
+    owner: &str,
+    current_status_version: &str,
+    yaml_status: &str,
+) -> Result<(), tonic::Status> {
+    let message = UpdateStatusRequest {
+        namespace: String::from("system"),
+        r#type: String::from("Mount"),
+        id: String::from("tmp"),
+        api: String::new(),
+        current_status_version: current_status_version.to_owned(),
+        status: status(yaml_status),
+        options: Some(UpdateStatusOptions {
+            owner: owner.to_owned(),
+        }),
+    };
+
+    state
+        .update_status(request(service, message))
+        .await
+        .map(|_| ())
+}
+
+#[tokio::test]
+async fn status_subresource() {
+    let registry = Registry::default();
+
+    registry.register(&Mount::schema()).unwrap();
+
+    let state = StateService::new(registry.clone(), tokens(&["mount", "other"]));
+
+    let mut resource = mount("target: /tmp");
+    resource.status = status("mounted: true");
+
+    state
+        .create(Request::new(CreateRequest {
+            resource: Some(resource),
+            options: None,
+        }))
+        .await
+        .unwrap();
+
+    // Users cannot set the status, nor can anyone update the status of a type without an owner.
+    assert_eq!(get(&state).await.status, None);
+    assert_eq!(
+        update_status(&state, "", "", "0", "mounted: true")
+            .await
+            .unwrap_err()
+            .code(),
+        Code::FailedPrecondition
+    );
+
+    registry
+        .register(&Schema {
+            owner: String::from("mount"),
+            ..Mount::schema()
+        })
+        .unwrap();
+
+    assert!(registry
+        .register(&Schema {
+            owner: String::from("other"),
+            ..Mount::schema()
+        })
+        .is_err());
+
+    assert_eq!(
+        update_status(&state, "other", "other", "0", "mounted: true")
+            .await
+            .unwrap_err()
+            .code(),
+        Code::PermissionDenied
+    );
+
+    // The owner is the service that makes the request, which cannot claim to be another.
+    assert_eq!(
+        update_status(&state, "other", "mount", "0", "mounted: true")
+            .await
+            .unwrap_err()
+            .code(),
+        Code::PermissionDenied
+    );
+    assert_eq!(
+        update_status(&state, "", "mount", "0", "mounted: true")
+            .await
+            .unwrap_err()
+            .code(),
+        Code::PermissionDenied
+    );
+
+    update_status(&state, "mount", "", "0", "mounted: false")
+        .await
+        .unwrap();
+
+    // The spec and the status are versioned independently.
+    state
+        .update(Request::new(UpdateRequest {
+            current_version: String::from("1"),
+            new_resource: Some(mount("target: /var/tmp")),
+            options: None,
+        }))
+        .await
+        .unwrap();
+
+    update_status(&state, "mount", "mount", "1", "mounted: true")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        update_status(&state, "mount", "mount", "1", "mounted: false")
+            .await
+            .unwrap_err()
+            .code(),
+        Code::FailedPrecondition
+    );
+
+    let resource = TypedResource::<Mount>::decode(get(&state).await).unwrap();
+
+    assert_eq!(resource.metadata.version, "2");
+    assert_eq!(resource.metadata.status_version, "2");
+    assert_eq!(resource.spec.target, "/var/tmp");
+    assert_eq!(
+        resource.decode_status::<MountStatus>().unwrap(),
+        Some(MountStatus { mounted: true })
+    );
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::setup;
+use cosi::unix::sysctl::{self, SysctlError};
+use std::fs;
+
+#[test]
+fn sysctl_path() {
+    let root = std::path::Path::new("/proc/sys");
+
+    assert_eq!(
+        sysctl::path(root, "net.ipv4.ip_forward").unwrap(),
+        root.join("net/ipv4/ip_forward")
+    );
+    assert_eq!(
+        sysctl::path(root, "net/ipv4/conf/eth0.1/forwarding").unwrap(),
+        root.join("net/ipv4/conf/eth0.1/forwarding")
+    );
+
+    for key in &["", "net..ip_forward", "net/../../etc/passwd", "/etc/passwd"] {
+        assert!(
+            matches!(sysctl::path(root, key), Err(SysctlError::InvalidKey(_))),
+            "{}",
+            key
+        );
+    }
+}
+
+#[test]
+fn sysctl_write() {
+    let root = setup().unwrap();
+
+    std::env::set_var(sysctl::PROC_SYS_ENV, &root);
+
+    assert_eq!(sysctl::root(), root);
+
+    fs::create_dir_all(root.join("net/ipv4")).unwrap();
+    fs::write(root.join("net/ipv4/ip_forward"), "0\n").unwrap();
+    fs::write(root.join("net/ipv4/ip_local_port_range"), "32768\t60999\n").unwrap();
+
+    assert_eq!(sysctl::read(&root, "net.ipv4.ip_forward").unwrap(), "0");
+    assert_eq!(
+        sysctl::write(&root, "net.ipv4.ip_forward", "1").unwrap(),
+        "1"
+    );
+    assert_eq!(
+        sysctl::read(&root, "net.ipv4.ip_local_port_range").unwrap(),
+        "32768 60999"
+    );
+    assert_eq!(
+        sysctl::write(&root, "net.ipv4.ip_local_port_range", "1024  65535").unwrap(),
+        "1024 65535"
+    );
+
+    assert!(matches!(
+        sysctl::write(&root, "net.ipv4.missing", "1"),
+        Err(SysctlError::UnknownKey(key)) if key == "net.ipv4.missing"
+    ));
+
+    // Unknown keys are not created.
+    assert!(!root.join("net/ipv4/missing").exists());
+}
@@ -0,0 +1,153 @@
+use cosi::unix::sntp::{self, SntpError};
+use std::net::UdpSocket;
+use std::thread;
+use std::time::Duration;
+
+// The offset of the stand-in server's clock from the local one.
+const OFFSET: i64 = 2_000_000_000;
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+// Starts a stand-in time server that answers a single request with the packet `respond` makes of
+// it, if any, and returns its address.
+fn serve(respond: fn(&[u8]) -> Option<Vec<u8>>) -> String {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = socket.local_addr().unwrap().to_string();
+
+    thread::spawn(move || {
+        let mut request = [0; 48];
+        let (_, peer) = socket.recv_from(&mut request).unwrap();
+
+        if let Some(response) = respond(&request) {
+            socket.send_to(&response, peer).unwrap();
+        }
+    });
+
+    address
+}
+
+// Answers `request` as a stratum 2 server whose clock is ahead by `OFFSET`.
+fn response(request: &[u8]) -> Vec<u8> {
+    let mut response = vec![0; 48];
+
+    // LI 0, VN 4, mode 4 (server).
+    response[0] = 0x24;
+    response[1] = 2;
+    // A root dispersion of 1/16 second.
+    response[10] = 0x10;
+    response[12..16].copy_from_slice(b"GPS\0");
+    response[24..32].copy_from_slice(&request[40..48]);
+    response[32..40].copy_from_slice(&sntp::encode(sntp::now() + OFFSET));
+    response[40..48].copy_from_slice(&sntp::encode(sntp::now() + OFFSET));
+
+    response
+}
+
+#[test]
+fn sntp_timestamps() {
+    let now = sntp::now();
+
+    // Timestamps have a resolution of about 233 picoseconds.
+    assert!((sntp::decode(&sntp::encode(now)) - now).abs() <= 1);
+
+    // Timestamps wrap around on 2036-02-07.
+    let era = 2_085_978_496_000_000_000;
+
+    assert_eq!(sntp::encode(era), [0; 8]);
+    assert_eq!(
+        sntp::decode(&sntp::encode(era + 1_500_000_000)),
+        era + 1_500_000_000
+    );
+    assert_eq!(sntp::decode(&sntp::encode(0)), 0);
+
+    assert_eq!(
+        sntp::resolve("127.0.0.1").unwrap().to_string(),
+        "127.0.0.1:123"
+    );
+    assert_eq!(
+        sntp::resolve("127.0.0.1:1123").unwrap().to_string(),
+        "127.0.0.1:1123"
+    );
+    assert_eq!(sntp::resolve("::1").unwrap().to_string(), "[::1]:123");
+    assert!(matches!(
+        sntp::resolve(""),
+        Err(SntpError::InvalidAddress(_))
+    ));
+}
+
+#[test]
+fn sntp_query() {
+    let address = serve(|request| Some(response(request)));
+
+    let sample = sntp::query(&address, TIMEOUT).unwrap();
+
+    assert!((sample.offset - OFFSET).abs() < 100_000_000);
+    assert!(sample.delay >= 0 && sample.delay < 100_000_000);
+    assert_eq!(sample.stratum, 2);
+    assert_eq!(sample.root_dispersion, 62_500_000);
+    assert!(sample.offset.abs() > sntp::STEP_THRESHOLD);
+
+    // Kiss-o'-death packets carry a code instead of a reference ID.
+    let address = serve(|request| {
+        let mut response = response(request);
+
+        response[1] = 0;
+        response[12..16].copy_from_slice(b"RATE");
+
+        Some(response)
+    });
+
+    match sntp::query(&address, TIMEOUT) {
+        Err(SntpError::KissOfDeath { code, .. }) => assert_eq!(code, "RATE"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    let address = serve(|request| {
+        let mut response = response(request);
+
+        // LI 3 (unsynchronized).
+        response[0] |= 0xc0;
+
+        Some(response)
+    });
+
+    assert!(matches!(
+        sntp::query(&address, TIMEOUT),
+        Err(SntpError::Unsynchronized(_))
+    ));
+
+    let address = serve(|request| {
+        let mut response = response(request);
+
+        // Mode 3 (client).
+        response[0] = 0x23;
+
+        Some(response)
+    });
+
+    assert!(matches!(
+        sntp::query(&address, TIMEOUT),
+        Err(SntpError::InvalidResponse(_))
+    ));
+
+    // Responses to other requests are ignored.
+    let address = serve(|request| {
+        let mut response = response(request);
+
+        response[24] ^= 0xff;
+
+        Some(response)
+    });
+
+    assert!(matches!(
+        sntp::query(&address, TIMEOUT),
+        Err(SntpError::Timeout(_))
+    ));
+
+    let address = serve(|_| None);
+
+    assert!(matches!(
+        sntp::query(&address, TIMEOUT),
+        Err(SntpError::Timeout(_))
+    ));
+}
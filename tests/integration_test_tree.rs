@@ -0,0 +1,68 @@
+use cosi::unix::process::tree;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+#[test]
+fn tree() {
+    tree::subreaper().unwrap();
+
+    let reaper = cosi::unix::process::reaper::new();
+
+    // The shell forks a grandchild and then exits, orphaning it.
+    let mut child = Command::new("/bin/sh")
+        .args(&["-c", "sleep 30 & echo $!; read _; exit 0"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let pid = Pid::from_raw(child.id() as i32);
+
+    reaper.tracker.track("test", pid);
+
+    let mut line = String::new();
+
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+
+    let grandchild = Pid::from_raw(line.trim().parse().unwrap());
+
+    reaper.tracker.refresh().unwrap();
+
+    assert_eq!(reaper.tracker.members("test"), {
+        let mut members = vec![pid, grandchild];
+        members.sort();
+        members
+    });
+
+    // Let the shell exit, the grandchild is reparented to this process.
+    drop(child.stdin.take());
+
+    assert!(child.wait().unwrap().success());
+
+    assert!(reaper.tracker.forget(pid).unwrap().main);
+
+    let origin = reaper.tracker.origin(grandchild).unwrap();
+
+    assert_eq!(origin.service, "test");
+    assert!(!origin.main);
+
+    // Stopping the service kills the orphan as well.
+    assert_eq!(
+        reaper.tracker.kill("test", Signal::SIGKILL).unwrap(),
+        vec![grandchild]
+    );
+
+    let mut exits = vec![];
+
+    while exits.is_empty() {
+        exits = reaper.reap(grandchild).unwrap();
+    }
+
+    assert_eq!(exits[0].pid, grandchild);
+    assert_eq!(exits[0].signal, Some(Signal::SIGKILL));
+    assert!(reaper.tracker.forget(grandchild).is_some());
+}
@@ -1,8 +1,10 @@
+use cosi::unix::process::reaper::ChildState;
+use nix::sys::signal::Signal;
 use nix::unistd::{fork, ForkResult, Pid};
 
 #[tokio::test]
 async fn reaper() {
-    let (tx, reaper) = cosi::unix::process::reaper::new();
+    let reaper = cosi::unix::process::reaper::new();
 
     let mut children: Vec<procfs::process::Process> = vec![];
 
@@ -46,10 +48,18 @@ async fn reaper() {
                             std::process::exit(0);
                         },
                         || {
-                            match reaper.reap(Pid::from_raw(-1), &tx) {
-                                Ok(pids) => {
+                            match reaper.reap(Pid::from_raw(-1)) {
+                                Ok(exits) => {
+                                    for exit in &exits {
+                                        assert_eq!(exit.state, ChildState::Signaled);
+                                        assert_eq!(exit.signal, Some(Signal::SIGKILL));
+                                        assert_eq!(exit.code, None);
+                                    }
+
                                     children.retain(|process| {
-                                        !pids.contains(&Pid::from_raw(process.pid()))
+                                        !exits
+                                            .iter()
+                                            .any(|exit| exit.pid == Pid::from_raw(process.pid()))
                                     });
                                 }
                                 Err(err) => panic!("{:?}", err),
@@ -0,0 +1,46 @@
+use cosi::unix::process::reaper::{self, ChildState};
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn watch() {
+    let reaper = reaper::new();
+
+    // Two services run at once, and only one of them exits.
+    let (mut long, long_exits) = reaper
+        .spawn(|| Command::new("/bin/sleep").arg("30").spawn())
+        .unwrap();
+
+    let (short, short_exits) = reaper
+        .spawn(|| Command::new("/bin/sh").args(["-c", "exit 3"]).spawn())
+        .unwrap();
+
+    let reaping = reaper.clone();
+
+    thread::spawn(move || loop {
+        reaping.reap(Pid::from_raw(-1)).unwrap();
+
+        thread::sleep(Duration::from_millis(10));
+    });
+
+    let exit = short_exits.recv_timeout(TIMEOUT).unwrap();
+
+    assert_eq!(exit.pid, Pid::from_raw(short.id() as i32));
+    assert_eq!(exit.state, ChildState::Exited);
+    assert_eq!(exit.code, Some(3));
+
+    // The exit of one service is not taken by the watcher of the other.
+    assert!(long_exits.try_recv().is_err());
+
+    long.kill().unwrap();
+
+    let exit = long_exits.recv_timeout(TIMEOUT).unwrap();
+
+    assert_eq!(exit.pid, Pid::from_raw(long.id() as i32));
+    assert_eq!(exit.signal, Some(Signal::SIGKILL));
+}